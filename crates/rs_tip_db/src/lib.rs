@@ -0,0 +1,170 @@
+//! An incremental compilation database for editor integrations.
+//!
+//! The database holds the source of a single file and answers queries about
+//! it, memoizing every result along with the queries it was computed from.
+//! When the source changes, only the queries whose inputs changed are
+//! computed again: editing the body of one function re-parses and re-resolves
+//! that function alone. Type inference is the exception, as it is not
+//! memoized per function: any change to a syntax tree infers the whole
+//! program again. Imports are not followed.
+//!
+//! ```
+//! use rs_tip_db::Database;
+//!
+//! let mut db = Database::new();
+//! db.set_source("id(x) { return x; } main() { return id(1); }");
+//! assert_eq!(db.fun_ty(&db.functions()[0]).as_deref(), Some("(int) -> int"));
+//! assert!(db.diagnostics().is_empty());
+//! ```
+
+use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+
+use rs_tip_errors::{Diagnostic, FileId, Span};
+use rs_tip_syntax::symbol::Symbol;
+
+use crate::{
+    queries::{
+        Dep, Duplicates, FunIds, FunTy, InferProg, Item, Items, ParseFun, ResolveFun, Storages,
+    },
+    runtime::Revision,
+};
+
+mod queries;
+mod runtime;
+
+#[cfg(test)]
+mod tests;
+
+/// Identifies a function by its name and, among functions with the same
+/// name, by how many of them come before it.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct FunId {
+    pub name: Symbol,
+    pub index: usize,
+}
+
+impl fmt::Display for FunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.index {
+            0 => write!(f, "{}", self.name),
+            index => write!(f, "{}#{}", self.name, index),
+        }
+    }
+}
+
+impl fmt::Debug for FunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+pub struct Database {
+    revision: Revision,
+    source: Rc<str>,
+    source_changed_at: Revision,
+    storages: Storages,
+    /// The function parsed as every file after the first, which is the
+    /// source itself, and the reverse.
+    files: RefCell<(Vec<FunId>, HashMap<FunId, FileId>)>,
+    /// The dependencies of the queries being executed, innermost last.
+    active: RefCell<Vec<Vec<Dep>>>,
+    /// The queries executed since the last call to `take_executed`.
+    executed: RefCell<Vec<String>>,
+}
+
+impl Default for Database {
+    fn default() -> Database {
+        Database {
+            revision: 0,
+            source: "".into(),
+            source_changed_at: 0,
+            storages: Storages::default(),
+            files: RefCell::default(),
+            active: RefCell::default(),
+            executed: RefCell::default(),
+        }
+    }
+}
+
+impl Database {
+    pub fn new() -> Database {
+        Database::default()
+    }
+
+    pub fn set_source(&mut self, source: &str) {
+        self.revision += 1;
+        self.source = source.into();
+        self.source_changed_at = self.revision;
+    }
+
+    pub fn source(&self) -> Rc<str> {
+        if let Some(deps) = self.active.borrow_mut().last_mut() {
+            deps.push(Dep::Source);
+        }
+        self.source.clone()
+    }
+
+    /// Returns the functions of the source, in order.
+    pub fn functions(&self) -> Vec<FunId> {
+        self.fetch::<FunIds>(&())
+            .iter()
+            .filter(|id| self.fetch::<ParseFun>(id).fun.is_some())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the inferred type of a function, e.g. `(int) -> int`.
+    pub fn fun_ty(&self, id: &FunId) -> Option<String> {
+        self.fetch::<FunTy>(id).map(|ty| ty.to_string())
+    }
+
+    /// Returns the syntax, name resolution and type errors of the source.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let items = self.fetch::<Items>(&());
+        let mut diagnostics = Vec::new();
+        for item in items.iter() {
+            diagnostics.extend(self.fetch::<ParseFun>(&item.id).diagnostics.iter().cloned());
+        }
+        diagnostics.extend(self.fetch::<Duplicates>(&()).iter().cloned());
+        for item in items.iter() {
+            diagnostics.extend(self.fetch::<ResolveFun>(&item.id).iter().cloned());
+        }
+        diagnostics.extend(self.fetch::<InferProg>(&()).diagnostics.iter().cloned());
+        diagnostics
+            .into_iter()
+            .map(|diagnostic| diagnostic.map_spans(|span| self.in_source(&items, span)))
+            .collect()
+    }
+
+    /// Returns the queries executed since the last call, e.g.
+    /// `parse_fun(main)`, to see what an edit caused to be recomputed.
+    pub fn take_executed(&self) -> Vec<String> {
+        std::mem::take(&mut self.executed.borrow_mut())
+    }
+
+    /// Returns the file a function is parsed as. Spans in it count from the
+    /// start of the function, so they do not depend on the text before it.
+    fn fun_file(&self, id: &FunId) -> FileId {
+        let mut files = self.files.borrow_mut();
+        let (ids, files) = &mut *files;
+        *files.entry(id.clone()).or_insert_with(|| {
+            ids.push(id.clone());
+            FileId::from_index(ids.len())
+        })
+    }
+
+    /// Maps a span in the file of a function to the source.
+    fn in_source(&self, items: &[Item], span: Span) -> Span {
+        let files = self.files.borrow();
+        let item = (span.file().index().checked_sub(1))
+            .and_then(|index| files.0.get(index))
+            .and_then(|id| items.iter().find(|item| item.id == *id));
+        match item {
+            Some(item) => {
+                let start = item.range.start;
+                Span::new(span.start() + start, span.end() + start)
+            }
+            None => span,
+        }
+    }
+}
@@ -0,0 +1,446 @@
+//! The queries of `Database`, from the source text down to inferred types.
+//!
+//! - `items`: the top-level items of the source and their ranges.
+//! - `fun_ids`: the functions of the source, from the items.
+//! - `fun_text(f)`: the text of function `f`.
+//! - `parse_fun(f)`: the syntax tree of `f`, from its text.
+//! - `signatures`: the name and arity of every function.
+//! - `globals`: every global, from the items declaring them.
+//! - `duplicates`: functions and globals with the same name, from the
+//!   signatures and the globals.
+//! - `resolve_fun(f)`: name resolution errors in `f`, from its syntax tree,
+//!   the signatures and the globals.
+//! - `infer_prog`: type inference, from every syntax tree.
+//! - `fun_ty(f)`: the type of `f`, from type inference.
+//!
+//! Each function is parsed on its own, as a file of its own, so editing one
+//! function leaves the syntax trees of the others untouched. Name resolution
+//! is per function too and only depends on the names and arities of the
+//! other functions and on the globals. Type inference is whole-program, as
+//! TIP types are monomorphic and callers constrain the types of their
+//! callees, so `infer_prog` runs again whenever any syntax tree changes; the
+//! types of the functions are separate queries so that their readers only run
+//! again when the type they read changed.
+
+use std::{ops::Range, rc::Rc};
+
+use rs_tip_errors::{Diagnostic, Span};
+use rs_tip_syntax::{
+    ast::{AstFun, AstSymbol, IntoSpanned, Prog},
+    resolve::{duplicate_functions, duplicate_globals, resolve_fun},
+    symbol::Symbol,
+};
+use rs_tip_ty::{
+    infer::{infer, InferenceResult},
+    TyCtxt, TyCtxtInner,
+};
+
+use crate::{
+    runtime::{Query, Revision, Storage},
+    Database, FunId,
+};
+
+/// A query read while executing another one.
+#[derive(Clone, Debug)]
+pub(crate) enum Dep {
+    Source,
+    Items,
+    FunIds,
+    FunText(FunId),
+    ParseFun(FunId),
+    Signatures,
+    Globals,
+    Duplicates,
+    ResolveFun(FunId),
+    InferProg,
+    FunTy(FunId),
+}
+
+impl Dep {
+    /// Brings the query up to date and returns the revision its value last
+    /// changed in.
+    pub(crate) fn changed_at(&self, db: &Database) -> Revision {
+        match self {
+            Dep::Source => db.source_changed_at,
+            Dep::Items => db.update::<Items>(&()).1,
+            Dep::FunIds => db.update::<FunIds>(&()).1,
+            Dep::FunText(id) => db.update::<FunText>(id).1,
+            Dep::ParseFun(id) => db.update::<ParseFun>(id).1,
+            Dep::Signatures => db.update::<Signatures>(&()).1,
+            Dep::Globals => db.update::<Globals>(&()).1,
+            Dep::Duplicates => db.update::<Duplicates>(&()).1,
+            Dep::ResolveFun(id) => db.update::<ResolveFun>(id).1,
+            Dep::InferProg => db.update::<InferProg>(&()).1,
+            Dep::FunTy(id) => db.update::<FunTy>(id).1,
+        }
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct Storages {
+    items: Storage<Items>,
+    fun_ids: Storage<FunIds>,
+    fun_text: Storage<FunText>,
+    parse_fun: Storage<ParseFun>,
+    signatures: Storage<Signatures>,
+    globals: Storage<Globals>,
+    duplicates: Storage<Duplicates>,
+    resolve_fun: Storage<ResolveFun>,
+    infer_prog: Storage<InferProg>,
+    fun_ty: Storage<FunTy>,
+}
+
+/// A top-level item of the source, usually a function, else a declaration
+/// of globals.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct Item {
+    pub(crate) id: FunId,
+    pub(crate) range: Range<usize>,
+}
+
+/// The top-level items of the source, in order.
+pub(crate) struct Items;
+
+impl Query for Items {
+    const NAME: &'static str = "items";
+    type Key = ();
+    type Value = Rc<Vec<Item>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.items
+    }
+
+    fn dep(_: &()) -> Dep {
+        Dep::Items
+    }
+
+    fn execute(db: &Database, _: &()) -> Self::Value {
+        let source = db.source();
+        let mut items: Vec<Item> = Vec::new();
+        for range in rs_tip_syntax::item_ranges(&source) {
+            let text = &source[range.clone()];
+            let name_len = text
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(text.len());
+            let name = Symbol::intern(&text[..name_len]);
+            let index = items.iter().filter(|item| item.id.name == name).count();
+            let id = FunId { name, index };
+            items.push(Item { id, range });
+        }
+        Rc::new(items)
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+/// The functions of the source, in order. Unlike `items`, this does not
+/// change when text is inserted between or inside functions.
+pub(crate) struct FunIds;
+
+impl Query for FunIds {
+    const NAME: &'static str = "fun_ids";
+    type Key = ();
+    type Value = Rc<Vec<FunId>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.fun_ids
+    }
+
+    fn dep(_: &()) -> Dep {
+        Dep::FunIds
+    }
+
+    fn execute(db: &Database, _: &()) -> Self::Value {
+        let items = db.fetch::<Items>(&());
+        Rc::new(items.iter().map(|item| item.id.clone()).collect())
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+/// The source text of a function, if the source still has it.
+pub(crate) struct FunText;
+
+impl Query for FunText {
+    const NAME: &'static str = "fun_text";
+    type Key = FunId;
+    type Value = Option<Rc<str>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.fun_text
+    }
+
+    fn dep(id: &FunId) -> Dep {
+        Dep::FunText(id.clone())
+    }
+
+    fn execute(db: &Database, id: &FunId) -> Self::Value {
+        let source = db.source();
+        let items = db.fetch::<Items>(&());
+        let item = items.iter().find(|item| item.id == *id)?;
+        Some(source[item.range.clone()].into())
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+/// The syntax tree of a function along with its syntax errors.
+#[derive(Debug, PartialEq)]
+pub(crate) struct ParsedFun {
+    pub(crate) fun: Option<AstFun>,
+    /// The globals an item declares instead of a function.
+    pub(crate) globals: Vec<AstSymbol>,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+pub(crate) struct ParseFun;
+
+impl Query for ParseFun {
+    const NAME: &'static str = "parse_fun";
+    type Key = FunId;
+    type Value = Rc<ParsedFun>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.parse_fun
+    }
+
+    fn dep(id: &FunId) -> Dep {
+        Dep::ParseFun(id.clone())
+    }
+
+    fn execute(db: &Database, id: &FunId) -> Self::Value {
+        let mut diagnostics = Vec::new();
+        let prog = db.fetch::<FunText>(id).and_then(|text| {
+            rs_tip_syntax::parse_in_file(&text, db.fun_file(id), &mut diagnostics)
+        });
+        let (fun, globals) = match prog {
+            Some(prog) => (prog.node.funs.into_iter().next(), prog.node.globals),
+            None => (None, Vec::new()),
+        };
+        Rc::new(ParsedFun {
+            fun,
+            globals,
+            diagnostics,
+        })
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+/// The name and arity of every function, in source order.
+pub(crate) struct Signatures;
+
+impl Query for Signatures {
+    const NAME: &'static str = "signatures";
+    type Key = ();
+    type Value = Rc<Vec<(AstSymbol, usize)>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.signatures
+    }
+
+    fn dep(_: &()) -> Dep {
+        Dep::Signatures
+    }
+
+    fn execute(db: &Database, _: &()) -> Self::Value {
+        let signatures = db
+            .fetch::<FunIds>(&())
+            .iter()
+            .filter_map(|id| {
+                let parsed = db.fetch::<ParseFun>(id);
+                let fun = parsed.fun.as_ref()?;
+                Some((fun.name.clone(), fun.params.len()))
+            })
+            .collect();
+        Rc::new(signatures)
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+/// Every global, in source order.
+pub(crate) struct Globals;
+
+impl Query for Globals {
+    const NAME: &'static str = "globals";
+    type Key = ();
+    type Value = Rc<Vec<AstSymbol>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.globals
+    }
+
+    fn dep(_: &()) -> Dep {
+        Dep::Globals
+    }
+
+    fn execute(db: &Database, _: &()) -> Self::Value {
+        let globals = db
+            .fetch::<FunIds>(&())
+            .iter()
+            .flat_map(|id| db.fetch::<ParseFun>(id).globals.clone())
+            .collect();
+        Rc::new(globals)
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+/// Functions and globals declared more than once, and globals named like
+/// functions.
+pub(crate) struct Duplicates;
+
+impl Query for Duplicates {
+    const NAME: &'static str = "duplicates";
+    type Key = ();
+    type Value = Rc<Vec<Diagnostic>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.duplicates
+    }
+
+    fn dep(_: &()) -> Dep {
+        Dep::Duplicates
+    }
+
+    fn execute(db: &Database, _: &()) -> Self::Value {
+        let mut diagnostics = Vec::new();
+        let signatures = db.fetch::<Signatures>(&());
+        duplicate_functions(&signatures, &mut diagnostics);
+        duplicate_globals(&db.fetch::<Globals>(&()), &signatures, &mut diagnostics);
+        Rc::new(diagnostics)
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+/// The name resolution errors of a function.
+pub(crate) struct ResolveFun;
+
+impl Query for ResolveFun {
+    const NAME: &'static str = "resolve_fun";
+    type Key = FunId;
+    type Value = Rc<Vec<Diagnostic>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.resolve_fun
+    }
+
+    fn dep(id: &FunId) -> Dep {
+        Dep::ResolveFun(id.clone())
+    }
+
+    fn execute(db: &Database, id: &FunId) -> Self::Value {
+        let mut diagnostics = Vec::new();
+        let parsed = db.fetch::<ParseFun>(id);
+        if let Some(fun) = &parsed.fun {
+            let signatures = db.fetch::<Signatures>(&());
+            let globals = db.fetch::<Globals>(&());
+            resolve_fun(fun, &signatures, &globals, &mut diagnostics);
+        }
+        Rc::new(diagnostics)
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
+
+pub(crate) struct Inferred {
+    /// The types of `result`. Every run of the query interns its types
+    /// afresh, so the types of earlier revisions are dropped with them.
+    pub(crate) tcx: TyCtxtInner,
+    pub(crate) result: InferenceResult,
+    pub(crate) diagnostics: Vec<Diagnostic>,
+}
+
+/// Type inference over the whole program.
+pub(crate) struct InferProg;
+
+impl Query for InferProg {
+    const NAME: &'static str = "infer_prog";
+    type Key = ();
+    type Value = Rc<Inferred>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.infer_prog
+    }
+
+    fn dep(_: &()) -> Dep {
+        Dep::InferProg
+    }
+
+    fn execute(db: &Database, _: &()) -> Self::Value {
+        let funs = db
+            .fetch::<FunIds>(&())
+            .iter()
+            .filter_map(|id| db.fetch::<ParseFun>(id).fun.clone())
+            .collect();
+        let prog = Prog {
+            imports: Vec::new(),
+            globals: db.fetch::<Globals>(&()).to_vec(),
+            funs,
+        }
+        .into_spanned(Span::new(0, 0));
+        let tcx = TyCtxtInner::new();
+        let mut diagnostics = Vec::new();
+        let result = infer(TyCtxt::new(&tcx), &prog, &mut diagnostics);
+        Rc::new(Inferred {
+            tcx,
+            result,
+            diagnostics,
+        })
+    }
+
+    /// Types are only comparable through the queries reading them.
+    fn same(_: &Self::Value, _: &Self::Value) -> bool {
+        false
+    }
+}
+
+/// The type of a function, in TIP notation.
+pub(crate) struct FunTy;
+
+impl Query for FunTy {
+    const NAME: &'static str = "fun_ty";
+    type Key = FunId;
+    type Value = Option<Rc<str>>;
+
+    fn storage(db: &Database) -> &Storage<Self> {
+        &db.storages.fun_ty
+    }
+
+    fn dep(id: &FunId) -> Dep {
+        Dep::FunTy(id.clone())
+    }
+
+    fn execute(db: &Database, id: &FunId) -> Self::Value {
+        let inferred = db.fetch::<InferProg>(&());
+        let ty = *inferred.result.fun_to_ty.get(&id.name)?;
+        Some(
+            inferred
+                .result
+                .display_ty(TyCtxt::new(&inferred.tcx), ty)
+                .into(),
+        )
+    }
+
+    fn same(old: &Self::Value, new: &Self::Value) -> bool {
+        old == new
+    }
+}
@@ -0,0 +1,104 @@
+//! The memoization engine behind `Database`.
+//!
+//! Every query result is stored with the revision it was last verified in,
+//! the revision its value last changed in, and the queries it read while
+//! executing. A result that was not verified in the current revision is
+//! reused if none of the queries it read changed since it was verified;
+//! otherwise the query is executed again. A re-executed query whose value
+//! equals the old one keeps its old `changed_at`, so the queries that read it
+//! are not executed again either.
+
+use std::{cell::RefCell, collections::HashMap, fmt, hash::Hash};
+
+use crate::{queries::Dep, Database};
+
+pub(crate) type Revision = u64;
+
+pub(crate) trait Query: Sized {
+    const NAME: &'static str;
+    type Key: Clone + Eq + Hash + fmt::Debug;
+    type Value: Clone;
+
+    fn storage(db: &Database) -> &Storage<Self>;
+    fn dep(key: &Self::Key) -> Dep;
+    fn execute(db: &Database, key: &Self::Key) -> Self::Value;
+    /// Returns whether a re-executed query produced the same value as before.
+    fn same(old: &Self::Value, new: &Self::Value) -> bool;
+}
+
+struct Memo<V> {
+    value: V,
+    verified_at: Revision,
+    changed_at: Revision,
+    deps: Vec<Dep>,
+}
+
+pub(crate) struct Storage<Q: Query> {
+    memos: RefCell<HashMap<Q::Key, Memo<Q::Value>>>,
+}
+
+impl<Q: Query> Default for Storage<Q> {
+    fn default() -> Storage<Q> {
+        Storage {
+            memos: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl Database {
+    /// Returns the up-to-date value of a query, recording it as a dependency
+    /// of the query being executed.
+    pub(crate) fn fetch<Q: Query>(&self, key: &Q::Key) -> Q::Value {
+        let (value, _) = self.update::<Q>(key);
+        if let Some(deps) = self.active.borrow_mut().last_mut() {
+            deps.push(Q::dep(key));
+        }
+        value
+    }
+
+    /// Brings a query up to date, returning its value and the revision the
+    /// value last changed in.
+    pub(crate) fn update<Q: Query>(&self, key: &Q::Key) -> (Q::Value, Revision) {
+        let memos = &Q::storage(self).memos;
+        let memo = memos
+            .borrow()
+            .get(key)
+            .map(|memo| (memo.verified_at, memo.deps.clone()));
+        if let Some((verified_at, deps)) = memo {
+            if verified_at == self.revision
+                || deps.iter().all(|dep| dep.changed_at(self) <= verified_at)
+            {
+                let mut memos = memos.borrow_mut();
+                let memo = memos.get_mut(key).unwrap();
+                memo.verified_at = self.revision;
+                return (memo.value.clone(), memo.changed_at);
+            }
+        }
+
+        self.executed.borrow_mut().push(describe::<Q>(key));
+        self.active.borrow_mut().push(Vec::new());
+        let value = Q::execute(self, key);
+        let deps = self.active.borrow_mut().pop().unwrap();
+
+        let mut memos = memos.borrow_mut();
+        let changed_at = match memos.get(key) {
+            Some(old) if Q::same(&old.value, &value) => old.changed_at,
+            _ => self.revision,
+        };
+        let memo = Memo {
+            value: value.clone(),
+            verified_at: self.revision,
+            changed_at,
+            deps,
+        };
+        memos.insert(key.clone(), memo);
+        (value, changed_at)
+    }
+}
+
+fn describe<Q: Query>(key: &Q::Key) -> String {
+    match format!("{:?}", key).as_str() {
+        "()" => Q::NAME.to_owned(),
+        key => format!("{}({})", Q::NAME, key),
+    }
+}
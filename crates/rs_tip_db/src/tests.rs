@@ -0,0 +1,307 @@
+use expect_test::{expect, Expect};
+use rs_tip_errors::{Renderer, SourceMap};
+
+use crate::Database;
+
+/// Computes every result of the database, returning the queries executed.
+fn run(db: &Database) -> Vec<String> {
+    db.diagnostics();
+    for id in db.functions() {
+        db.fun_ty(&id);
+    }
+    db.take_executed()
+}
+
+fn check_executed(db: &Database, expect: Expect) {
+    expect.assert_debug_eq(&run(db));
+}
+
+fn check_diagnostics(db: &Database, expect: Expect) {
+    let mut source_map = SourceMap::new();
+    source_map.add_file("main.tip".to_owned(), db.source().to_string());
+    let out: String = db
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| Renderer::new(false).render(diagnostic, &source_map))
+        .collect();
+    expect.assert_eq(&out);
+}
+
+const SOURCE: &str = "\
+inc(n) {
+    return n + 1;
+}
+
+twice(f, x) {
+    return f(f(x));
+}
+
+main() {
+    return twice(inc, 1);
+}
+";
+
+/// Returns a database holding `SOURCE` with every result computed once.
+fn checked_database() -> Database {
+    let mut db = Database::new();
+    db.set_source(SOURCE);
+    run(&db);
+    db
+}
+
+#[test]
+fn initial_run() {
+    let mut db = Database::new();
+    db.set_source(SOURCE);
+    check_executed(
+        &db,
+        expect![[r#"
+        [
+            "items",
+            "parse_fun(inc)",
+            "fun_text(inc)",
+            "parse_fun(twice)",
+            "fun_text(twice)",
+            "parse_fun(main)",
+            "fun_text(main)",
+            "duplicates",
+            "signatures",
+            "fun_ids",
+            "globals",
+            "resolve_fun(inc)",
+            "resolve_fun(twice)",
+            "resolve_fun(main)",
+            "infer_prog",
+            "fun_ty(inc)",
+            "fun_ty(twice)",
+            "fun_ty(main)",
+        ]
+    "#]],
+    );
+}
+
+#[test]
+fn editing_a_body_reparses_only_that_function_but_infers_all() {
+    let mut db = checked_database();
+    db.set_source(&SOURCE.replace("n + 1", "n + 2"));
+    check_executed(
+        &db,
+        expect![[r#"
+        [
+            "items",
+            "fun_text(inc)",
+            "parse_fun(inc)",
+            "fun_text(twice)",
+            "fun_text(main)",
+            "signatures",
+            "globals",
+            "resolve_fun(inc)",
+            "infer_prog",
+            "fun_ty(inc)",
+            "fun_ty(twice)",
+            "fun_ty(main)",
+        ]
+    "#]],
+    );
+}
+
+#[test]
+fn whitespace_between_functions_changes_nothing() {
+    let mut db = checked_database();
+    db.set_source(&SOURCE.replace("}\n\ntwice", "}\n\n\n// twice\ntwice"));
+    check_executed(
+        &db,
+        expect![[r#"
+        [
+            "items",
+            "fun_text(inc)",
+            "fun_text(twice)",
+            "fun_text(main)",
+            "fun_ids",
+        ]
+    "#]],
+    );
+}
+
+#[test]
+fn changing_an_arity_resolves_every_function_again() {
+    let mut db = checked_database();
+    db.set_source(&SOURCE.replace("inc(n)", "inc(n, m)"));
+    check_executed(
+        &db,
+        expect![[r#"
+        [
+            "items",
+            "fun_text(inc)",
+            "parse_fun(inc)",
+            "fun_text(twice)",
+            "fun_text(main)",
+            "fun_ids",
+            "signatures",
+            "duplicates",
+            "globals",
+            "resolve_fun(inc)",
+            "resolve_fun(twice)",
+            "resolve_fun(main)",
+            "infer_prog",
+            "fun_ty(inc)",
+            "fun_ty(twice)",
+            "fun_ty(main)",
+        ]
+    "#]],
+    );
+}
+
+#[test]
+fn reading_a_type_runs_only_the_queries_it_needs() {
+    let mut db = Database::new();
+    db.set_source(SOURCE);
+    let main = db.functions().pop().unwrap();
+    assert_eq!(db.fun_ty(&main).as_deref(), Some("() -> int"));
+    db.take_executed();
+    db.set_source(&SOURCE.replace("twice(inc, 1)", "twice(inc, 2)"));
+    assert_eq!(db.fun_ty(&main).as_deref(), Some("() -> int"));
+    expect![[r#"
+        [
+            "items",
+            "fun_text(inc)",
+            "fun_text(twice)",
+            "fun_text(main)",
+            "parse_fun(main)",
+            "infer_prog",
+            "globals",
+            "fun_ty(main)",
+        ]
+    "#]]
+    .assert_debug_eq(&db.take_executed());
+}
+
+#[test]
+fn diagnostics_follow_edits() {
+    let mut db = Database::new();
+    db.set_source(SOURCE);
+    db.set_source(&SOURCE.replace("n + 1", "m + 1").replace("main", "twice"));
+    check_diagnostics(
+        &db,
+        expect![[r#"
+        error[R0002]: function `twice` is defined more than once
+         --> main.tip:9:1
+          |
+        5 | twice(f, x) {
+          | ----- first defined here
+        ...
+        9 | twice() {
+          | ^^^^^
+        error[R0001]: undeclared identifier `m`
+         --> main.tip:2:12
+          |
+        2 |     return m + 1;
+          |            ^
+        error[T0002]: undefined variable: m
+         --> main.tip:2:12
+          |
+        2 |     return m + 1;
+          |            ^
+        error[T0001]: mismatched types: expected `((α) -> α, α) -> α`, found `() -> β`
+         --> main.tip:9:1
+          |
+        5 | twice(f, x) {
+          | ----- inferred to be `((α) -> α, α) -> α` here
+        ...
+        9 | twice() {
+          | ^^^^^ this has type `() -> β`
+    "#]],
+    );
+    db.set_source(&format!("\n\n{}", SOURCE.replace("n + 1", "m + 1")));
+    check_diagnostics(
+        &db,
+        expect![[r#"
+        error[R0001]: undeclared identifier `m`
+         --> main.tip:4:12
+          |
+        4 |     return m + 1;
+          |            ^
+        error[T0002]: undefined variable: m
+         --> main.tip:4:12
+          |
+        4 |     return m + 1;
+          |            ^
+    "#]],
+    );
+    db.set_source("main() { return 0 }");
+    check_diagnostics(
+        &db,
+        expect![[r#"
+        error[S0003]: unexpected token `}`
+         --> main.tip:1:19
+          |
+        1 | main() { return 0 }
+          |                   ^
+    "#]],
+    );
+}
+
+#[test]
+fn globals() {
+    let mut db = Database::new();
+    let source = "\
+var count;
+
+tick() {
+    count = count + 1;
+    return count;
+}
+
+main() {
+    count = 0;
+    return tick();
+}
+";
+    db.set_source(source);
+    run(&db);
+    check_diagnostics(&db, expect![[r#""#]]);
+
+    // Globals live outside the functions, so editing a body leaves the
+    // other functions resolved.
+    db.set_source(&source.replace("count = 0", "count = 1"));
+    check_executed(
+        &db,
+        expect![[r#"
+        [
+            "items",
+            "fun_text(var)",
+            "fun_text(tick)",
+            "fun_text(main)",
+            "parse_fun(main)",
+            "signatures",
+            "globals",
+            "resolve_fun(main)",
+            "infer_prog",
+            "fun_ty(tick)",
+            "fun_ty(main)",
+        ]
+    "#]],
+    );
+
+    db.set_source(&format!("var tick, count;\n{}", source));
+    check_diagnostics(
+        &db,
+        expect![[r#"
+        error[R0010]: global variable `tick` has the same name as a function
+         --> main.tip:1:5
+          |
+        1 | var tick, count;
+          |     ^^^^
+        ...
+        4 | tick() {
+          | ---- function defined here
+        error[R0004]: variable `count` is declared more than once
+         --> main.tip:2:5
+          |
+        1 | var tick, count;
+          |           ----- first declared here
+        2 | var count;
+          |     ^^^^^
+    "#]],
+    );
+}
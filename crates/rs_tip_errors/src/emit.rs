@@ -0,0 +1,196 @@
+//! Machine-readable diagnostic output.
+//!
+//! Lines are 1-based. JSON Lines columns count UTF-8 bytes, while SARIF
+//! columns count UTF-16 code units as its default `columnKind` requires.
+//! End positions are exclusive in both formats.
+
+use serde_json::{json, Value};
+
+use crate::{Diagnostic, LineCol, Severity, SourceMap, Span};
+
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Serializes diagnostics as JSON Lines, one object per diagnostic.
+pub fn to_json_lines(diagnostics: &[Diagnostic], source_map: &SourceMap) -> String {
+    let mut out = String::new();
+    for diagnostic in diagnostics {
+        let related: Vec<Value> = diagnostic
+            .secondary
+            .iter()
+            .map(|label| {
+                let mut location = json_location(label.span, source_map);
+                location["message"] = json!(label.message);
+                location
+            })
+            .collect();
+        let mut value = json!({
+            "severity": diagnostic.severity.to_string(),
+            "code": diagnostic.code,
+            "message": format!("{:#}", diagnostic.message),
+            "location": json_location(diagnostic.span, source_map),
+            "related": related,
+            "notes": diagnostic.notes,
+            "help": diagnostic.help,
+        });
+        if let Some(label) = &diagnostic.label {
+            value["location"]["message"] = json!(label);
+        }
+        out.push_str(&value.to_string());
+        out.push('\n');
+    }
+    out
+}
+
+fn json_location(span: Span, source_map: &SourceMap) -> Value {
+    let file = source_map.file(span.file());
+    let position = |pos: LineCol| json!({ "line": pos.line, "column": pos.column });
+    json!({
+        "file": file.name(),
+        "start": position(file.line_col(span.start())),
+        "end": position(file.line_col(span.end())),
+    })
+}
+
+/// Serializes diagnostics as a SARIF 2.1.0 log with a single run of the
+/// tool named `tool`.
+pub fn to_sarif(diagnostics: &[Diagnostic], source_map: &SourceMap, tool: &str) -> String {
+    let mut rules: Vec<&str> = diagnostics.iter().filter_map(|d| d.code).collect();
+    rules.sort_unstable();
+    rules.dedup();
+
+    let results: Vec<Value> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            let mut result = json!({
+                "level": sarif_level(diagnostic.severity),
+                "message": { "text": format!("{:#}", diagnostic.message) },
+                "locations": [sarif_location(diagnostic.span, diagnostic.label.as_deref(), source_map)],
+            });
+            if let Some(code) = diagnostic.code {
+                result["ruleId"] = json!(code);
+                result["ruleIndex"] = json!(rules.binary_search(&code).unwrap());
+            }
+            if !diagnostic.secondary.is_empty() {
+                let related: Vec<Value> = diagnostic
+                    .secondary
+                    .iter()
+                    .enumerate()
+                    .map(|(id, label)| {
+                        let mut location =
+                            sarif_location(label.span, Some(&label.message), source_map);
+                        location["id"] = json!(id);
+                        location
+                    })
+                    .collect();
+                result["relatedLocations"] = json!(related);
+            }
+            if !diagnostic.notes.is_empty() || !diagnostic.help.is_empty() {
+                result["properties"] = json!({
+                    "notes": diagnostic.notes,
+                    "help": diagnostic.help,
+                });
+            }
+            result
+        })
+        .collect();
+
+    let rules: Vec<Value> = rules.iter().map(|id| json!({ "id": id })).collect();
+    let log = json!({
+        "$schema": SARIF_SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": { "driver": { "name": tool, "rules": rules } },
+            "columnKind": "utf16CodeUnits",
+            "results": results,
+        }],
+    });
+    let mut out = serde_json::to_string_pretty(&log).unwrap();
+    out.push('\n');
+    out
+}
+
+fn sarif_level(severity: Severity) -> &'static str {
+    match severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    }
+}
+
+fn sarif_location(span: Span, message: Option<&str>, source_map: &SourceMap) -> Value {
+    let file = source_map.file(span.file());
+    let start = file.line_col(span.start());
+    let end = file.line_col(span.end());
+    let mut location = json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": file.name() },
+            "region": {
+                "startLine": start.line,
+                "startColumn": start.column_utf16,
+                "endLine": end.line,
+                "endColumn": end.column_utf16,
+            },
+        },
+    });
+    if let Some(message) = message.filter(|message| !message.is_empty()) {
+        location["message"] = json!({ "text": message });
+    }
+    location
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use expect_test::expect_file;
+
+    fn diagnostics() -> (Vec<Diagnostic>, SourceMap) {
+        let mut source_map = SourceMap::new();
+        let main = source_map.add_file(
+            "main.tip",
+            "main() {\n    var x;\n    x = 1;\n    x = alloc 1;\n    return x;\n}\n",
+        );
+        let lib = source_map.add_file("lib.tip", "id(x) { /* é */ return y; }\n");
+        let diagnostics = vec![
+            Diagnostic::new(
+                anyhow!("mismatched types: expected `int`, found `↑int`"),
+                Span::in_file(main, 39, 46),
+            )
+            .with_code("T0001")
+            .with_label("this has type `↑int`")
+            .with_secondary(Span::in_file(main, 28, 29), "inferred to be `int` here"),
+            Diagnostic::warning(anyhow!("unused parameter `x`"), Span::in_file(lib, 3, 4))
+                .with_help("remove the parameter"),
+            Diagnostic::new(
+                anyhow!("undeclared identifier `y`"),
+                Span::in_file(lib, 24, 25),
+            )
+            .with_note("`y` is not in scope"),
+        ];
+        (diagnostics, source_map)
+    }
+
+    #[test]
+    fn json_lines() {
+        let (diagnostics, source_map) = diagnostics();
+        expect_file!["../tests/golden/diagnostics.jsonl"]
+            .assert_eq(&to_json_lines(&diagnostics, &source_map));
+    }
+
+    #[test]
+    fn sarif() {
+        let (diagnostics, source_map) = diagnostics();
+        let sarif = to_sarif(&diagnostics, &source_map, "rs_tip");
+        let log: Value = serde_json::from_str(&sarif).unwrap();
+        assert_eq!(log["version"], "2.1.0");
+        expect_file!["../tests/golden/diagnostics.sarif"].assert_eq(&sarif);
+    }
+
+    #[test]
+    fn empty() {
+        let source_map = SourceMap::new();
+        assert_eq!(to_json_lines(&[], &source_map), "");
+        let log: Value = serde_json::from_str(&to_sarif(&[], &source_map, "rs_tip")).unwrap();
+        assert_eq!(log["runs"][0]["results"], json!([]));
+    }
+}
@@ -0,0 +1,270 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+
+use crate::{Diagnostic, Severity, Span};
+
+const UNUSED_SUPPRESSION: &str = "W0001";
+
+/// Something the passes of the compiler report their diagnostics to.
+pub trait DiagnosticSink {
+    fn emit(&mut self, diagnostic: Diagnostic);
+}
+
+impl DiagnosticSink for Vec<Diagnostic> {
+    fn emit(&mut self, diagnostic: Diagnostic) {
+        self.push(diagnostic);
+    }
+}
+
+/// A `// tip-allow(code)` comment, which silences warnings with `code`
+/// reported within `scope`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Suppression {
+    pub code: String,
+    /// The span of the comment itself.
+    pub span: Span,
+    /// The statement or function the comment is attached to.
+    pub scope: Span,
+}
+
+impl Suppression {
+    fn covers(&self, diagnostic: &Diagnostic) -> bool {
+        let span = diagnostic.span;
+        diagnostic.code == Some(self.code.as_str())
+            && span.file() == self.scope.file()
+            && self.scope.start() <= span.start()
+            && span.end() <= self.scope.end()
+    }
+}
+
+/// Collects the diagnostics of a compilation.
+///
+/// Identical diagnostics are only kept once, warnings can be promoted to
+/// errors or silenced by code, either everywhere or within the scope of a
+/// `Suppression`, and once `error_limit` errors have been kept further errors
+/// are dropped.
+#[derive(Default)]
+pub struct Handler {
+    diagnostics: Vec<Diagnostic>,
+    seen: HashSet<(Severity, Option<&'static str>, String, Span)>,
+    error_count: usize,
+    warning_count: usize,
+    dropped_errors: usize,
+    error_limit: Option<usize>,
+    promoted: HashSet<String>,
+    silenced: HashSet<String>,
+    /// Suppressions along with whether they have silenced a warning yet.
+    suppressions: Vec<(Suppression, bool)>,
+}
+
+impl Handler {
+    pub fn new() -> Handler {
+        Handler::default()
+    }
+
+    pub fn with_error_limit(mut self, limit: usize) -> Handler {
+        self.error_limit = Some(limit);
+        self
+    }
+
+    /// Reports warnings with `code` as errors.
+    pub fn promote(&mut self, code: impl Into<String>) {
+        self.promoted.insert(code.into());
+    }
+
+    /// Drops warnings with `code`.
+    pub fn silence(&mut self, code: impl Into<String>) {
+        self.silenced.insert(code.into());
+    }
+
+    pub fn suppress(&mut self, suppression: Suppression) {
+        self.suppressions.push((suppression, false));
+    }
+
+    /// Warns about every suppression that has not silenced any warning. Call
+    /// this once all passes have run.
+    pub fn report_unused_suppressions(&mut self) {
+        let unused: Vec<Suppression> = self
+            .suppressions
+            .drain(..)
+            .filter(|(_, used)| !used)
+            .map(|(suppression, _)| suppression)
+            .collect();
+        for suppression in unused {
+            let message = anyhow!("unused suppression of `{}`", suppression.code);
+            let diagnostic = Diagnostic::warning(message, suppression.span)
+                .with_code(UNUSED_SUPPRESSION)
+                .with_label("no warning with this code is reported here")
+                .with_help("remove the comment");
+            self.emit(diagnostic);
+        }
+    }
+
+    pub fn error_count(&self) -> usize {
+        self.error_count
+    }
+
+    pub fn warning_count(&self) -> usize {
+        self.warning_count
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.error_count > 0
+    }
+
+    /// Returns whether errors have been dropped because of the error limit.
+    pub fn limit_reached(&self) -> bool {
+        self.dropped_errors > 0
+    }
+
+    pub fn dropped_errors(&self) -> usize {
+        self.dropped_errors
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+}
+
+impl DiagnosticSink for Handler {
+    fn emit(&mut self, mut diagnostic: Diagnostic) {
+        if let (Severity::Warning, Some(code)) = (diagnostic.severity, diagnostic.code) {
+            if self.silenced.contains(code) {
+                return;
+            }
+            let suppression = self
+                .suppressions
+                .iter_mut()
+                .find(|(suppression, _)| suppression.covers(&diagnostic));
+            if let Some((_, used)) = suppression {
+                *used = true;
+                return;
+            }
+            if self.promoted.contains(code) {
+                diagnostic.severity = Severity::Error;
+            }
+        }
+        let key = (
+            diagnostic.severity,
+            diagnostic.code,
+            format!("{:#}", diagnostic.message),
+            diagnostic.span,
+        );
+        if !self.seen.insert(key) {
+            return;
+        }
+        match diagnostic.severity {
+            Severity::Error if self.error_limit == Some(self.error_count) => {
+                self.dropped_errors += 1;
+                return;
+            }
+            Severity::Error => self.error_count += 1,
+            Severity::Warning => self.warning_count += 1,
+            Severity::Note => {}
+        }
+        self.diagnostics.push(diagnostic);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    fn error(message: &str, start: usize) -> Diagnostic {
+        Diagnostic::new(anyhow!("{}", message), Span::new(start, start + 1))
+    }
+
+    fn warning(code: &'static str, start: usize) -> Diagnostic {
+        Diagnostic::warning(anyhow!("unused"), Span::new(start, start + 1)).with_code(code)
+    }
+
+    #[test]
+    fn counts_and_dedups() {
+        let mut handler = Handler::new();
+        handler.emit(error("a", 0));
+        handler.emit(error("a", 0));
+        handler.emit(error("a", 1));
+        handler.emit(error("b", 0));
+        handler.emit(warning("W0001", 0));
+        handler.emit(warning("W0001", 0));
+        handler.emit(Diagnostic::new(anyhow!("n"), Span::new(0, 1)).with_severity(Severity::Note));
+        assert_eq!(handler.error_count(), 3);
+        assert_eq!(handler.warning_count(), 1);
+        assert_eq!(handler.diagnostics().len(), 5);
+        assert!(handler.has_errors());
+    }
+
+    #[test]
+    fn error_limit() {
+        let mut handler = Handler::new().with_error_limit(2);
+        for i in 0..5 {
+            handler.emit(error("e", i));
+        }
+        handler.emit(warning("W0001", 0));
+        assert_eq!(handler.error_count(), 2);
+        assert_eq!(handler.dropped_errors(), 3);
+        assert!(handler.limit_reached());
+        assert_eq!(handler.warning_count(), 1);
+    }
+
+    #[test]
+    fn promote_and_silence() {
+        let mut handler = Handler::new();
+        handler.promote("W0001");
+        handler.silence("W0002");
+        handler.emit(warning("W0001", 0));
+        handler.emit(warning("W0002", 0));
+        handler.emit(warning("W0003", 0));
+        assert_eq!(handler.error_count(), 1);
+        assert_eq!(handler.warning_count(), 1);
+        let severities: Vec<_> = handler
+            .into_diagnostics()
+            .into_iter()
+            .map(|d| (d.code, d.severity))
+            .collect();
+        assert_eq!(
+            severities,
+            [
+                (Some("W0001"), Severity::Error),
+                (Some("W0003"), Severity::Warning)
+            ]
+        );
+    }
+
+    #[test]
+    fn suppressions() {
+        let mut handler = Handler::new();
+        handler.promote("W0002");
+        for code in ["W0002", "W0003"] {
+            handler.suppress(Suppression {
+                code: code.to_owned(),
+                span: Span::new(0, 20),
+                scope: Span::new(21, 40),
+            });
+        }
+        handler.emit(warning("W0002", 25));
+        handler.emit(warning("W0002", 45));
+        handler.emit(error("e", 25).with_code("W0003"));
+        handler.report_unused_suppressions();
+
+        let diagnostics: Vec<_> = handler
+            .diagnostics()
+            .iter()
+            .map(|d| (d.code, d.severity, d.span.start()))
+            .collect();
+        assert_eq!(
+            diagnostics,
+            [
+                (Some("W0002"), Severity::Error, 45),
+                (Some("W0003"), Severity::Error, 25),
+                (Some("W0001"), Severity::Warning, 0),
+            ]
+        );
+    }
+}
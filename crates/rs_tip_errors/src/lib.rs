@@ -1,17 +1,133 @@
 use std::error::Error;
 use std::fmt;
+use std::sync::Arc;
 
-#[derive(Debug)]
+mod emit;
+mod handler;
+mod registry;
+mod render;
+mod source_map;
+
+pub use emit::{to_json_lines, to_sarif};
+pub use handler::{DiagnosticSink, Handler, Suppression};
+pub use registry::{explain, explanations, Explanation};
+pub use render::Renderer;
+pub use source_map::{FileId, LineCol, SourceFile, SourceMap};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A message attached to a span of source code.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Clone, Debug)]
 pub struct Diagnostic {
-    pub message: anyhow::Error,
+    pub severity: Severity,
+    pub code: Option<&'static str>,
+    /// Shared so that clones, such as those returned by the query database,
+    /// can still be downcast to the error type.
+    pub message: Arc<anyhow::Error>,
     pub span: Span,
+    /// Explains what is wrong at `span`.
+    pub label: Option<String>,
+    /// Other locations that contribute to the problem.
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
 }
 
 impl Diagnostic {
     pub fn new(message: impl Into<anyhow::Error>, span: Span) -> Diagnostic {
         Diagnostic {
-            message: message.into(),
+            severity: Severity::Error,
+            code: None,
+            message: Arc::new(message.into()),
             span,
+            label: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn warning(message: impl Into<anyhow::Error>, span: Span) -> Diagnostic {
+        Diagnostic::new(message, span).with_severity(Severity::Warning)
+    }
+
+    pub fn with_severity(mut self, severity: Severity) -> Diagnostic {
+        self.severity = severity;
+        self
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Diagnostic {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_label(mut self, label: impl Into<String>) -> Diagnostic {
+        self.label = Some(label.into());
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Span, message: impl Into<String>) -> Diagnostic {
+        self.secondary.push(Label {
+            span,
+            message: message.into(),
+        });
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Diagnostic {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Diagnostic {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Moves the diagnostic into `file`, for passes that only see offsets.
+    pub fn with_file(self, file: FileId) -> Diagnostic {
+        self.map_spans(|span| Span::in_file(file, span.start, span.end))
+    }
+
+    /// Replaces the primary and secondary spans by the result of `f`.
+    pub fn map_spans(mut self, mut f: impl FnMut(Span) -> Span) -> Diagnostic {
+        self.span = f(self.span);
+        for label in &mut self.secondary {
+            label.span = f(label.span);
+        }
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    /// Displays the diagnostic as `file:line:column: message`.
+    pub fn display<'a>(&'a self, source_map: &'a SourceMap) -> impl fmt::Display + 'a {
+        DisplayWithSourceMap {
+            diagnostic: self,
+            source_map,
         }
     }
 }
@@ -28,14 +144,100 @@ impl fmt::Display for Diagnostic {
 
 impl Error for Diagnostic {}
 
-#[derive(Debug, PartialEq, Eq)]
+/// Diagnostics are equal when they would be reported the same way.
+impl PartialEq for Diagnostic {
+    fn eq(&self, other: &Diagnostic) -> bool {
+        self.severity == other.severity
+            && self.code == other.code
+            && format!("{:#}", self.message) == format!("{:#}", other.message)
+            && self.span == other.span
+            && self.label == other.label
+            && self.secondary == other.secondary
+            && self.notes == other.notes
+            && self.help == other.help
+    }
+}
+
+struct DisplayWithSourceMap<'a> {
+    diagnostic: &'a Diagnostic,
+    source_map: &'a SourceMap,
+}
+
+impl fmt::Display for DisplayWithSourceMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.source_map.file(self.diagnostic.span.file);
+        let start = file.line_col(self.diagnostic.span.start);
+        writeln!(
+            f,
+            "{}:{}:{}: {:#}",
+            file.name(),
+            start.line,
+            start.column,
+            self.diagnostic.message
+        )
+    }
+}
+
+/// A byte range in one of the files of a `SourceMap`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Span {
+    file: FileId,
     start: usize,
     end: usize,
 }
 
 impl Span {
+    /// Creates a span in the default file, for single-file programs.
     pub fn new(start: usize, end: usize) -> Span {
-        Span { start, end }
+        Span::in_file(FileId::default(), start, end)
+    }
+
+    pub fn in_file(file: FileId, start: usize, end: usize) -> Span {
+        Span { file, start, end }
+    }
+
+    pub fn file(&self) -> FileId {
+        self.file
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+
+    #[test]
+    fn display_with_source_map() {
+        let mut source_map = SourceMap::new();
+        source_map.add_file("lib.tip", "libs() {\n    return x;\n}\n");
+        let main = source_map.add_file("main.tip", "main() {\n    return x;\n}\n");
+
+        let diagnostic = Diagnostic::new(anyhow!("undeclared identifier `x`"), Span::new(20, 21));
+        assert_eq!(diagnostic.to_string(), "20:21: undeclared identifier `x`\n");
+        assert_eq!(
+            diagnostic.display(&source_map).to_string(),
+            "lib.tip:2:12: undeclared identifier `x`\n"
+        );
+
+        let diagnostic = diagnostic.with_file(main);
+        assert_eq!(diagnostic.span.file(), main);
+        assert_eq!(
+            diagnostic.display(&source_map).to_string(),
+            "main.tip:2:12: undeclared identifier `x`\n"
+        );
+    }
+
+    #[test]
+    fn clones_keep_the_error_type() {
+        let diagnostic = Diagnostic::new(fmt::Error, Span::new(0, 1));
+        assert!(diagnostic.clone().message.is::<fmt::Error>());
     }
 }
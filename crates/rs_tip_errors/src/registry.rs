@@ -0,0 +1,443 @@
+//! Long-form explanations of diagnostic codes.
+//!
+//! Codes are grouped by the pass that reports them: `S` for lexing and
+//! parsing, `I` for loading imported files, `R` for name resolution, `T` for
+//! type checking and `W` for warnings about the program that are not tied to
+//! a single pass. A code is never reused for a different error once
+//! published.
+
+use std::fmt;
+
+/// The explanation of a diagnostic code, with a program that triggers it and
+/// the same program fixed.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Explanation {
+    pub code: &'static str,
+    pub title: &'static str,
+    pub description: &'static str,
+    pub failing: &'static str,
+    pub fixed: &'static str,
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.code, self.title)?;
+        writeln!(f)?;
+        writeln!(f, "{}", self.description)?;
+        writeln!(f)?;
+        writeln!(f, "Erroneous code example:")?;
+        writeln!(f)?;
+        for line in self.failing.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "Fixed:")?;
+        writeln!(f)?;
+        for line in self.fixed.lines() {
+            writeln!(f, "    {}", line)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the explanation of `code`, if it is a known diagnostic code.
+pub fn explain(code: &str) -> Option<&'static Explanation> {
+    REGISTRY.iter().find(|explanation| explanation.code == code)
+}
+
+/// Returns every registered explanation, ordered by code.
+pub fn explanations() -> &'static [Explanation] {
+    REGISTRY
+}
+
+static REGISTRY: &[Explanation] = &[
+    Explanation {
+        code: "I0001",
+        title: "imported file cannot be read",
+        description: "\
+An `import` names a file that does not exist or cannot be read. Paths are
+relative to the directory of the file containing the `import`.",
+        failing: "\
+import \"missing.tip\";
+main() {
+    return 0;
+}",
+        fixed: "\
+main() {
+    return 0;
+}",
+    },
+    Explanation {
+        code: "I0002",
+        title: "import cycle",
+        description: "\
+A file imports itself, directly or through the files it imports. Move the
+functions the files share into a file of their own that both import.",
+        failing: "\
+// main.tip
+import \"main.tip\";
+main() {
+    return 0;
+}",
+        fixed: "\
+// main.tip
+main() {
+    return 0;
+}",
+    },
+    Explanation {
+        code: "R0001",
+        title: "undeclared identifier",
+        description: "\
+An identifier was used that is neither a function nor a parameter or local
+variable of the enclosing function. Locals must be declared with `var` at the
+start of the function body.",
+        failing: "\
+main() {
+    x = 1;
+    return x;
+}",
+        fixed: "\
+main() {
+    var x;
+    x = 1;
+    return x;
+}",
+    },
+    Explanation {
+        code: "R0002",
+        title: "function defined more than once",
+        description: "\
+Two functions have the same name. Functions live in a single global namespace
+shared by all imported files, so every function needs a name of its own.",
+        failing: "\
+f() { return 1; }
+f() { return 2; }",
+        fixed: "\
+f() { return 1; }
+g() { return 2; }",
+    },
+    Explanation {
+        code: "R0003",
+        title: "parameter declared more than once",
+        description: "\
+A function lists the same parameter name twice, so it would be ambiguous which
+argument the name refers to.",
+        failing: "\
+add(a, a) {
+    return a + a;
+}",
+        fixed: "\
+add(a, b) {
+    return a + b;
+}",
+    },
+    Explanation {
+        code: "R0004",
+        title: "variable declared more than once",
+        description: "\
+A local variable is declared twice, or has the same name as a parameter of the
+function. Each name may only be declared once per function, and each global
+once per program.",
+        failing: "\
+f(n) {
+    var n;
+    n = 1;
+    return n;
+}",
+        fixed: "\
+f(n) {
+    var m;
+    m = n;
+    return m;
+}",
+    },
+    Explanation {
+        code: "R0005",
+        title: "address of a function taken",
+        description: "\
+`&` can only be applied to parameters and local variables. Functions are
+already values and can be passed around or stored without taking their
+address.",
+        failing: "\
+f() { return 1; }
+main() {
+    var p;
+    p = &f;
+    return 0;
+}",
+        fixed: "\
+f() { return 1; }
+main() {
+    var p;
+    p = f;
+    return p();
+}",
+    },
+    Explanation {
+        code: "R0006",
+        title: "assignment to a function",
+        description: "\
+A function name was used as the target of an assignment. Functions are
+constant; store the value in a local variable instead.",
+        failing: "\
+f() { return 1; }
+main() {
+    f = 2;
+    return 0;
+}",
+        fixed: "\
+f() { return 1; }
+main() {
+    var g;
+    g = 2;
+    return g;
+}",
+    },
+    Explanation {
+        code: "R0007",
+        title: "wrong number of arguments",
+        description: "\
+A function was called directly by name with a different number of arguments
+than it has parameters.",
+        failing: "\
+inc(n) { return n + 1; }
+main() { return inc(1, 2); }",
+        fixed: "\
+inc(n) { return n + 1; }
+main() { return inc(1); }",
+    },
+    Explanation {
+        code: "R0008",
+        title: "`break` or `continue` outside of a loop",
+        description: "\
+A `break` or `continue` statement is not inside the body of a `while` or
+`for` loop, so there is no loop for it to leave or continue.",
+        failing: "\
+main(n) {
+    if (n > 10) {
+        break;
+    }
+    return n;
+}",
+        fixed: "\
+main(n) {
+    while (n > 10) {
+        n = n - 1;
+        if (n == 12) {
+            break;
+        }
+    }
+    return n;
+}",
+    },
+    Explanation {
+        code: "R0009",
+        title: "function can end without returning",
+        description: "\
+Every function returns a value, so every path through its body has to end
+in a `return`. Loops count as possibly not running at all, so a `return`
+inside one does not cover the path that skips the loop.",
+        failing: "\
+sign(n) {
+    if (n > 0) {
+        return 1;
+    } else {
+        if (n < 0) {
+            return -1;
+        }
+    }
+}
+main() { return sign(2); }",
+        fixed: "\
+sign(n) {
+    if (n > 0) {
+        return 1;
+    }
+    if (n < 0) {
+        return -1;
+    }
+    return 0;
+}
+main() { return sign(2); }",
+    },
+    Explanation {
+        code: "R0010",
+        title: "global variable named like a function",
+        description: "\
+Globals and functions share one namespace, so a global cannot take the
+name of a function: every use of the name would be ambiguous.",
+        failing: "\
+var count;
+count() { return 0; }
+main() { return count(); }",
+        fixed: "\
+var total;
+count() { return 0; }
+main() { total = count(); return total; }",
+    },
+    Explanation {
+        code: "S0001",
+        title: "invalid input",
+        description: "\
+The source contains a character that does not start any TIP token, or a block
+comment that is never closed.
+
+The parser reports the same code, as `invalid token`, for a token it cannot
+read. The lexer already rejects such input, so this only appears if the parser
+is driven by a different lexer.",
+        failing: "\
+main() {
+    return 1 % 2;
+}",
+        fixed: "\
+main() {
+    return 1 - 1 / 2 * 2;
+}",
+    },
+    Explanation {
+        code: "S0002",
+        title: "integer literal out of range",
+        description: "\
+Integer literals must fit in a signed 32-bit integer, so the largest literal
+is 2147483647. Negative values are written with unary minus.",
+        failing: "\
+main() {
+    return 2147483648;
+}",
+        fixed: "\
+main() {
+    return 2147483647;
+}",
+    },
+    Explanation {
+        code: "S0003",
+        title: "unexpected token",
+        description: "\
+The parser found a token that cannot appear at this point of the program.
+Common causes are a missing `;`, a missing `return` at the end of a function,
+and `var` declarations after the first statement.",
+        failing: "\
+main() {
+    var x;
+    x = 1
+    return x;
+}",
+        fixed: "\
+main() {
+    var x;
+    x = 1;
+    return x;
+}",
+    },
+    Explanation {
+        code: "S0004",
+        title: "unexpected end of file",
+        description: "\
+The program ended in the middle of a function, usually because of a missing
+closing `}`.",
+        failing: "\
+main() {
+    return 0;",
+        fixed: "\
+main() {
+    return 0;
+}",
+    },
+    Explanation {
+        code: "T0001",
+        title: "mismatched types",
+        description: "\
+A value is used with a type that conflicts with another use of it. TIP infers
+a single type for every variable and expression, so a variable that holds an
+integer cannot later hold a pointer, and only integers can be printed, added
+or used as conditions.",
+        failing: "\
+main() {
+    var x;
+    x = 1;
+    x = alloc 1;
+    return 0;
+}",
+        fixed: "\
+main() {
+    var x, p;
+    x = 1;
+    p = alloc 1;
+    return 0;
+}",
+    },
+    Explanation {
+        code: "T0002",
+        title: "undefined variable during type inference",
+        description: "\
+Type inference met an identifier that is not declared. This is normally
+reported by name resolution as R0001 first; declare the variable to fix both.",
+        failing: "\
+main() {
+    return y;
+}",
+        fixed: "\
+main() {
+    var y;
+    y = 0;
+    return y;
+}",
+    },
+    Explanation {
+        code: "W0001",
+        title: "unused suppression",
+        description: "\
+A `// tip-allow(code)` comment did not silence any warning. Either the
+statement or function that follows it reports no warning with that code, or
+the comment is not directly followed by a statement or function at all.",
+        failing: "\
+// tip-allow(T0001)
+main() {
+    return 0;
+}",
+        fixed: "\
+main() {
+    return 0;
+}",
+    },
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::expect;
+
+    #[test]
+    fn registry_is_sorted_and_unique() {
+        let codes: Vec<&str> = explanations().iter().map(|e| e.code).collect();
+        let mut sorted = codes.clone();
+        sorted.sort_unstable();
+        sorted.dedup();
+        assert_eq!(codes, sorted);
+    }
+
+    #[test]
+    fn explain_code() {
+        assert_eq!(explain("T9999"), None);
+        expect![[r#"
+            S0002: integer literal out of range
+
+            Integer literals must fit in a signed 32-bit integer, so the largest literal
+            is 2147483647. Negative values are written with unary minus.
+
+            Erroneous code example:
+
+                main() {
+                    return 2147483648;
+                }
+
+            Fixed:
+
+                main() {
+                    return 2147483647;
+                }
+        "#]]
+        .assert_eq(&explain("S0002").unwrap().to_string());
+    }
+}
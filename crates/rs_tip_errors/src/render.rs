@@ -0,0 +1,344 @@
+use std::fmt::Write;
+
+use crate::{Diagnostic, FileId, Severity, SourceFile, SourceMap, Span};
+
+const BOLD: &str = "1";
+const RED: &str = "1;31";
+const YELLOW: &str = "1;33";
+const GREEN: &str = "1;32";
+const BLUE: &str = "1;34";
+
+/// Renders diagnostics for a terminal, quoting the offending source lines
+/// with their labels underlined.
+///
+/// ```text
+/// error[T0001]: cannot unify int with pointer
+///  --> main.tip:3:9
+///   |
+/// 2 |     x = 1;
+///   |         - `x` is an int here
+/// 3 |     x = alloc 1;
+///   |         ^^^^^^^ this is a pointer
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Renderer {
+    colour: bool,
+}
+
+struct Mark<'a> {
+    span: Span,
+    message: &'a str,
+    primary: bool,
+}
+
+impl Renderer {
+    pub fn new(colour: bool) -> Renderer {
+        Renderer { colour }
+    }
+
+    pub fn render(&self, diagnostic: &Diagnostic, source_map: &SourceMap) -> String {
+        let mut out = String::new();
+        let severity_style = match diagnostic.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+            Severity::Note => GREEN,
+        };
+        let mut header = diagnostic.severity.to_string();
+        if let Some(code) = diagnostic.code {
+            write!(header, "[{}]", code).unwrap();
+        }
+        writeln!(
+            out,
+            "{}{}",
+            self.paint(&header, severity_style),
+            self.paint(&format!(": {:#}", diagnostic.message), BOLD)
+        )
+        .unwrap();
+
+        let mut marks = vec![Mark {
+            span: diagnostic.span,
+            message: diagnostic.label.as_deref().unwrap_or(""),
+            primary: true,
+        }];
+        marks.extend(diagnostic.secondary.iter().map(|label| Mark {
+            span: label.span,
+            message: &label.message,
+            primary: false,
+        }));
+
+        // Group the marks by file, starting with the primary span's file.
+        let mut files: Vec<FileId> = Vec::new();
+        for mark in &marks {
+            if !files.contains(&mark.span.file()) {
+                files.push(mark.span.file());
+            }
+        }
+        let width = marks
+            .iter()
+            .map(|mark| {
+                let file = source_map.file(mark.span.file());
+                file.line_col(mark.span.start()).line.to_string().len()
+            })
+            .max()
+            .unwrap_or(1);
+        let pad = " ".repeat(width);
+        let gutter = self.paint("|", BLUE);
+
+        for (i, &id) in files.iter().enumerate() {
+            let file = source_map.file(id);
+            let mut file_marks: Vec<&Mark> =
+                marks.iter().filter(|mark| mark.span.file() == id).collect();
+            file_marks.sort_by_key(|mark| (mark.span.start(), !mark.primary));
+
+            let (arrow, first) = if i == 0 {
+                ("-->", file.line_col(marks[0].span.start()))
+            } else {
+                (":::", file.line_col(file_marks[0].span.start()))
+            };
+            writeln!(
+                out,
+                "{}{} {}:{}:{}",
+                pad,
+                self.paint(arrow, BLUE),
+                file.name(),
+                first.line,
+                first.column
+            )
+            .unwrap();
+            writeln!(out, "{} {}", pad, gutter).unwrap();
+
+            let mut previous_line = None;
+            for mark in &file_marks {
+                let line = file.line_col(mark.span.start()).line;
+                if previous_line != Some(line) {
+                    if matches!(previous_line, Some(previous) if line > previous + 1) {
+                        writeln!(out, "{}", self.paint("...", BLUE)).unwrap();
+                    }
+                    let number = format!("{:>width$}", line, width = width);
+                    writeln!(
+                        out,
+                        "{} {} {}",
+                        self.paint(&number, BLUE),
+                        gutter,
+                        file.line(line)
+                    )
+                    .unwrap();
+                    previous_line = Some(line);
+                }
+                self.underline(&mut out, &pad, &gutter, file, mark, severity_style);
+            }
+        }
+
+        if !diagnostic.notes.is_empty() || !diagnostic.help.is_empty() {
+            writeln!(out, "{} {}", pad, gutter).unwrap();
+        }
+        for note in &diagnostic.notes {
+            writeln!(
+                out,
+                "{} {} {}: {}",
+                pad,
+                self.paint("=", BLUE),
+                self.paint("note", BOLD),
+                note
+            )
+            .unwrap();
+        }
+        for help in &diagnostic.help {
+            writeln!(
+                out,
+                "{} {} {}: {}",
+                pad,
+                self.paint("=", BLUE),
+                self.paint("help", BOLD),
+                help
+            )
+            .unwrap();
+        }
+        out
+    }
+
+    fn underline(
+        &self,
+        out: &mut String,
+        pad: &str,
+        gutter: &str,
+        file: &SourceFile,
+        mark: &Mark,
+        severity_style: &str,
+    ) {
+        let start = file.line_col(mark.span.start());
+        let line_range = file.line_range(start.line);
+        let line = file.line(start.line);
+        // Spans starting in the line break, such as on the `\n` of a `\r\n`,
+        // are underlined just past the end of the line, and spans running
+        // past its end up to it.
+        let start_offset = mark.span.start().min(line_range.end);
+        let start_byte = start_offset - line_range.start;
+        let end_byte = mark.span.end().clamp(start_offset, line_range.end) - line_range.start;
+        let indent = line[..start_byte].chars().count();
+        let len = line[start_byte..end_byte].chars().count().max(1);
+        let (symbol, style) = if mark.primary {
+            ("^", severity_style)
+        } else {
+            ("-", BLUE)
+        };
+        let mut underline = symbol.repeat(len);
+        if !mark.message.is_empty() {
+            underline.push(' ');
+            underline.push_str(mark.message);
+        }
+        writeln!(
+            out,
+            "{} {} {}{}",
+            pad,
+            gutter,
+            " ".repeat(indent),
+            self.paint(&underline, style)
+        )
+        .unwrap();
+    }
+
+    fn paint(&self, text: &str, style: &str) -> String {
+        if self.colour {
+            format!("\x1b[{}m{}\x1b[0m", style, text)
+        } else {
+            text.to_owned()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use expect_test::{expect, Expect};
+
+    fn check(diagnostic: &Diagnostic, source_map: &SourceMap, expect: Expect) {
+        expect.assert_eq(&Renderer::new(false).render(diagnostic, source_map));
+    }
+
+    fn source_map() -> (SourceMap, FileId, FileId) {
+        let mut source_map = SourceMap::new();
+        let main = source_map.add_file(
+            "main.tip",
+            "main() {\n    var x;\n    x = 1;\n    output x;\n    x = alloc 1;\n    return x;\n}\n",
+        );
+        let lib = source_map.add_file("lib.tip", "id(x) {\n    return x;\n}\n");
+        (source_map, main, lib)
+    }
+
+    #[test]
+    fn primary_only() {
+        let (source_map, main, _) = source_map();
+        let diagnostic = Diagnostic::new(
+            anyhow!("undeclared identifier `y`"),
+            Span::in_file(main, 49, 50),
+        );
+        check(
+            &diagnostic,
+            &source_map,
+            expect![[r#"
+                error: undeclared identifier `y`
+                 --> main.tip:5:5
+                  |
+                5 |     x = alloc 1;
+                  |     ^
+            "#]],
+        );
+    }
+
+    #[test]
+    fn labels_notes_and_help() {
+        let (source_map, main, lib) = source_map();
+        let diagnostic = Diagnostic::new(
+            anyhow!("cannot unify int with pointer"),
+            Span::in_file(main, 53, 60),
+        )
+        .with_code("T0001")
+        .with_label("this is a pointer")
+        .with_secondary(Span::in_file(main, 28, 29), "`x` is an int here")
+        .with_secondary(Span::in_file(main, 24, 25), "")
+        .with_secondary(Span::in_file(lib, 19, 20), "returned here")
+        .with_note("`x` cannot be both")
+        .with_help("use a different variable");
+        check(
+            &diagnostic,
+            &source_map,
+            expect![[r#"
+                error[T0001]: cannot unify int with pointer
+                 --> main.tip:5:9
+                  |
+                3 |     x = 1;
+                  |     -
+                  |         - `x` is an int here
+                ...
+                5 |     x = alloc 1;
+                  |         ^^^^^^^ this is a pointer
+                 ::: lib.tip:2:12
+                  |
+                2 |     return x;
+                  |            - returned here
+                  |
+                  = note: `x` cannot be both
+                  = help: use a different variable
+            "#]],
+        );
+    }
+
+    #[test]
+    fn spans_in_crlf_line_breaks() {
+        let mut source_map = SourceMap::new();
+        let main = source_map.add_file("main.tip", "main() {\r\n    return x\r\n}\r\n");
+        let at =
+            |start, end| Diagnostic::new(anyhow!("expected `;`"), Span::in_file(main, start, end));
+        // On the `\n`, on the whole line break, and from the `x` past it.
+        check(
+            &at(23, 24),
+            &source_map,
+            expect![[r#"
+            error: expected `;`
+             --> main.tip:2:14
+              |
+            2 |     return x
+              |             ^
+        "#]],
+        );
+        check(
+            &at(22, 24),
+            &source_map,
+            expect![[r#"
+            error: expected `;`
+             --> main.tip:2:13
+              |
+            2 |     return x
+              |             ^
+        "#]],
+        );
+        check(
+            &at(21, 26),
+            &source_map,
+            expect![[r#"
+            error: expected `;`
+             --> main.tip:2:12
+              |
+            2 |     return x
+              |            ^
+        "#]],
+        );
+    }
+
+    #[test]
+    fn warning_with_colour() {
+        let (source_map, main, _) = source_map();
+        let diagnostic = Diagnostic::warning(anyhow!("unused"), Span::in_file(main, 17, 18));
+        let rendered = Renderer::new(true).render(&diagnostic, &source_map);
+        expect![[r#"
+            [1;33mwarning[0m[1m: unused[0m
+             [1;34m-->[0m main.tip:2:9
+              [1;34m|[0m
+            [1;34m2[0m [1;34m|[0m     var x;
+              [1;34m|[0m         [1;33m^[0m
+        "#]]
+        .assert_eq(&rendered);
+    }
+}
@@ -0,0 +1,188 @@
+use std::ops::Range;
+
+/// Identifies a file registered in a `SourceMap`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FileId(u32);
+
+impl FileId {
+    /// Returns the file with the given `index`, as returned by `index`.
+    pub fn from_index(index: usize) -> FileId {
+        FileId(index as u32)
+    }
+
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// A 1-based position in a source file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct LineCol {
+    pub line: usize,
+    /// Column counted in UTF-8 bytes.
+    pub column: usize,
+    /// Column counted in UTF-16 code units, as editors using LSP expect.
+    pub column_utf16: usize,
+}
+
+pub struct SourceFile {
+    id: FileId,
+    name: String,
+    source: String,
+    /// Byte offset of the start of every line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(id: FileId, name: String, source: String) -> SourceFile {
+        let line_starts = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        SourceFile {
+            id,
+            name,
+            source,
+            line_starts,
+        }
+    }
+
+    pub fn id(&self) -> FileId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Converts a byte offset to a line and column. Offsets past the end of
+    /// the file, or inside a multi-byte character, are clamped.
+    pub fn line_col(&self, offset: usize) -> LineCol {
+        let mut offset = offset.min(self.source.len());
+        while !self.source.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let prefix = &self.source[self.line_starts[line]..offset];
+        LineCol {
+            line: line + 1,
+            column: prefix.len() + 1,
+            column_utf16: prefix.encode_utf16().count() + 1,
+        }
+    }
+
+    /// Returns the byte range of a 1-based line, excluding its line break.
+    pub fn line_range(&self, line: usize) -> Range<usize> {
+        let start = self.line_starts[line - 1];
+        let end = self
+            .line_starts
+            .get(line)
+            .map_or(self.source.len(), |&next| next - 1);
+        let end = if self.source[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        start..end
+    }
+
+    /// Returns the text of a 1-based line, excluding its line break.
+    pub fn line(&self, line: usize) -> &str {
+        &self.source[self.line_range(line)]
+    }
+}
+
+/// The set of source files making up a program.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    pub fn add_file(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files
+            .push(SourceFile::new(id, name.into(), source.into()));
+        id
+    }
+
+    pub fn file(&self, id: FileId) -> &SourceFile {
+        &self.files[id.index()]
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &SourceFile> {
+        self.files.iter()
+    }
+
+    pub fn find_file(&self, name: &str) -> Option<FileId> {
+        self.files
+            .iter()
+            .find(|file| file.name == name)
+            .map(|file| file.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lc(line: usize, column: usize, column_utf16: usize) -> LineCol {
+        LineCol {
+            line,
+            column,
+            column_utf16,
+        }
+    }
+
+    #[test]
+    fn line_col() {
+        let mut map = SourceMap::new();
+        let id = map.add_file("main.tip", "main() {\n    return 0;\n}\n");
+        let file = map.file(id);
+        assert_eq!(file.name(), "main.tip");
+        assert_eq!(file.line_count(), 4);
+        assert_eq!(file.line_col(0), lc(1, 1, 1));
+        assert_eq!(file.line_col(8), lc(1, 9, 9));
+        assert_eq!(file.line_col(9), lc(2, 1, 1));
+        assert_eq!(file.line_col(13), lc(2, 5, 5));
+        assert_eq!(file.line_col(23), lc(3, 1, 1));
+        assert_eq!(file.line_col(100), lc(4, 1, 1));
+        assert_eq!(file.line(2), "    return 0;");
+        assert_eq!(file.line(4), "");
+    }
+
+    #[test]
+    fn multi_byte_columns() {
+        let mut map = SourceMap::new();
+        // `é` is two UTF-8 bytes and one UTF-16 unit; `𝔁` is four and two.
+        let id = map.add_file("a.tip", "// é𝔁\r\nx = 1;");
+        let file = map.file(id);
+        assert_eq!(file.line_col(5), lc(1, 6, 5));
+        assert_eq!(file.line_col(9), lc(1, 10, 7));
+        assert_eq!(file.line_col(7), lc(1, 6, 5));
+        assert_eq!(file.line(1), "// é𝔁");
+        assert_eq!(file.line_col(11), lc(2, 1, 1));
+    }
+
+    #[test]
+    fn files() {
+        let mut map = SourceMap::new();
+        let a = map.add_file("a.tip", "");
+        let b = map.add_file("b.tip", "");
+        assert_ne!(a, b);
+        assert_eq!(map.find_file("b.tip"), Some(b));
+        assert_eq!(map.find_file("c.tip"), None);
+        assert_eq!(map.files().count(), 2);
+    }
+}
@@ -0,0 +1,101 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    process::ExitCode,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use rs_tip_errors::{Diagnostic, SourceMap};
+use rs_tip_syntax::format::{format, FormatConfig};
+
+const USAGE: &str = "usage: tipfmt [--check] [--indent N] [--max-width N] [FILE...]
+
+Formats TIP source files in place, or stdin to stdout when no files are given.
+With --check, nothing is written and the exit status is 1 if any input is not
+already formatted.";
+
+struct Options {
+    check: bool,
+    config: FormatConfig,
+    files: Vec<String>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options> {
+    let mut options = Options {
+        check: false,
+        config: FormatConfig::default(),
+        files: Vec::new(),
+    };
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--check" => options.check = true,
+            "--indent" => options.config.indent_width = number(&arg, args.next())?,
+            "--max-width" => options.config.max_width = number(&arg, args.next())?,
+            "-h" | "--help" => bail!("{}", USAGE),
+            _ if arg.starts_with('-') => bail!("unknown option `{}`\n\n{}", arg, USAGE),
+            _ => options.files.push(arg),
+        }
+    }
+    Ok(options)
+}
+
+/// Formats `source`, reporting syntax errors as `name:line:column`.
+fn format_file(name: &str, source: &str, config: &FormatConfig) -> Result<String> {
+    format(source, config).map_err(|err| match err.downcast_ref::<Diagnostic>() {
+        Some(diagnostic) => {
+            let mut source_map = SourceMap::new();
+            source_map.add_file(name, source);
+            anyhow!("{}", diagnostic.display(&source_map).to_string().trim_end())
+        }
+        None => err.context(name.to_owned()),
+    })
+}
+
+fn number(flag: &str, value: Option<String>) -> Result<usize> {
+    let value = value.ok_or_else(|| anyhow!("`{}` expects a number", flag))?;
+    value
+        .parse()
+        .with_context(|| format!("invalid value `{}` for `{}`", value, flag))
+}
+
+/// Formats every input, returning whether they were all already formatted.
+fn run(options: &Options) -> Result<bool> {
+    if options.files.is_empty() {
+        let mut source = String::new();
+        io::stdin().read_to_string(&mut source)?;
+        let formatted = format_file("<stdin>", &source, &options.config)?;
+        if options.check {
+            return Ok(formatted == source);
+        }
+        io::stdout().write_all(formatted.as_bytes())?;
+        return Ok(true);
+    }
+
+    let mut all_formatted = true;
+    for file in &options.files {
+        let source = fs::read_to_string(file).with_context(|| format!("reading {}", file))?;
+        let formatted = format_file(file, &source, &options.config)?;
+        if formatted == source {
+            continue;
+        }
+        all_formatted = false;
+        if options.check {
+            println!("{} is not formatted", file);
+        } else {
+            fs::write(file, formatted).with_context(|| format!("writing {}", file))?;
+        }
+    }
+    Ok(all_formatted || !options.check)
+}
+
+fn main() -> ExitCode {
+    let result = parse_args(std::env::args().skip(1)).and_then(|options| run(&options));
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(err) => {
+            eprintln!("tipfmt: {:#}", err);
+            ExitCode::from(2)
+        }
+    }
+}
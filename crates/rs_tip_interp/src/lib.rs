@@ -0,0 +1,648 @@
+//! An interpreter for TIP programs.
+//!
+//! Programs run from `main`, whose parameters are read from the input before
+//! its body runs. Integers are 32 bits wide and arithmetic wraps around on
+//! overflow. Every call gets a frame of its own holding its parameters and
+//! locals, which `&x` can point into; a pointer into the frame of a call that
+//! has returned is dangling. Globals live in a frame of their own for the whole
+//! run, behind the locals of every call. Reading a variable before it is
+//! assigned is an error, as is any operation on a value of the wrong kind, so
+//! programs need not type check to run. Operands are evaluated from left to
+//! right, all of them before the operation checks their kinds. Records and
+//! arrays are values that assignments copy, and indexing outside an array is an
+//! error. So is reaching the end of a function body that has no final `return`.
+//!
+//! ```
+//! use rs_tip_interp::{Interpreter, Value};
+//!
+//! let prog = rs_tip_syntax::parse("main(n) { output n * 2; return n; }", &mut Vec::new()).unwrap();
+//! let outcome = Interpreter::new(&prog.node).with_input(&[21]).run();
+//! assert_eq!(outcome.output, [42]);
+//! assert_eq!(outcome.result, Ok(Value::Int(21)));
+//! ```
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+};
+
+use rs_tip_errors::Span;
+use rs_tip_syntax::{
+    ast::{AstExp, AstFun, AstStm, BinOp, Exp, Prog, Stm, UnOp},
+    symbol::Symbol,
+};
+use thiserror::Error;
+
+#[cfg(test)]
+mod tests;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Int(i32),
+    Null,
+    Pointer(Location),
+    Fun(Symbol),
+    /// Fields in the order they were written in.
+    Record(Vec<(Symbol, Value)>),
+    Array(Vec<Value>),
+}
+
+impl Value {
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "an integer",
+            Value::Null => "null",
+            Value::Pointer(_) => "a pointer",
+            Value::Fun(_) => "a function",
+            Value::Record(_) => "a record",
+            Value::Array(_) => "an array",
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Null => write!(f, "null"),
+            Value::Pointer(location) => write!(f, "{}", location),
+            Value::Fun(name) => write!(f, "{}", name),
+            Value::Record(fields) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, value)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Array(elements) => {
+                write!(f, "[")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+        }
+    }
+}
+
+/// Where a pointer points. Frames and heap cells are numbered in the order
+/// they were created, so the locations of two runs only differ if the runs
+/// made different calls or allocations.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Location {
+    Var { frame: usize, name: Symbol },
+    Heap(usize),
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Location::Var { frame, name } => write!(f, "&{}#{}", name, frame),
+            Location::Heap(cell) => write!(f, "alloc#{}", cell),
+        }
+    }
+}
+
+#[derive(Error, Clone, Debug, PartialEq, Eq)]
+pub enum RuntimeError {
+    #[error("the program has no `main` function")]
+    NoMain,
+    #[error("undeclared identifier `{0}`")]
+    Undeclared(String),
+    #[error("`{0}` is read before it is assigned")]
+    Uninitialized(String),
+    #[error("expected {expected} but found {found}")]
+    Mismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    #[error("dereference of null")]
+    NullDereference,
+    #[error("dereference of a pointer into the frame of a call that returned")]
+    DanglingPointer,
+    #[error("division by zero")]
+    DivisionByZero,
+    #[error("the record has no field `{0}`")]
+    NoSuchField(String),
+    #[error("index {index} is out of bounds for an array of length {len}")]
+    IndexOutOfBounds { index: i32, len: usize },
+    #[error("function `{name}` takes {expected} argument(s) but {found} were supplied")]
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("function `{0}` ended without returning")]
+    MissingReturn(String),
+    #[error("the input is exhausted")]
+    EndOfInput,
+    #[error("more than {0} calls are active")]
+    StackOverflow(usize),
+    #[error("the program ran out of fuel")]
+    OutOfFuel,
+}
+
+/// A runtime error and the span of the expression or statement that
+/// raised it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Trap {
+    pub error: RuntimeError,
+    pub span: Span,
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}",
+            self.span.start(),
+            self.span.end(),
+            self.error
+        )
+    }
+}
+
+/// What running a program did: the numbers it output, and the value `main`
+/// returned or the error that stopped it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: Vec<i32>,
+    pub result: std::result::Result<Value, Trap>,
+}
+
+/// Calls nested deeper than this stop the program rather than overflow the
+/// interpreter's own stack. Each call takes around 10 KiB of it in debug
+/// builds, and threads other than the main one get 2 MiB by default.
+const MAX_DEPTH: usize = 100;
+
+/// The logical operators treat any value but this as true, like the
+/// comparisons with 0 they desugar to.
+const ZERO: Value = Value::Int(0);
+
+pub struct Interpreter<'p> {
+    funs: HashMap<Symbol, &'p AstFun>,
+    input: VecDeque<i32>,
+    output: Vec<i32>,
+    /// Statements and calls left to run, if limited.
+    fuel: Option<u64>,
+    /// The variables of every frame, or `None` once its call returned. The
+    /// first frame holds the globals.
+    frames: Vec<Option<HashMap<Symbol, Option<Value>>>>,
+    /// The frames of the active calls, innermost last.
+    stack: Vec<usize>,
+    /// Heap cells, which are never `None`; the `Option` lets them share code
+    /// with variables.
+    heap: Vec<Option<Value>>,
+}
+
+/// Traps are boxed to keep the frames of the recursive functions small.
+type Result<T> = std::result::Result<T, Box<Trap>>;
+
+/// Where a statement leaves control.
+enum Flow {
+    /// On to the next statement.
+    Next,
+    /// Out of the innermost loop.
+    Break,
+    /// On to the next iteration of the innermost loop.
+    Continue,
+    /// Out of the function, returning the value.
+    Return(Value),
+}
+
+fn trap<T>(error: RuntimeError, span: Span) -> Result<T> {
+    Err(Box::new(Trap { error, span }))
+}
+
+impl<'p> Interpreter<'p> {
+    pub fn new(prog: &'p Prog) -> Self {
+        let mut funs = HashMap::new();
+        for fun in &prog.funs {
+            funs.entry(fun.name.node).or_insert(fun);
+        }
+        let globals = prog.globals.iter().map(|global| (global.node, None));
+        Interpreter {
+            funs,
+            input: VecDeque::new(),
+            output: Vec::new(),
+            fuel: None,
+            frames: vec![Some(globals.collect())],
+            stack: Vec::new(),
+            heap: Vec::new(),
+        }
+    }
+
+    /// Sets the numbers that `main`'s parameters and `input` read, in order.
+    pub fn with_input(mut self, input: &[i32]) -> Self {
+        self.input = input.iter().copied().collect();
+        self
+    }
+
+    /// Stops the program after it has run `fuel` statements and calls, so
+    /// that programs that loop forever can be run too.
+    pub fn with_fuel(mut self, fuel: u64) -> Self {
+        self.fuel = Some(fuel);
+        self
+    }
+
+    pub fn run(mut self) -> Outcome {
+        let result = self.run_main().map_err(|trap| *trap);
+        Outcome {
+            output: self.output,
+            result,
+        }
+    }
+
+    fn run_main(&mut self) -> Result<Value> {
+        let Some(&main) = self.funs.get(&Symbol::intern("main")) else {
+            return trap(RuntimeError::NoMain, Span::new(0, 0));
+        };
+        let mut args = Vec::new();
+        for _ in &main.params {
+            match self.input.pop_front() {
+                Some(n) => args.push(Value::Int(n)),
+                None => return trap(RuntimeError::EndOfInput, main.span()),
+            }
+        }
+        self.call(main, args, main.span())
+    }
+
+    fn call(&mut self, fun: &'p AstFun, args: Vec<Value>, span: Span) -> Result<Value> {
+        if args.len() != fun.params.len() {
+            let error = RuntimeError::ArityMismatch {
+                name: fun.name.to_string(),
+                expected: fun.params.len(),
+                found: args.len(),
+            };
+            return trap(error, span);
+        }
+        if self.stack.len() == MAX_DEPTH {
+            return trap(RuntimeError::StackOverflow(MAX_DEPTH), span);
+        }
+        self.burn(span)?;
+        let mut vars: HashMap<Symbol, Option<Value>> =
+            fun.vars.iter().map(|var| (var.node, None)).collect();
+        for (param, arg) in fun.params.iter().zip(args) {
+            vars.insert(param.node, Some(arg));
+        }
+        self.frames.push(Some(vars));
+        self.stack.push(self.frames.len() - 1);
+        let value = match (self.block(&fun.stms)?, &fun.return_) {
+            (Flow::Return(value), _) => value,
+            (_, Some(return_)) => self.exp(return_)?,
+            (_, None) => return trap(RuntimeError::MissingReturn(fun.name.to_string()), span),
+        };
+        let frame = self.stack.pop().unwrap();
+        self.frames[frame] = None;
+        Ok(value)
+    }
+
+    fn burn(&mut self, span: Span) -> Result<()> {
+        match &mut self.fuel {
+            Some(0) => trap(RuntimeError::OutOfFuel, span),
+            Some(fuel) => {
+                *fuel -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn block(&mut self, stms: &[AstStm]) -> Result<Flow> {
+        for stm in stms {
+            match self.stm(stm)? {
+                Flow::Next => {}
+                jump => return Ok(jump),
+            }
+        }
+        Ok(Flow::Next)
+    }
+
+    fn stm(&mut self, stm: &AstStm) -> Result<Flow> {
+        let span = stm.span();
+        self.burn(span)?;
+        match &stm.node {
+            Stm::IdentifierAssign(name, exp) => {
+                let value = self.exp(exp)?;
+                let location = self.var(name.node, name.span())?;
+                self.store(location, value, span)?;
+            }
+            Stm::PointerAssign(target, exp) => {
+                let pointer = self.exp(target)?;
+                let value = self.exp(exp)?;
+                let location = as_pointer(pointer, target)?;
+                self.store(location, value, span)?;
+            }
+            Stm::FieldAssign(name, field, exp) => {
+                let value = self.exp(exp)?;
+                let location = self.var(name.node, name.span())?;
+                self.store_field(location, field.node, value, span)?;
+            }
+            Stm::DereferenceFieldAssign(target, field, exp) => {
+                let pointer = self.exp(target)?;
+                let value = self.exp(exp)?;
+                let location = as_pointer(pointer, target)?;
+                self.store_field(location, field.node, value, span)?;
+            }
+            Stm::IndexAssign(name, index, exp) => {
+                let index_value = self.exp(index)?;
+                let value = self.exp(exp)?;
+                let index = as_int(index_value, index)?;
+                let location = self.var(name.node, name.span())?;
+                self.store_index(location, index, value, span)?;
+            }
+            Stm::Output(exp) => {
+                let n = self.int(exp)?;
+                self.output.push(n);
+            }
+            Stm::If(cond, then, else_) => {
+                if self.int(cond)? != 0 {
+                    return self.block(then);
+                } else if let Some(else_) = else_ {
+                    return self.block(else_);
+                }
+            }
+            Stm::While(cond, body) => return self.loop_(cond, None, body, span),
+            Stm::For(init, cond, step, body) => {
+                self.stm(init)?;
+                return self.loop_(cond, Some(step), body, span);
+            }
+            Stm::Break => return Ok(Flow::Break),
+            Stm::Continue => return Ok(Flow::Continue),
+            Stm::Return(exp) => return Ok(Flow::Return(self.exp(exp)?)),
+        }
+        Ok(Flow::Next)
+    }
+
+    /// Runs `body` and then `step` while `cond` holds. Kept out of `stm`,
+    /// whose frame every call nests.
+    #[inline(never)]
+    fn loop_(
+        &mut self,
+        cond: &AstExp,
+        step: Option<&AstStm>,
+        body: &[AstStm],
+        span: Span,
+    ) -> Result<Flow> {
+        while self.int(cond)? != 0 {
+            match self.block(body)? {
+                Flow::Break => break,
+                Flow::Return(value) => return Ok(Flow::Return(value)),
+                Flow::Next | Flow::Continue => {}
+            }
+            if let Some(step) = step {
+                self.stm(step)?;
+            }
+            self.burn(span)?;
+        }
+        Ok(Flow::Next)
+    }
+
+    fn exp(&mut self, exp: &AstExp) -> Result<Value> {
+        let span = exp.span();
+        Ok(match &exp.node {
+            Exp::Int(n) => Value::Int(n.node),
+            Exp::Identifier(name) => match self.frame_of(name.node) {
+                Some(frame) => {
+                    let location = Location::Var {
+                        frame,
+                        name: name.node,
+                    };
+                    self.load(location, span)?
+                }
+                None if self.funs.contains_key(&name.node) => Value::Fun(name.node),
+                None => return trap(RuntimeError::Undeclared(name.to_string()), span),
+            },
+            Exp::Unary(UnOp::Negative, operand) => Value::Int(self.int(operand)?.wrapping_neg()),
+            // Like `a == 0`, so any value can be negated.
+            Exp::Unary(UnOp::Not, operand) => Value::Int((self.exp(operand)? == ZERO) as i32),
+            Exp::Binary(lhs, op @ (BinOp::Equal | BinOp::NotEqual), rhs) => {
+                let lhs = self.exp(lhs)?;
+                let rhs = self.exp(rhs)?;
+                Value::Int(((lhs == rhs) == (*op == BinOp::Equal)) as i32)
+            }
+            Exp::Binary(lhs, op @ (BinOp::And | BinOp::Or), rhs) => {
+                let lhs = self.exp(lhs)? != ZERO;
+                Value::Int(match op {
+                    BinOp::And if !lhs => 0,
+                    BinOp::Or if lhs => 1,
+                    _ => (self.exp(rhs)? != ZERO) as i32,
+                })
+            }
+            Exp::Binary(lhs, op, rhs) => {
+                let (lhs_value, rhs_value) = (self.exp(lhs)?, self.exp(rhs)?);
+                // `a < b` and `a >= b` are `b > a` with the operands swapped,
+                // so the kind of `b` is checked first.
+                let (lhs, rhs) = if matches!(op, BinOp::Less | BinOp::GreaterEqual) {
+                    let rhs = as_int(rhs_value, rhs)?;
+                    (as_int(lhs_value, lhs)?, rhs)
+                } else {
+                    (as_int(lhs_value, lhs)?, as_int(rhs_value, rhs)?)
+                };
+                Value::Int(match op {
+                    BinOp::Add => lhs.wrapping_add(rhs),
+                    BinOp::Subtract => lhs.wrapping_sub(rhs),
+                    BinOp::Multiply => lhs.wrapping_mul(rhs),
+                    BinOp::Divide if rhs == 0 => return trap(RuntimeError::DivisionByZero, span),
+                    BinOp::Divide => lhs.wrapping_div(rhs),
+                    BinOp::Greater => (lhs > rhs) as i32,
+                    BinOp::Less => (lhs < rhs) as i32,
+                    BinOp::LessEqual => (lhs <= rhs) as i32,
+                    BinOp::GreaterEqual => (lhs >= rhs) as i32,
+                    BinOp::Equal | BinOp::NotEqual | BinOp::And | BinOp::Or => unreachable!(),
+                })
+            }
+            Exp::Input => match self.input.pop_front() {
+                Some(n) => Value::Int(n),
+                None => return trap(RuntimeError::EndOfInput, span),
+            },
+            Exp::Call(callee, args) => {
+                let callee_value = self.exp(callee)?;
+                let args = args
+                    .iter()
+                    .map(|arg| self.exp(arg))
+                    .collect::<Result<Vec<_>>>()?;
+                let callee = match callee_value {
+                    Value::Fun(name) => self.funs[&name],
+                    value => return mismatch("a function", &value, callee),
+                };
+                self.call(callee, args, span)?
+            }
+            Exp::Alloc(exp) => {
+                let value = self.exp(exp)?;
+                self.heap.push(Some(value));
+                Value::Pointer(Location::Heap(self.heap.len() - 1))
+            }
+            Exp::Pointer(name) => Value::Pointer(self.var(name.node, span)?),
+            Exp::Dereference(exp) => {
+                let pointer = self.exp(exp)?;
+                let location = as_pointer(pointer, exp)?;
+                self.load(location, span)?
+            }
+            Exp::Null => Value::Null,
+            Exp::Record(fields) => Value::Record(
+                fields
+                    .iter()
+                    .map(|field| Ok((field.name.node, self.exp(&field.value)?)))
+                    .collect::<Result<_>>()?,
+            ),
+            Exp::Field(record, field) => match self.exp(record)? {
+                Value::Record(fields) => {
+                    match fields.into_iter().find(|(name, _)| *name == field.node) {
+                        Some((_, value)) => value,
+                        None => return trap(RuntimeError::NoSuchField(field.to_string()), span),
+                    }
+                }
+                value => return mismatch("a record", &value, record),
+            },
+            Exp::Array(elements) => Value::Array(
+                elements
+                    .iter()
+                    .map(|element| self.exp(element))
+                    .collect::<Result<_>>()?,
+            ),
+            Exp::Index(array, index) => {
+                let array_value = self.exp(array)?;
+                let index_value = self.exp(index)?;
+                let Value::Array(mut elements) = array_value else {
+                    return mismatch("an array", &array_value, array);
+                };
+                let index = as_int(index_value, index)?;
+                let i = bounds_check(index, elements.len(), span)?;
+                elements.swap_remove(i)
+            }
+            Exp::Paren(exp) => self.exp(exp)?,
+        })
+    }
+
+    fn int(&mut self, exp: &AstExp) -> Result<i32> {
+        let value = self.exp(exp)?;
+        as_int(value, exp)
+    }
+
+    /// Returns the frame holding the variable `name` in the current call:
+    /// that of the call if it declares one, else the globals.
+    fn frame_of(&self, name: Symbol) -> Option<usize> {
+        [*self.stack.last().unwrap(), 0]
+            .into_iter()
+            .find(|&frame| self.frames[frame].as_ref().unwrap().contains_key(&name))
+    }
+
+    /// Returns the location of a variable visible in the current call.
+    fn var(&self, name: Symbol, span: Span) -> Result<Location> {
+        match self.frame_of(name) {
+            Some(frame) => Ok(Location::Var { frame, name }),
+            None => trap(RuntimeError::Undeclared(name.to_string()), span),
+        }
+    }
+
+    fn cell(&mut self, location: Location, span: Span) -> Result<&mut Option<Value>> {
+        match location {
+            Location::Var { frame, name } => match &mut self.frames[frame] {
+                Some(vars) => Ok(vars.get_mut(&name).unwrap()),
+                None => trap(RuntimeError::DanglingPointer, span),
+            },
+            Location::Heap(cell) => Ok(&mut self.heap[cell]),
+        }
+    }
+
+    fn load(&mut self, location: Location, span: Span) -> Result<Value> {
+        match self.cell(location, span)? {
+            Some(value) => Ok(value.clone()),
+            None => {
+                let Location::Var { name, .. } = location else {
+                    unreachable!("heap cells are always initialized")
+                };
+                trap(RuntimeError::Uninitialized(name.to_string()), span)
+            }
+        }
+    }
+
+    fn store(&mut self, location: Location, value: Value, span: Span) -> Result<()> {
+        *self.cell(location, span)? = Some(value);
+        Ok(())
+    }
+
+    fn store_field(
+        &mut self,
+        location: Location,
+        field: Symbol,
+        value: Value,
+        span: Span,
+    ) -> Result<()> {
+        let record = self.load(location, span)?;
+        let Value::Record(mut fields) = record else {
+            return trap(
+                RuntimeError::Mismatch {
+                    expected: "a record",
+                    found: record.kind(),
+                },
+                span,
+            );
+        };
+        match fields.iter_mut().find(|(name, _)| *name == field) {
+            Some((_, old)) => *old = value,
+            None => return trap(RuntimeError::NoSuchField(field.to_string()), span),
+        }
+        self.store(location, Value::Record(fields), span)
+    }
+
+    fn store_index(
+        &mut self,
+        location: Location,
+        index: i32,
+        value: Value,
+        span: Span,
+    ) -> Result<()> {
+        let array = self.load(location, span)?;
+        let Value::Array(mut elements) = array else {
+            return trap(
+                RuntimeError::Mismatch {
+                    expected: "an array",
+                    found: array.kind(),
+                },
+                span,
+            );
+        };
+        let i = bounds_check(index, elements.len(), span)?;
+        elements[i] = value;
+        self.store(location, Value::Array(elements), span)
+    }
+}
+
+/// Returns `index` as a position in an array of length `len`, if it is one.
+fn bounds_check(index: i32, len: usize, span: Span) -> Result<usize> {
+    match usize::try_from(index) {
+        Ok(i) if i < len => Ok(i),
+        _ => trap(RuntimeError::IndexOutOfBounds { index, len }, span),
+    }
+}
+
+fn as_int(value: Value, exp: &AstExp) -> Result<i32> {
+    match value {
+        Value::Int(n) => Ok(n),
+        value => mismatch("an integer", &value, exp),
+    }
+}
+
+fn as_pointer(value: Value, exp: &AstExp) -> Result<Location> {
+    match value {
+        Value::Pointer(location) => Ok(location),
+        Value::Null => trap(RuntimeError::NullDereference, exp.span()),
+        value => mismatch("a pointer", &value, exp),
+    }
+}
+
+fn mismatch<T>(expected: &'static str, found: &Value, exp: &AstExp) -> Result<T> {
+    let error = RuntimeError::Mismatch {
+        expected,
+        found: found.kind(),
+    };
+    trap(error, exp.span())
+}
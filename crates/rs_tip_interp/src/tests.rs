@@ -0,0 +1,579 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use expect_test::{expect, Expect};
+use rs_tip_errors::SourceMap;
+use rs_tip_syntax::{
+    ast::{visit, AstExp, AstStm, BinOp, Exp, Prog, Stm, UnOp, Visitor},
+    desugar::desugar,
+    gen::{generate, GenConfig},
+    loader::load,
+    normalize::normalize,
+    parse,
+    printer::print_prog,
+    resolve::resolve,
+};
+
+use crate::{Interpreter, Outcome};
+
+fn parse_ok(source: &str) -> Prog {
+    let mut diagnostics = Vec::new();
+    let prog = parse(source, &mut diagnostics).expect("syntax error");
+    prog.node
+}
+
+fn describe(source: &str, outcome: &Outcome) -> String {
+    let output: Vec<String> = outcome.output.iter().map(i32::to_string).collect();
+    let result = match &outcome.result {
+        Ok(value) => format!("returned {}", value),
+        Err(trap) => {
+            let span = trap.span;
+            format!("{} at `{}`", trap.error, &source[span.start()..span.end()])
+        }
+    };
+    format!("output [{}], {}", output.join(", "), result)
+}
+
+fn check(source: &str, input: &[i32], expect: Expect) {
+    let prog = parse_ok(source);
+    let outcome = Interpreter::new(&prog)
+        .with_input(input)
+        .with_fuel(10_000)
+        .run();
+    expect.assert_eq(&describe(source, &outcome));
+    for (transformed, what) in [
+        (desugar(&prog), "desugaring"),
+        (normalize(&prog).prog, "normalizing"),
+    ] {
+        let transformed_outcome = Interpreter::new(&transformed)
+            .with_input(input)
+            .with_fuel(10_000)
+            .run();
+        assert_eq!(
+            outcome,
+            transformed_outcome,
+            "{} changed the outcome of\n{}",
+            what,
+            print_prog(&transformed)
+        );
+    }
+}
+
+#[test]
+fn iterate() {
+    check(
+        "main(n) { var f; f = 1; while (n > 0) { f = f * n; n = n - 1; } output f; return input; }",
+        &[5, 7],
+        expect![[r#"output [120], returned 7"#]],
+    );
+}
+
+#[test]
+fn arithmetic_wraps() {
+    check(
+        "main() { output 2147483647 + 1; output -(0 - 2147483647 - 1); output 65536 * 65536; output (0 - 2147483647 - 1) / -1; output -7 / 2; return 7 > -7; }",
+        &[],
+        expect![[r#"output [-2147483648, -2147483648, 0, -2147483648, -3], returned 1"#]],
+    );
+}
+
+#[test]
+fn pointers_and_records() {
+    check(
+        r#"
+cons(head, tail) { return alloc {head: head, tail: tail}; }
+sum(list) {
+    var total;
+    total = 0;
+    while (0 == (list == null)) { total = total + (*list).head; list = (*list).tail; }
+    return total;
+}
+set(p, v) { *p = v; return 0; }
+main() {
+    var list, x, r;
+    list = cons(1, cons(2, cons(3, null)));
+    (*list).head = 10;
+    output sum(list);
+    x = 1;
+    x = x + set(&x, 4) + x;
+    output x;
+    r = {a: {b: 1}, c: &x};
+    r.c = &r;
+    *(r.c) = {a: 5, c: null};
+    output r.a;
+    return r;
+}
+"#,
+        &[],
+        expect![[r#"output [15, 5, 5], returned {a: 5, c: null}"#]],
+    );
+}
+
+#[test]
+fn function_values() {
+    check(
+        r#"
+inc(x) { return x + 1; }
+twice(f, x) { return f(f(x)); }
+choose(up) { var f; if (up > 0) { f = inc; } else { f = twice; } return f; }
+main() { output twice(inc, 0); output (choose(1))(41); return choose(0) == twice; }
+"#,
+        &[],
+        expect![[r#"output [2, 42], returned 1"#]],
+    );
+}
+
+#[test]
+fn pointer_values() {
+    check(
+        "f(p) { var x; return &x; } main() { var x, p; p = alloc 1; output p == p; output &x == &x; output f(0) == f(0); return p; }",
+        &[],
+        expect![[r#"output [1, 1, 0], returned alloc#0"#]],
+    );
+}
+
+#[test]
+fn sugar() {
+    check(
+        "main(a, b) { output a < b; output a <= b; output a >= b; output a != b; output !a; output !(!b); return 0; }",
+        &[2, 3],
+        expect!["output [1, 1, 0, 1, 0, 1], returned 0"],
+    );
+    check(
+        r#"
+f(x) { output x; return x; }
+main() {
+    var r;
+    r = {a: 1};
+    output f(0) && f(1);
+    output f(2) && f(3);
+    output f(0) || f(4);
+    output f(5) || f(6);
+    output r != null && -7;
+    output !r;
+    return (0 || input) + (input && 0);
+}
+"#,
+        &[4, 9],
+        expect!["output [0, 0, 2, 3, 1, 0, 4, 1, 5, 1, 1, 0], returned 1"],
+    );
+    check(
+        "main() { var p; p = null; return 1 < *p; }",
+        &[],
+        expect!["output [], dereference of null at `p`"],
+    );
+    check(
+        "main() { var r, s; r = {a: 1}; s = null; return r < s; }",
+        &[],
+        expect!["output [], expected an integer but found null at `s`"],
+    );
+    check(
+        "main() { var r, s; r = {a: 1}; s = null; return r <= s; }",
+        &[],
+        expect!["output [], expected an integer but found a record at `r`"],
+    );
+}
+
+#[test]
+fn arrays() {
+    check(
+        r#"
+sum(a, n) {
+    var i, total;
+    i = 0;
+    total = 0;
+    while (n > i) { total = total + a[i]; i = i + 1; }
+    return total;
+}
+main() {
+    var a, b, p;
+    a = [1, 2, 3];
+    b = a;
+    b[0] = 10;
+    output a[0];
+    output sum(b, 3);
+    p = alloc [[1], []];
+    output (*p)[0][0];
+    a[a[0]] = input;
+    output a == [1, 7, 3];
+    return [a, [], [null]];
+}
+"#,
+        &[7],
+        expect!["output [1, 15, 1, 1], returned [[1, 7, 3], [], [null]]"],
+    );
+    check(
+        "main() { var a; a = [1, 2]; return a[2]; }",
+        &[],
+        expect!["output [], index 2 is out of bounds for an array of length 2 at `a[2]`"],
+    );
+    check(
+        "main() { var a; a = [1, 2]; a[-1] = 0; return 0; }",
+        &[],
+        expect!["output [], index -1 is out of bounds for an array of length 2 at `a[-1] = 0;`"],
+    );
+    check(
+        "main() { var a; a = {f: 1}; return a[0]; }",
+        &[],
+        expect!["output [], expected an array but found a record at `a`"],
+    );
+    check(
+        "main() { var a; a = 1; a[0] = 2; return 0; }",
+        &[],
+        expect!["output [], expected an array but found an integer at `a[0] = 2;`"],
+    );
+    check(
+        "main() { var a; a = [1]; return a[null]; }",
+        &[],
+        expect!["output [], expected an integer but found null at `null`"],
+    );
+}
+
+#[test]
+fn loops() {
+    check(
+        r#"
+main() {
+    var i, j, n, found;
+    n = input;
+    for (i = 0; i < n; i = i + 1) {
+        if (i - i / 2 * 2 == 1) {
+            continue;
+        }
+        output i;
+    }
+    found = 0;
+    for (i = 1; i < 10; i = i + 1) {
+        for (j = 1; j < 10; j = j + 1) {
+            if (i * j == n) {
+                break;
+            }
+        }
+        if (j < 10 && i > 1) {
+            found = i;
+            break;
+        }
+    }
+    while (1) {
+        n = n - 1;
+        if (n < 3) { break; }
+        if (n == 4) { continue; }
+        output n;
+    }
+    return [found, i, n];
+}
+"#,
+        &[6],
+        expect!["output [0, 2, 4, 5, 3], returned [2, 2, 2]"],
+    );
+}
+
+#[test]
+fn returns() {
+    check(
+        r#"
+find(a, x) {
+    var i;
+    for (i = 0; i < 3; i = i + 1) {
+        while (1) {
+            if (a[i] == x) { return i; }
+            break;
+        }
+        output i;
+    }
+    return -1;
+}
+sign(n) {
+    if (n > 0) { return 1; } else { if (n < 0) { return -1; } else { return 0; } }
+}
+main() {
+    output find([4, 5, 6], 5);
+    output find([4, 5, 6], 7);
+    return sign(input) + sign(0);
+}
+"#,
+        &[-8],
+        expect!["output [0, 1, 0, 1, 2, -1], returned -1"],
+    );
+    // The interpreter runs programs that do not resolve, unlike their
+    // desugaring, which reads the result before it is assigned.
+    let source = "f(n) { if (n) { return 1; } } main() { return f(0); }";
+    let outcome = Interpreter::new(&parse_ok(source)).run();
+    expect!["output [], function `f` ended without returning at `f(0)`"]
+        .assert_eq(&describe(source, &outcome));
+}
+
+#[test]
+fn globals() {
+    check(
+        r#"
+var g, p;
+bump() { g = g + 1; return g; }
+shadow(g) { g = g * 2; return g; }
+main() {
+    g = 1;
+    output g + bump() + g;
+    p = &g;
+    *p = 10;
+    output bump();
+    output shadow(g);
+    output g;
+    return p;
+}
+"#,
+        &[],
+        expect!["output [5, 11, 22, 11], returned &g#0"],
+    );
+    check(
+        "var g; main() { return g; }",
+        &[],
+        expect!["output [], `g` is read before it is assigned at `g`"],
+    );
+}
+
+#[test]
+fn runtime_errors() {
+    check(
+        "main() { var p; p = null; return *p; }",
+        &[],
+        expect![[r#"output [], dereference of null at `p`"#]],
+    );
+    check(
+        "main() { output 1; return 1 / (input - 3); }",
+        &[3],
+        expect![[r#"output [1], division by zero at `1 / (input - 3)`"#]],
+    );
+    check(
+        "main() { var x, y; y = 1; return y + x; }",
+        &[],
+        expect![[r#"output [], `x` is read before it is assigned at `x`"#]],
+    );
+    check(
+        "f() { var x; x = 1; return &x; } main() { return *f(); }",
+        &[],
+        expect![[
+            r#"output [], dereference of a pointer into the frame of a call that returned at `*f()`"#
+        ]],
+    );
+    check(
+        "main() { var r; r = {a: 1}; output 1; output r; return 0; }",
+        &[],
+        expect![[r#"output [1], expected an integer but found a record at `r`"#]],
+    );
+    check(
+        "main() { var r; r = {a: 1}; r.b = 2; return 0; }",
+        &[],
+        expect![[r#"output [], the record has no field `b` at `r.b = 2;`"#]],
+    );
+    check(
+        "f(x) { return x; } main() { var g; g = f; return g(1, 2); }",
+        &[],
+        expect![[
+            r#"output [], function `f` takes 1 argument(s) but 2 were supplied at `g(1, 2)`"#
+        ]],
+    );
+    check(
+        "main(a, b) { return input; }",
+        &[1, 2],
+        expect![[r#"output [], the input is exhausted at `input`"#]],
+    );
+    check(
+        "f(n) { return f(n + 1); } main() { return f(0); }",
+        &[],
+        expect!["output [], more than 100 calls are active at `f(n + 1)`"],
+    );
+    check(
+        "f() { return 0; }",
+        &[],
+        expect![[r#"output [], the program has no `main` function at ``"#]],
+    );
+}
+
+#[test]
+fn traps_in_imported_files() {
+    let fs = HashMap::from([
+        (
+            PathBuf::from("main.tip"),
+            r#"import "lib.tip"; main() { return div(1, 0); }"#.to_owned(),
+        ),
+        (
+            PathBuf::from("lib.tip"),
+            "div(a, b) { return a / b; }".to_owned(),
+        ),
+    ]);
+    let mut source_map = SourceMap::new();
+    let prog = load("main.tip", &fs, &mut source_map, &mut Vec::new())
+        .unwrap()
+        .unwrap();
+    let trap = Interpreter::new(&prog).run().result.unwrap_err();
+    let file = source_map.file(trap.span.file());
+    let text = &file.source()[trap.span.start()..trap.span.end()];
+    expect!["division by zero at `a / b` in lib.tip"].assert_eq(&format!(
+        "{} at `{}` in {}",
+        trap.error,
+        text,
+        file.name()
+    ));
+}
+
+#[test]
+fn fuel() {
+    let source = "main() { var n; n = 0; while (1) { output n; n = n + 1; } return n; }";
+    let prog = parse_ok(source);
+    let outcome = Interpreter::new(&prog).with_fuel(8).run();
+    expect!["output [0, 1], the program ran out of fuel at `while (1) { output n; n = n + 1; }`"]
+        .assert_eq(&describe(source, &outcome));
+}
+
+/// Checks that every statement of `prog` is in normal form.
+fn assert_normal(prog: &Prog) {
+    fn atom(exp: &AstExp) -> bool {
+        match &exp.node {
+            Exp::Int(_) | Exp::Identifier(_) | Exp::Null => true,
+            Exp::Unary(_, inner) => matches!(inner.node, Exp::Int(_)),
+            _ => false,
+        }
+    }
+    fn var(exp: &AstExp) -> bool {
+        matches!(exp.node, Exp::Identifier(_))
+    }
+    fn value(exp: &AstExp) -> bool {
+        match &exp.node {
+            Exp::Input | Exp::Pointer(_) => true,
+            Exp::Unary(_, inner) | Exp::Alloc(inner) => atom(inner),
+            Exp::Binary(lhs, _, rhs) => atom(lhs) && atom(rhs),
+            Exp::Call(callee, args) => var(callee) && args.iter().all(atom),
+            Exp::Dereference(inner) | Exp::Field(inner, _) => var(inner),
+            Exp::Record(fields) => fields.iter().all(|field| atom(&field.value)),
+            Exp::Array(elements) => elements.iter().all(atom),
+            Exp::Index(array, index) => var(array) && atom(index),
+            _ => atom(exp),
+        }
+    }
+    fn block(stms: &[AstStm]) {
+        for stm in stms {
+            let normal = match &stm.node {
+                Stm::IdentifierAssign(_, exp) => value(exp),
+                Stm::PointerAssign(target, exp) | Stm::DereferenceFieldAssign(target, _, exp) => {
+                    var(target) && atom(exp)
+                }
+                Stm::FieldAssign(_, _, exp) | Stm::Output(exp) => atom(exp),
+                Stm::IndexAssign(_, index, exp) => atom(index) && atom(exp),
+                Stm::If(cond, then, else_) => {
+                    block(then);
+                    block(else_.as_deref().unwrap_or_default());
+                    atom(cond)
+                }
+                Stm::While(cond, body) => {
+                    block(body);
+                    atom(cond)
+                }
+                Stm::For(..) | Stm::Break | Stm::Continue | Stm::Return(_) => false,
+            };
+            assert!(normal, "not in normal form: {:?}", stm);
+        }
+    }
+    for fun in &prog.funs {
+        block(&fun.stms);
+        let normal = matches!(&fun.return_, Some(exp) if atom(exp));
+        assert!(normal, "not in normal form: {:?}", fun.return_);
+    }
+}
+
+/// Checks that `prog` only uses the core operators and loops.
+fn assert_no_sugar(prog: &Prog) {
+    struct Sugar;
+
+    impl Visitor for Sugar {
+        fn visit_stm(&mut self, stm: &AstStm) {
+            let core = !matches!(
+                stm.node,
+                Stm::For(..) | Stm::Break | Stm::Continue | Stm::Return(_)
+            );
+            assert!(core, "not desugared: {:?}", stm);
+            visit::walk_stm(self, stm);
+        }
+
+        fn visit_exp(&mut self, exp: &AstExp) {
+            let core = match &exp.node {
+                Exp::Unary(op, _) => *op == UnOp::Negative,
+                Exp::Binary(_, op, _) => matches!(
+                    op,
+                    BinOp::Add
+                        | BinOp::Subtract
+                        | BinOp::Multiply
+                        | BinOp::Divide
+                        | BinOp::Greater
+                        | BinOp::Equal
+                ),
+                _ => true,
+            };
+            assert!(core, "not desugared: {:?}", exp);
+            visit::walk_exp(self, exp);
+        }
+    }
+
+    Sugar.visit_prog(prog);
+}
+
+/// Runs a generated program, its desugaring and its normal form on the same input,
+/// returning whether the original ran to completion.
+fn check_generated(seed: u64, config: &GenConfig) -> bool {
+    // Print and parse the program to give its nodes real spans.
+    let source = print_prog(&generate(seed, config));
+    let prog = parse_ok(&source);
+    let desugared = desugar(&prog);
+    assert_no_sugar(&desugared);
+    let normalized = normalize(&prog);
+    assert_normal(&normalized.prog);
+    let mut diagnostics = Vec::new();
+    resolve(&normalized.prog, &mut diagnostics);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    let input: Vec<i32> = (0..32).map(|i| (seed as i32 + i * 7) % 11 - 5).collect();
+    let run = |prog| {
+        Interpreter::new(prog)
+            .with_input(&input)
+            .with_fuel(100_000)
+            .run()
+    };
+    let outcome = run(&prog);
+    let out_of_fuel = |outcome: &Outcome| matches!(&outcome.result, Err(trap) if trap.error == crate::RuntimeError::OutOfFuel);
+    for (transformed, what) in [
+        (&desugared, "desugaring"),
+        (&normalized.prog, "normalizing"),
+    ] {
+        let transformed_outcome = run(transformed);
+        // The transformed programs run more statements, so they may run out
+        // of fuel where the original did not.
+        if !out_of_fuel(&outcome) && !out_of_fuel(&transformed_outcome) {
+            assert_eq!(
+                outcome,
+                transformed_outcome,
+                "{} changed the outcome of\n{}\nto\n{}",
+                what,
+                source,
+                print_prog(transformed)
+            );
+        }
+    }
+    outcome.result.is_ok()
+}
+
+#[test]
+fn transformations_preserve_behaviour() {
+    let config = GenConfig::default();
+    let completed = (0..200)
+        .filter(|&seed| check_generated(seed, &config))
+        .count();
+    // Well-typed programs terminate, but can still divide by zero, read
+    // uninitialized variables or dereference null.
+    assert!(completed > 50, "only {} programs completed", completed);
+
+    let config = GenConfig {
+        well_typed: false,
+        ..GenConfig::default()
+    };
+    for seed in 0..200 {
+        check_generated(seed, &config);
+    }
+}
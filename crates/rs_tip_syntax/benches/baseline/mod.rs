@@ -0,0 +1,114 @@
+//! Token types to compare `Token` with, which hold the text of identifiers
+//! and string literals instead of interning it. `OwnedToken` is the token
+//! type the lexer had before identifiers were interned and allocates a
+//! `String` for each; `BorrowedToken` borrows the text from the source, which
+//! is as cheap as lexing gets but ties every token to the source's lifetime.
+
+use logos::Logos;
+
+/// Defines a token type whose identifiers and strings are `$text`, made from
+/// their slice of the source by `$make`.
+macro_rules! token_type {
+    ($name:ident $(<$lt:lifetime>)?, $text:ty, $make:ident) => {
+        #[derive(Logos, Debug, PartialEq)]
+        pub enum $name$(<$lt>)? {
+            #[error]
+            #[regex(r"[ \t\r\n\f]+", logos::skip)]
+            #[regex(r"//[^\n]*", logos::skip)]
+            Error,
+            #[regex("[0-9]+", |lex| lex.slice().parse())]
+            Int(i32),
+            #[regex(r#""[^"\n]*""#, |lex| $make(&lex.slice()[1..lex.slice().len() - 1]))]
+            String($text),
+            #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| $make(lex.slice()))]
+            Identifier($text),
+            #[token("input")]
+            Input,
+            #[token("output")]
+            Output,
+            #[token("if")]
+            If,
+            #[token("else")]
+            Else,
+            #[token("while")]
+            While,
+            #[token("for")]
+            For,
+            #[token("break")]
+            Break,
+            #[token("continue")]
+            Continue,
+            #[token("return")]
+            Return,
+            #[token("var")]
+            Var,
+            #[token("alloc")]
+            Alloc,
+            #[token("null")]
+            Null,
+            #[token("import")]
+            Import,
+            #[token(",")]
+            Comma,
+            #[token(".")]
+            Dot,
+            #[token(":")]
+            Colon,
+            #[token(";")]
+            Semicolon,
+            #[token("&")]
+            Ampersand,
+            #[token("+")]
+            Plus,
+            #[token("-")]
+            Minus,
+            #[token("*")]
+            Star,
+            #[token("/")]
+            Slash,
+            #[token(">")]
+            Greater,
+            #[token(">=")]
+            GreaterEqual,
+            #[token("<")]
+            Less,
+            #[token("<=")]
+            LessEqual,
+            #[token("=")]
+            Equal,
+            #[token("==")]
+            EqualEqual,
+            #[token("!=")]
+            BangEqual,
+            #[token("!")]
+            Bang,
+            #[token("&&")]
+            AmpersandAmpersand,
+            #[token("||")]
+            PipePipe,
+            #[token("(")]
+            OpeningRound,
+            #[token("{")]
+            OpeningCurly,
+            #[token("[")]
+            OpeningSquare,
+            #[token(")")]
+            ClosingRound,
+            #[token("}")]
+            ClosingCurly,
+            #[token("]")]
+            ClosingSquare,
+        }
+    };
+}
+
+token_type!(OwnedToken, String, to_owned);
+token_type!(BorrowedToken<'source>, &'source str, borrow);
+
+fn to_owned(text: &str) -> String {
+    text.to_owned()
+}
+
+fn borrow(text: &str) -> &str {
+    text
+}
@@ -0,0 +1,93 @@
+//! Measures the allocations made while parsing a large generated program.
+//!
+//! Run with `cargo bench -p rs_tip_syntax --bench identifiers`. Identifiers
+//! are interned, so once the interner has seen a name, parsing another
+//! occurrence of it allocates nothing; the `owned` lexer, whose tokens held
+//! a `String` before identifiers were interned, allocates once per
+//! occurrence before the parser even sees the token.
+
+use std::fmt::Write;
+
+use logos::Logos;
+use rs_tip_syntax::{
+    lexer::{Lexer, Token},
+    parse,
+};
+
+mod baseline;
+mod counting;
+
+use baseline::{BorrowedToken, OwnedToken};
+use counting::CountingAlloc;
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Generates `funs` functions that each use a handful of variables many
+/// times, returning the program and the number of identifiers in it.
+fn generate(funs: usize) -> (String, usize) {
+    let mut source = String::new();
+    let mut identifiers = 0;
+    for i in 0..funs {
+        writeln!(source, "f{i}(count, step) {{").unwrap();
+        writeln!(source, "    var total, index;").unwrap();
+        writeln!(source, "    total = 0;").unwrap();
+        writeln!(source, "    index = count;").unwrap();
+        writeln!(source, "    while (index > 0) {{").unwrap();
+        writeln!(source, "        total = total + index * step;").unwrap();
+        writeln!(source, "        index = index - step;").unwrap();
+        writeln!(source, "    }}").unwrap();
+        if i > 0 {
+            writeln!(source, "    total = total + f{}(count, step);", i - 1).unwrap();
+            identifiers += 5;
+        }
+        writeln!(source, "    return total;").unwrap();
+        writeln!(source, "}}").unwrap();
+        identifiers += 17;
+    }
+    (source, identifiers)
+}
+
+fn measure(name: &str, identifiers: usize, f: impl FnOnce()) {
+    let counting::Measurement {
+        elapsed,
+        allocations,
+        bytes,
+    } = counting::measure(f);
+    println!(
+        "{name:<12} {elapsed:>10.2?} {allocations:>9} allocations ({:.2} per identifier) {bytes:>10} bytes",
+        allocations as f64 / identifiers as f64,
+    );
+}
+
+fn main() {
+    let (source, identifiers) = generate(5_000);
+    println!("{} bytes, {} identifiers", source.len(), identifiers);
+    // The first parse interns every name; later parses only look them up.
+    measure("cold parse", identifiers, || {
+        parse(&source, &mut Vec::new()).unwrap();
+    });
+    measure("warm parse", identifiers, || {
+        parse(&source, &mut Vec::new()).unwrap();
+    });
+    // Lexing alone, with the token types from before and after interning and
+    // with tokens borrowing their text.
+    measure("owned lex", identifiers, || {
+        let count = OwnedToken::lexer(&source)
+            .filter(|token| matches!(token, OwnedToken::Identifier(_)))
+            .count();
+        assert_eq!(count, identifiers);
+    });
+    measure("borrowed lex", identifiers, || {
+        let count = BorrowedToken::lexer(&source)
+            .filter(|token| matches!(token, BorrowedToken::Identifier(_)))
+            .count();
+        assert_eq!(count, identifiers);
+    });
+    measure("warm lex", identifiers, || {
+        let count = Lexer::new(&source)
+            .filter(|token| matches!(token, Ok((_, Token::Identifier(_), _))))
+            .count();
+        assert_eq!(count, identifiers);
+    });
+}
@@ -0,0 +1,81 @@
+//! Measures lexing and parsing throughput on a multi-megabyte program.
+//!
+//! Run with `cargo bench -p rs_tip_syntax --bench lexer`. `Lexer` yields
+//! `Copy` tokens holding interned symbols, so once the interner has seen
+//! every name, lexing allocates nothing and the parser consumes the tokens
+//! as they are produced, allocating only for the nodes it builds. The
+//! `owned` lexer is the token type the lexer had before identifiers were
+//! interned, allocating a `String` for every identifier, and the `borrowed`
+//! one holds slices of the source instead.
+
+use logos::Logos;
+use rs_tip_syntax::{
+    gen::{generate, GenConfig},
+    lexer::Lexer,
+    parse,
+    printer::print_prog,
+};
+
+mod baseline;
+mod counting;
+
+use baseline::{BorrowedToken, OwnedToken};
+use counting::{CountingAlloc, Measurement};
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+fn report(name: &str, source: &str, tokens: usize, m: Measurement) {
+    let seconds = m.elapsed.as_secs_f64();
+    println!(
+        "{name:<10} {:>10.2?} {:>8.1} MB/s {:>6.1} Mtokens/s {:>9} allocations ({:.3} per token) {:>10} bytes",
+        m.elapsed,
+        source.len() as f64 / seconds / 1e6,
+        tokens as f64 / seconds / 1e6,
+        m.allocations,
+        m.allocations as f64 / tokens as f64,
+        m.bytes,
+    );
+}
+
+/// Runs `f` five times and keeps the fastest run.
+fn best_of(mut f: impl FnMut()) -> Measurement {
+    (0..5)
+        .map(|_| counting::measure(&mut f))
+        .min_by_key(|m| m.elapsed)
+        .unwrap()
+}
+
+fn main() {
+    let config = GenConfig {
+        funs: 20_000,
+        ..GenConfig::default()
+    };
+    let source = print_prog(&generate(0, &config));
+    let tokens = Lexer::new(&source).count();
+    println!("{} bytes, {} tokens", source.len(), tokens);
+
+    let m = best_of(|| {
+        assert_eq!(OwnedToken::lexer(&source).count(), tokens);
+    });
+    report("owned", &source, tokens, m);
+    let m = best_of(|| {
+        assert_eq!(BorrowedToken::lexer(&source).count(), tokens);
+    });
+    report("borrowed", &source, tokens, m);
+    // `count` above interned every name, so this lexes without allocating.
+    let m = best_of(|| {
+        assert!(Lexer::new(&source).all(|token| token.is_ok()));
+    });
+    report("symbols", &source, tokens, m);
+    let m = best_of(|| {
+        parse(&source, &mut Vec::new()).unwrap();
+    });
+    report("parse", &source, tokens, m);
+    // Printing reads the text of every symbol.
+    let prog = parse(&source, &mut Vec::new()).unwrap();
+    let m = best_of(|| {
+        print_prog(&prog);
+    });
+    report("print", &source, tokens, m);
+}
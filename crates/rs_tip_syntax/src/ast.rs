@@ -1,12 +1,58 @@
 use std::ops::Deref;
 
-#[derive(Clone, Debug, Hash)]
+use rs_tip_errors::{FileId, Span};
+
+use crate::symbol::Symbol;
+
+pub mod fold;
+pub mod visit;
+pub mod visit_mut;
+
+pub use fold::Folder;
+pub use visit::Visitor;
+pub use visit_mut::VisitorMut;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Spanned<T> {
     pub start: usize,
     pub end: usize,
+    /// The file the node was parsed from, serialized as its index.
+    #[cfg_attr(feature = "serde", serde(with = "file_index"))]
+    pub file: FileId,
     pub node: T,
 }
 
+#[cfg(feature = "serde")]
+mod file_index {
+    use rs_tip_errors::FileId;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(file: &FileId, serializer: S) -> Result<S::Ok, S::Error> {
+        file.index().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<FileId, D::Error> {
+        usize::deserialize(deserializer).map(FileId::from_index)
+    }
+}
+
+impl<T> Spanned<T> {
+    pub fn span(&self) -> Span {
+        Span::in_file(self.file, self.start, self.end)
+    }
+
+    /// Transforms the node while keeping its span.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned {
+            start: self.start,
+            end: self.end,
+            file: self.file,
+            node: f(self.node),
+        }
+    }
+}
+
 impl<T> Deref for Spanned<T> {
     type Target = T;
 
@@ -16,67 +62,97 @@ impl<T> Deref for Spanned<T> {
 }
 
 pub type AstInt = Spanned<i32>;
-pub type AstString = Spanned<String>;
+pub type AstSymbol = Spanned<Symbol>;
 pub type AstExp = Spanned<Exp>;
 pub type AstField = Spanned<Field>;
 pub type AstStm = Spanned<Stm>;
 pub type AstFun = Spanned<Fun>;
 pub type AstProg = Spanned<Prog>;
 
-#[derive(Debug, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Prog {
+    /// The paths of imported files, as written.
+    pub imports: Vec<AstSymbol>,
+    /// Variables declared outside of any function, visible in all of them.
+    pub globals: Vec<AstSymbol>,
     pub funs: Vec<AstFun>,
 }
 
-#[derive(Debug, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Fun {
-    pub name: AstString,
-    pub params: Vec<AstString>,
-    pub vars: Vec<AstString>,
+    pub name: AstSymbol,
+    pub params: Vec<AstSymbol>,
+    pub vars: Vec<AstSymbol>,
     pub stms: Vec<AstStm>,
-    pub return_: AstExp,
+    /// The `return` ending the body, if its last statement is one. Bodies
+    /// ending otherwise return on every path through `Stm::Return`.
+    pub return_: Option<AstExp>,
 }
 
-#[derive(Debug, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Stm {
-    IdentifierAssign(AstString, AstExp),
+    IdentifierAssign(AstSymbol, AstExp),
     PointerAssign(AstExp, AstExp),
-    FieldAssign(AstString, AstString, AstExp),
-    DereferenceFieldAssign(AstExp, AstString, AstExp),
+    FieldAssign(AstSymbol, AstSymbol, AstExp),
+    DereferenceFieldAssign(AstExp, AstSymbol, AstExp),
+    /// `a[i] = e;`, which like a field assignment updates the array stored
+    /// in a variable.
+    IndexAssign(AstSymbol, AstExp, AstExp),
     Output(AstExp),
     If(AstExp, Vec<AstStm>, Option<Vec<AstStm>>),
     While(AstExp, Vec<AstStm>),
+    /// `for (init; cond; step) { body }`, where `init` and `step` are
+    /// assignments. `continue` in the body still runs `step`.
+    For(Box<AstStm>, AstExp, Box<AstStm>, Vec<AstStm>),
+    /// Leaves the innermost loop.
+    Break,
+    /// Skips to the next iteration of the innermost loop.
+    Continue,
+    /// A `return` before the end of the function body.
+    Return(AstExp),
 }
 
-#[derive(Debug, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Exp {
     Int(AstInt),
-    Identifier(AstString),
+    Identifier(AstSymbol),
     Unary(UnOp, Box<AstExp>),
     Binary(Box<AstExp>, BinOp, Box<AstExp>),
     Input,
     Call(Box<AstExp>, Vec<AstExp>),
     Alloc(Box<AstExp>),
-    Pointer(AstString),
+    Pointer(AstSymbol),
     Dereference(Box<AstExp>),
     Null,
     Record(Vec<AstField>),
-    Field(Box<AstExp>, AstString),
+    Field(Box<AstExp>, AstSymbol),
+    /// An array literal. Arrays are values like records, so assigning one
+    /// copies it.
+    Array(Vec<AstExp>),
+    Index(Box<AstExp>, Box<AstExp>),
     Paren(Box<AstExp>),
 }
 
-#[derive(Debug, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Field {
-    pub name: AstString,
+    pub name: AstSymbol,
     pub value: Box<AstExp>,
 }
 
-#[derive(Debug, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnOp {
     Negative,
+    Not,
 }
 
-#[derive(Debug, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinOp {
     Add,
     Subtract,
@@ -84,13 +160,22 @@ pub enum BinOp {
     Divide,
     Greater,
     Equal,
+    // Sugar for the operators above; see `desugar`.
+    NotEqual,
+    Less,
+    LessEqual,
+    GreaterEqual,
+    /// Short-circuiting, like `Or`.
+    And,
+    Or,
 }
 
 pub trait IntoSpanned: Sized {
-    fn into_spanned(self, start: usize, end: usize) -> Spanned<Self> {
+    fn into_spanned(self, span: Span) -> Spanned<Self> {
         Spanned {
-            start,
-            end,
+            start: span.start(),
+            end: span.end(),
+            file: span.file(),
             node: self,
         }
     }
@@ -0,0 +1,177 @@
+//! Rebuilding traversal of the AST.
+//!
+//! A `Folder` takes ownership of each node and returns its replacement. The
+//! default `walk_*` functions rebuild a node from its folded children and
+//! keep its span.
+
+use super::*;
+
+pub trait Folder: Sized {
+    fn fold_prog(&mut self, prog: Prog) -> Prog {
+        walk_prog(self, prog)
+    }
+
+    fn fold_fun(&mut self, fun: AstFun) -> AstFun {
+        walk_fun(self, fun)
+    }
+
+    fn fold_stm(&mut self, stm: AstStm) -> AstStm {
+        walk_stm(self, stm)
+    }
+
+    fn fold_exp(&mut self, exp: AstExp) -> AstExp {
+        walk_exp(self, exp)
+    }
+
+    fn fold_field(&mut self, field: AstField) -> AstField {
+        walk_field(self, field)
+    }
+}
+
+pub fn walk_prog<F: Folder>(f: &mut F, prog: Prog) -> Prog {
+    Prog {
+        imports: prog.imports,
+        globals: prog.globals,
+        funs: prog.funs.into_iter().map(|fun| f.fold_fun(fun)).collect(),
+    }
+}
+
+pub fn walk_fun<F: Folder>(f: &mut F, fun: AstFun) -> AstFun {
+    fun.map(|fun| Fun {
+        name: fun.name,
+        params: fun.params,
+        vars: fun.vars,
+        stms: walk_stms(f, fun.stms),
+        return_: fun.return_.map(|return_| f.fold_exp(return_)),
+    })
+}
+
+pub fn walk_stms<F: Folder>(f: &mut F, stms: Vec<AstStm>) -> Vec<AstStm> {
+    stms.into_iter().map(|stm| f.fold_stm(stm)).collect()
+}
+
+pub fn walk_stm<F: Folder>(f: &mut F, stm: AstStm) -> AstStm {
+    stm.map(|stm| match stm {
+        Stm::IdentifierAssign(name, exp) => Stm::IdentifierAssign(name, f.fold_exp(exp)),
+        Stm::PointerAssign(target, exp) => Stm::PointerAssign(f.fold_exp(target), f.fold_exp(exp)),
+        Stm::FieldAssign(name, field, exp) => Stm::FieldAssign(name, field, f.fold_exp(exp)),
+        Stm::DereferenceFieldAssign(target, field, exp) => {
+            Stm::DereferenceFieldAssign(f.fold_exp(target), field, f.fold_exp(exp))
+        }
+        Stm::IndexAssign(name, index, exp) => {
+            Stm::IndexAssign(name, f.fold_exp(index), f.fold_exp(exp))
+        }
+        Stm::Output(exp) => Stm::Output(f.fold_exp(exp)),
+        Stm::Return(exp) => Stm::Return(f.fold_exp(exp)),
+        Stm::If(cond, then, else_) => Stm::If(
+            f.fold_exp(cond),
+            walk_stms(f, then),
+            else_.map(|else_| walk_stms(f, else_)),
+        ),
+        Stm::While(cond, body) => Stm::While(f.fold_exp(cond), walk_stms(f, body)),
+        Stm::For(init, cond, step, body) => Stm::For(
+            Box::new(f.fold_stm(*init)),
+            f.fold_exp(cond),
+            Box::new(f.fold_stm(*step)),
+            walk_stms(f, body),
+        ),
+        Stm::Break | Stm::Continue => stm,
+    })
+}
+
+pub fn walk_exp<F: Folder>(f: &mut F, exp: AstExp) -> AstExp {
+    exp.map(|exp| match exp {
+        Exp::Int(_) | Exp::Identifier(_) | Exp::Input | Exp::Pointer(_) | Exp::Null => exp,
+        Exp::Unary(op, operand) => Exp::Unary(op, fold_box(f, operand)),
+        Exp::Binary(lhs, op, rhs) => Exp::Binary(fold_box(f, lhs), op, fold_box(f, rhs)),
+        Exp::Call(callee, args) => Exp::Call(
+            fold_box(f, callee),
+            args.into_iter().map(|arg| f.fold_exp(arg)).collect(),
+        ),
+        Exp::Alloc(exp) => Exp::Alloc(fold_box(f, exp)),
+        Exp::Dereference(exp) => Exp::Dereference(fold_box(f, exp)),
+        Exp::Record(fields) => Exp::Record(
+            fields
+                .into_iter()
+                .map(|field| f.fold_field(field))
+                .collect(),
+        ),
+        Exp::Field(exp, field) => Exp::Field(fold_box(f, exp), field),
+        Exp::Array(elements) => Exp::Array(
+            elements
+                .into_iter()
+                .map(|element| f.fold_exp(element))
+                .collect(),
+        ),
+        Exp::Index(array, index) => Exp::Index(fold_box(f, array), fold_box(f, index)),
+        Exp::Paren(exp) => Exp::Paren(fold_box(f, exp)),
+    })
+}
+
+pub fn walk_field<F: Folder>(f: &mut F, field: AstField) -> AstField {
+    field.map(|field| Field {
+        name: field.name,
+        value: fold_box(f, field.value),
+    })
+}
+
+fn fold_box<F: Folder>(f: &mut F, mut exp: Box<AstExp>) -> Box<AstExp> {
+    *exp = f.fold_exp(*exp);
+    exp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::ProgParser, printer::print_prog};
+    use expect_test::expect;
+    use rs_tip_errors::FileId;
+
+    /// Folds additions and multiplications of literals, dropping parentheses.
+    struct ConstantFold;
+
+    impl Folder for ConstantFold {
+        fn fold_exp(&mut self, exp: AstExp) -> AstExp {
+            let exp = walk_exp(self, exp);
+            exp.map(|exp| match exp {
+                Exp::Paren(inner) => inner.node,
+                Exp::Binary(lhs, op @ (BinOp::Add | BinOp::Multiply), rhs) => {
+                    match (&lhs.node, &rhs.node) {
+                        (Exp::Int(l), Exp::Int(r)) => {
+                            let value = match op {
+                                BinOp::Add => l.node + r.node,
+                                _ => l.node * r.node,
+                            };
+                            Exp::Int(value.into_spanned(Span::in_file(l.file, l.start, r.end)))
+                        }
+                        _ => Exp::Binary(lhs, op, rhs),
+                    }
+                }
+                exp => exp,
+            })
+        }
+    }
+
+    #[test]
+    fn rebuilds_tree() {
+        let source = "f(p) { *p = (1 + 2) * 3; if (p) { output {a: 2 * 2}.a; } else { while (p) { p = p + (4 + 4); } } return f((1 + 1)); }";
+        let prog = ProgParser::new()
+            .parse(FileId::default(), Lexer::new(source))
+            .unwrap();
+        let prog = ConstantFold.fold_prog(prog.node);
+        expect![[r#"
+            f(p) {
+                *p = 9;
+                if (p) {
+                    output {a: 4}.a;
+                } else {
+                    while (p) {
+                        p = p + 8;
+                    }
+                }
+                return f(2);
+            }
+        "#]]
+        .assert_eq(&print_prog(&prog));
+    }
+}
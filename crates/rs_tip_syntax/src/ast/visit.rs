@@ -0,0 +1,175 @@
+//! Read-only traversal of the AST.
+//!
+//! Every `visit_*` method defaults to the matching `walk_*` function, which
+//! visits the node's children. Implementors override the methods for the
+//! nodes they care about and call `walk_*` to keep descending.
+
+use super::*;
+
+pub trait Visitor: Sized {
+    fn visit_prog(&mut self, prog: &Prog) {
+        walk_prog(self, prog);
+    }
+
+    fn visit_fun(&mut self, fun: &AstFun) {
+        walk_fun(self, fun);
+    }
+
+    fn visit_stm(&mut self, stm: &AstStm) {
+        walk_stm(self, stm);
+    }
+
+    fn visit_exp(&mut self, exp: &AstExp) {
+        walk_exp(self, exp);
+    }
+
+    fn visit_field(&mut self, field: &AstField) {
+        walk_field(self, field);
+    }
+}
+
+pub fn walk_prog<V: Visitor>(v: &mut V, prog: &Prog) {
+    for fun in &prog.funs {
+        v.visit_fun(fun);
+    }
+}
+
+pub fn walk_fun<V: Visitor>(v: &mut V, fun: &AstFun) {
+    for stm in &fun.stms {
+        v.visit_stm(stm);
+    }
+    if let Some(return_) = &fun.return_ {
+        v.visit_exp(return_);
+    }
+}
+
+pub fn walk_stm<V: Visitor>(v: &mut V, stm: &AstStm) {
+    match &stm.node {
+        Stm::IdentifierAssign(_, exp)
+        | Stm::FieldAssign(_, _, exp)
+        | Stm::Output(exp)
+        | Stm::Return(exp) => {
+            v.visit_exp(exp);
+        }
+        Stm::PointerAssign(target, exp)
+        | Stm::DereferenceFieldAssign(target, _, exp)
+        | Stm::IndexAssign(_, target, exp) => {
+            v.visit_exp(target);
+            v.visit_exp(exp);
+        }
+        Stm::If(cond, then, else_) => {
+            v.visit_exp(cond);
+            for stm in then {
+                v.visit_stm(stm);
+            }
+            for stm in else_.iter().flatten() {
+                v.visit_stm(stm);
+            }
+        }
+        Stm::While(cond, body) => {
+            v.visit_exp(cond);
+            for stm in body {
+                v.visit_stm(stm);
+            }
+        }
+        Stm::For(init, cond, step, body) => {
+            v.visit_stm(init);
+            v.visit_exp(cond);
+            v.visit_stm(step);
+            for stm in body {
+                v.visit_stm(stm);
+            }
+        }
+        Stm::Break | Stm::Continue => {}
+    }
+}
+
+pub fn walk_exp<V: Visitor>(v: &mut V, exp: &AstExp) {
+    match &exp.node {
+        Exp::Int(_) | Exp::Identifier(_) | Exp::Input | Exp::Pointer(_) | Exp::Null => {}
+        Exp::Unary(_, exp)
+        | Exp::Alloc(exp)
+        | Exp::Dereference(exp)
+        | Exp::Field(exp, _)
+        | Exp::Paren(exp) => v.visit_exp(exp),
+        Exp::Binary(lhs, _, rhs) | Exp::Index(lhs, rhs) => {
+            v.visit_exp(lhs);
+            v.visit_exp(rhs);
+        }
+        Exp::Call(callee, args) => {
+            v.visit_exp(callee);
+            for arg in args {
+                v.visit_exp(arg);
+            }
+        }
+        Exp::Record(fields) => {
+            for field in fields {
+                v.visit_field(field);
+            }
+        }
+        Exp::Array(elements) => {
+            for element in elements {
+                v.visit_exp(element);
+            }
+        }
+    }
+}
+
+pub fn walk_field<V: Visitor>(v: &mut V, field: &AstField) {
+    v.visit_exp(&field.value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::ProgParser, symbol::Symbol};
+    use rs_tip_errors::FileId;
+
+    #[derive(Default)]
+    struct Names {
+        assigned: Vec<Symbol>,
+        used: Vec<Symbol>,
+    }
+
+    impl Visitor for Names {
+        fn visit_stm(&mut self, stm: &AstStm) {
+            if let Stm::IdentifierAssign(name, _) = &stm.node {
+                self.assigned.push(name.node);
+            }
+            walk_stm(self, stm);
+        }
+
+        fn visit_exp(&mut self, exp: &AstExp) {
+            if let Exp::Identifier(name) | Exp::Pointer(name) = &exp.node {
+                self.used.push(name.node);
+            }
+            walk_exp(self, exp);
+        }
+    }
+
+    #[test]
+    fn visits_every_variant() {
+        let source = r#"
+f(a, b) {
+    var r, p;
+    r = {x: a, y: {z: b}};
+    r.x = (a);
+    p = alloc r;
+    *p = -b;
+    (*p).y = r.y;
+    if (a > b) { output f(a, &b)(c); } else { while (d == 0) { e = input; } }
+    return g * null;
+}
+"#;
+        let prog = ProgParser::new()
+            .parse(FileId::default(), Lexer::new(source))
+            .unwrap();
+        let mut names = Names::default();
+        names.visit_prog(&prog.node);
+        assert_eq!(names.assigned, ["r", "p", "e"]);
+        assert_eq!(
+            names.used,
+            ["a", "b", "a", "r", "p", "b", "p", "r", "a", "b", "f", "a", "b", "c", "d", "g"]
+        );
+    }
+}
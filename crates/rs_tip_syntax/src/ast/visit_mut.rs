@@ -0,0 +1,176 @@
+//! In-place mutable traversal of the AST.
+//!
+//! The mutable counterpart of `visit`: the same methods and `walk_*`
+//! functions, over `&mut` nodes.
+
+use super::*;
+
+pub trait VisitorMut: Sized {
+    fn visit_prog_mut(&mut self, prog: &mut Prog) {
+        walk_prog_mut(self, prog);
+    }
+
+    fn visit_fun_mut(&mut self, fun: &mut AstFun) {
+        walk_fun_mut(self, fun);
+    }
+
+    fn visit_stm_mut(&mut self, stm: &mut AstStm) {
+        walk_stm_mut(self, stm);
+    }
+
+    fn visit_exp_mut(&mut self, exp: &mut AstExp) {
+        walk_exp_mut(self, exp);
+    }
+
+    fn visit_field_mut(&mut self, field: &mut AstField) {
+        walk_field_mut(self, field);
+    }
+}
+
+pub fn walk_prog_mut<V: VisitorMut>(v: &mut V, prog: &mut Prog) {
+    for fun in &mut prog.funs {
+        v.visit_fun_mut(fun);
+    }
+}
+
+pub fn walk_fun_mut<V: VisitorMut>(v: &mut V, fun: &mut AstFun) {
+    for stm in &mut fun.node.stms {
+        v.visit_stm_mut(stm);
+    }
+    if let Some(return_) = &mut fun.node.return_ {
+        v.visit_exp_mut(return_);
+    }
+}
+
+pub fn walk_stm_mut<V: VisitorMut>(v: &mut V, stm: &mut AstStm) {
+    match &mut stm.node {
+        Stm::IdentifierAssign(_, exp)
+        | Stm::FieldAssign(_, _, exp)
+        | Stm::Output(exp)
+        | Stm::Return(exp) => {
+            v.visit_exp_mut(exp);
+        }
+        Stm::PointerAssign(target, exp)
+        | Stm::DereferenceFieldAssign(target, _, exp)
+        | Stm::IndexAssign(_, target, exp) => {
+            v.visit_exp_mut(target);
+            v.visit_exp_mut(exp);
+        }
+        Stm::If(cond, then, else_) => {
+            v.visit_exp_mut(cond);
+            for stm in then {
+                v.visit_stm_mut(stm);
+            }
+            for stm in else_.iter_mut().flatten() {
+                v.visit_stm_mut(stm);
+            }
+        }
+        Stm::While(cond, body) => {
+            v.visit_exp_mut(cond);
+            for stm in body {
+                v.visit_stm_mut(stm);
+            }
+        }
+        Stm::For(init, cond, step, body) => {
+            v.visit_stm_mut(init);
+            v.visit_exp_mut(cond);
+            v.visit_stm_mut(step);
+            for stm in body {
+                v.visit_stm_mut(stm);
+            }
+        }
+        Stm::Break | Stm::Continue => {}
+    }
+}
+
+pub fn walk_exp_mut<V: VisitorMut>(v: &mut V, exp: &mut AstExp) {
+    match &mut exp.node {
+        Exp::Int(_) | Exp::Identifier(_) | Exp::Input | Exp::Pointer(_) | Exp::Null => {}
+        Exp::Unary(_, exp)
+        | Exp::Alloc(exp)
+        | Exp::Dereference(exp)
+        | Exp::Field(exp, _)
+        | Exp::Paren(exp) => v.visit_exp_mut(exp),
+        Exp::Binary(lhs, _, rhs) | Exp::Index(lhs, rhs) => {
+            v.visit_exp_mut(lhs);
+            v.visit_exp_mut(rhs);
+        }
+        Exp::Call(callee, args) => {
+            v.visit_exp_mut(callee);
+            for arg in args {
+                v.visit_exp_mut(arg);
+            }
+        }
+        Exp::Record(fields) => {
+            for field in fields {
+                v.visit_field_mut(field);
+            }
+        }
+        Exp::Array(elements) => {
+            for element in elements {
+                v.visit_exp_mut(element);
+            }
+        }
+    }
+}
+
+pub fn walk_field_mut<V: VisitorMut>(v: &mut V, field: &mut AstField) {
+    v.visit_exp_mut(&mut field.node.value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::ProgParser, printer::print_prog, symbol::Symbol};
+    use expect_test::expect;
+    use rs_tip_errors::FileId;
+
+    /// Renames every use of `x` to `y`.
+    struct Rename;
+
+    impl VisitorMut for Rename {
+        fn visit_stm_mut(&mut self, stm: &mut AstStm) {
+            if let Stm::IdentifierAssign(name, _)
+            | Stm::FieldAssign(name, _, _)
+            | Stm::IndexAssign(name, _, _) = &mut stm.node
+            {
+                if name.node == "x" {
+                    name.node = Symbol::intern("y");
+                }
+            }
+            walk_stm_mut(self, stm);
+        }
+
+        fn visit_exp_mut(&mut self, exp: &mut AstExp) {
+            if let Exp::Identifier(name) | Exp::Pointer(name) = &mut exp.node {
+                if name.node == "x" {
+                    name.node = Symbol::intern("y");
+                }
+            }
+            walk_exp_mut(self, exp);
+        }
+    }
+
+    #[test]
+    fn renames_in_place() {
+        let source =
+            "f(x) { x = {a: x}; x.a = &x; if (x) { while (x) { output *x; } } return x.a; }";
+        let mut prog = ProgParser::new()
+            .parse(FileId::default(), Lexer::new(source))
+            .unwrap();
+        Rename.visit_prog_mut(&mut prog.node);
+        expect![[r#"
+            f(x) {
+                y = {a: y};
+                y.a = &y;
+                if (y) {
+                    while (y) {
+                        output *y;
+                    }
+                }
+                return y.a;
+            }
+        "#]]
+        .assert_eq(&print_prog(&prog.node));
+    }
+}
@@ -0,0 +1,65 @@
+//! Reference TIP programs that the front end must accept.
+
+use rs_tip_errors::FileId;
+
+use crate::{
+    format::{format, FormatConfig},
+    lexer::Lexer,
+    parser::ProgParser,
+    printer::{print_prog, tests::eq_prog},
+    resolve::resolve,
+};
+
+macro_rules! programs {
+    ($($name:literal),* $(,)?) => {
+        [$(($name, include_str!(concat!("../tests/programs/", $name, ".tip")))),*]
+    };
+}
+
+const PROGRAMS: &[(&str, &str)] = &programs![
+    "empty",
+    "functions",
+    "iterate",
+    "linkedlist",
+    "pointers",
+    "records",
+    "recursive",
+];
+
+#[test]
+fn reference_programs() {
+    for (name, source) in PROGRAMS {
+        let prog = ProgParser::new()
+            .parse(FileId::default(), Lexer::new(source))
+            .unwrap_or_else(|err| panic!("{}.tip does not parse: {:?}", name, err))
+            .node;
+
+        let mut diagnostics = Vec::new();
+        resolve(&prog, &mut diagnostics);
+        assert!(
+            diagnostics.is_empty(),
+            "{}.tip does not resolve: {:?}",
+            name,
+            diagnostics
+        );
+
+        let printed = print_prog(&prog);
+        let reparsed = ProgParser::new()
+            .parse(FileId::default(), Lexer::new(&printed))
+            .unwrap()
+            .node;
+        assert!(
+            eq_prog(&prog, &reparsed),
+            "{}.tip does not round-trip",
+            name
+        );
+
+        let formatted = format(source, &FormatConfig::default()).unwrap();
+        assert_eq!(
+            format(&formatted, &FormatConfig::default()).unwrap(),
+            formatted,
+            "{}.tip does not format idempotently",
+            name
+        );
+    }
+}
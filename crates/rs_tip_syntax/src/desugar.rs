@@ -0,0 +1,863 @@
+//! Lowering of the operators and loops that are sugar for the core ones.
+//!
+//! `!`, `!=`, `<`, `<=`, `>=`, `&&` and `||` are rewritten in terms of `==`
+//! and `>`, so that analyses written for the core language work on any
+//! program once it is desugared. Conditions are true when they are not 0,
+//! and these operators produce 1 or 0:
+//!
+//! ```text
+//! !a          a == 0
+//! a != b      (a == b) == 0
+//! a <= b      (a > b) == 0
+//! a < b       b > a
+//! a >= b      (b > a) == 0
+//! a && b      t = (a == 0) == 0; if (t) { t = (b == 0) == 0; }  ... t
+//! a || b      t = (a == 0) == 0; if (t == 0) { t = (b == 0) == 0; }  ... t
+//! ```
+//!
+//! Operands are still evaluated in the order they were written in. If the
+//! operands of `<` or `>=` call functions or read input, or could both stop
+//! the program with an error, the first is evaluated into a temporary before
+//! the two are swapped. `&&` and `||` only
+//! evaluate their second operand when the first does not decide the result,
+//! which takes an `if` before the statement they are part of, so the
+//! operands that come before them in the statement are evaluated into
+//! temporaries ahead of the `if` when the order matters. As in
+//! normalization, the statements computing the condition of a `while` loop
+//! are repeated at the end of its body.
+//!
+//! `for (init; c; step) { body }` becomes `init; while (c) { body; step }`.
+//! `break` and `continue` become assignments to flags, which are new
+//! variables of the function: a loop with a `break` keeps running while
+//! `run` is 1, and a loop with a `continue` sets `go` to 1 at the start of
+//! every iteration. The statements after one that may jump are guarded by
+//! the flag, and a loop with a `break` checks its condition inside the body:
+//!
+//! ```text
+//! run = 1;
+//! while (run) {
+//!     if (c) { go = 1; body; if (run) { step } } else { run = 0; }
+//! }
+//! ```
+//!
+//! Early returns leave a function through its final `return` again: `return
+//! e;` assigns `e` to a result variable, which the function returns, and
+//! clears a `running` flag along with the flags of every loop around it, as
+//! if it broke out of all of them.
+//!
+//! Expressions without sugar are left as they are, and new nodes have the
+//! span of the expression they replace.
+
+use std::collections::HashSet;
+
+use rs_tip_errors::Span;
+
+use crate::{
+    ast::{visit, *},
+    normalize::Fresh,
+    symbol::Symbol,
+};
+
+pub fn desugar(prog: &Prog) -> Prog {
+    let mut desugarer = Desugarer {
+        fresh: Fresh::new(prog),
+        temps: HashSet::new(),
+        fun_temps: Vec::new(),
+        stms: Vec::new(),
+        loops: Vec::new(),
+        exit: None,
+    };
+    Prog {
+        imports: prog.imports.clone(),
+        globals: prog.globals.clone(),
+        funs: prog.funs.iter().map(|fun| desugarer.fun(fun)).collect(),
+    }
+}
+
+struct Desugarer {
+    fresh: Fresh,
+    /// Every temporary, none of which changes between where it is assigned
+    /// and where it is used.
+    temps: HashSet<Symbol>,
+    /// The temporaries declared so far in the current function.
+    fun_temps: Vec<AstSymbol>,
+    /// The statements of the current block.
+    stms: Vec<AstStm>,
+    /// The flags of the loops around the current statement, innermost last.
+    loops: Vec<Flags>,
+    /// The variables of the current function, if it returns early.
+    exit: Option<Exit>,
+}
+
+/// The variables that `break` and `continue` in a loop assign to.
+#[derive(Clone)]
+struct Flags {
+    /// 0 once the loop has been broken out of.
+    run: Option<AstSymbol>,
+    /// 0 once the current iteration has been left.
+    go: Option<AstSymbol>,
+}
+
+/// The variables that `return` in a function assigns to.
+struct Exit {
+    /// The value the function returns.
+    result: AstSymbol,
+    /// 0 once the function has returned.
+    running: AstSymbol,
+}
+
+impl Desugarer {
+    fn fun(&mut self, fun: &AstFun) -> AstFun {
+        let return_ = if Jumps::of(&fun.stms).returns {
+            let span = fun.span();
+            let mut var = || {
+                let name = self.fresh.name().into_spanned(span);
+                self.fun_temps.push(name.clone());
+                name
+            };
+            let exit = Exit {
+                result: var(),
+                running: var(),
+            };
+            self.stms.push(set(&exit.running, 1, span));
+            let result = Exp::Identifier(exit.result.clone()).into_spanned(span);
+            self.exit = Some(exit);
+            // The final `return` becomes one more early return.
+            let mut stms = fun.stms.clone();
+            stms.extend(
+                fun.return_
+                    .iter()
+                    .map(|return_| Stm::Return(return_.clone()).into_spanned(return_.span())),
+            );
+            self.seq(&stms);
+            self.exit = None;
+            Some(result)
+        } else {
+            self.seq(&fun.stms);
+            fun.return_.as_ref().map(|return_| self.exp(return_))
+        };
+        let stms = std::mem::take(&mut self.stms);
+        let mut vars = fun.vars.clone();
+        vars.append(&mut self.fun_temps);
+        Spanned {
+            start: fun.start,
+            end: fun.end,
+            file: fun.file,
+            node: Fun {
+                name: fun.name.clone(),
+                params: fun.params.clone(),
+                vars,
+                stms,
+                return_,
+            },
+        }
+    }
+
+    /// Collects the statements pushed by `f` into a block of their own.
+    fn block_with(&mut self, f: impl FnOnce(&mut Self)) -> Vec<AstStm> {
+        let outer = std::mem::take(&mut self.stms);
+        f(self);
+        std::mem::replace(&mut self.stms, outer)
+    }
+
+    fn block(&mut self, stms: &[AstStm]) -> Vec<AstStm> {
+        self.block_with(|d| d.seq(stms))
+    }
+
+    /// Desugars a sequence of statements, guarding the ones after a jump out
+    /// of the current iteration or function.
+    fn seq(&mut self, stms: &[AstStm]) {
+        for (i, stm) in stms.iter().enumerate() {
+            self.stm(stm);
+            let skip = match self.loops.last() {
+                Some(flags) => flags.go.as_ref().or(flags.run.as_ref()),
+                None => self.exit.as_ref().map(|exit| &exit.running),
+            };
+            let Some(skip) = skip.cloned() else {
+                continue;
+            };
+            let jumps = Jumps::of(std::slice::from_ref(stm));
+            // Outside of loops, `break` and `continue` are left as they are.
+            if !(jumps.returns || !self.loops.is_empty() && jumps.any()) {
+                continue;
+            }
+            if matches!(stm.node, Stm::Break | Stm::Continue | Stm::Return(_)) {
+                return;
+            }
+            let rest = &stms[i + 1..];
+            if !rest.is_empty() {
+                let (first, last) = (&rest[0], &rest[rest.len() - 1]);
+                let span = Span::in_file(first.file, first.start, last.end);
+                let then = self.block(rest);
+                let cond = Exp::Identifier(skip).into_spanned(span);
+                self.stms.push(Stm::If(cond, then, None).into_spanned(span));
+                return;
+            }
+        }
+    }
+
+    fn stm(&mut self, stm: &AstStm) {
+        let node = match &stm.node {
+            Stm::IdentifierAssign(name, exp) => Stm::IdentifierAssign(name.clone(), self.exp(exp)),
+            Stm::PointerAssign(target, exp) => {
+                let (target, value) = self.pair(target, exp);
+                Stm::PointerAssign(target, value)
+            }
+            Stm::FieldAssign(name, field, exp) => {
+                Stm::FieldAssign(name.clone(), field.clone(), self.exp(exp))
+            }
+            Stm::DereferenceFieldAssign(target, field, exp) => {
+                let (target, value) = self.pair(target, exp);
+                Stm::DereferenceFieldAssign(target, field.clone(), value)
+            }
+            Stm::IndexAssign(name, index, exp) => {
+                let (index, value) = self.pair(index, exp);
+                Stm::IndexAssign(name.clone(), index, value)
+            }
+            Stm::Output(exp) => Stm::Output(self.exp(exp)),
+            Stm::If(cond, then, else_) => {
+                let cond = self.exp(cond);
+                let then = self.block(then);
+                let else_ = else_.as_ref().map(|else_| self.block(else_));
+                Stm::If(cond, then, else_)
+            }
+            Stm::While(cond, body) => return self.loop_(cond, None, body, stm.span()),
+            Stm::For(init, cond, step, body) => {
+                self.stm(init);
+                return self.loop_(cond, Some(step), body, stm.span());
+            }
+            Stm::Break | Stm::Continue | Stm::Return(_) => return self.jump(stm),
+        };
+        self.stms.push(node.into_spanned(stm.span()));
+    }
+
+    /// Lowers a loop, running `step` after every iteration of `body`.
+    fn loop_(&mut self, cond: &AstExp, step: Option<&AstStm>, body: &[AstStm], span: Span) {
+        let jumps = Jumps::of(body);
+        let mut flag = |used: bool| {
+            used.then(|| {
+                let name = self.fresh.name().into_spanned(span);
+                self.fun_temps.push(name.clone());
+                name
+            })
+        };
+        let flags = Flags {
+            run: flag(jumps.breaks || jumps.returns),
+            go: flag(jumps.continues),
+        };
+
+        let before = self.stms.len();
+        let cond = self.exp(cond);
+        let compute_cond = self.stms.split_off(before);
+
+        self.loops.push(flags.clone());
+        let mut body = self.block_with(|d| {
+            d.stms.extend(flags.go.as_ref().map(|go| set(go, 1, span)));
+            d.seq(body);
+        });
+        self.loops.pop();
+        let step = step.map(|step| self.block_with(|d| d.stm(step)));
+
+        match flags.run {
+            None => {
+                self.stms.extend(compute_cond.iter().cloned());
+                body.extend(step.into_iter().flatten());
+                body.extend(compute_cond);
+                self.stms.push(Stm::While(cond, body).into_spanned(span));
+            }
+            Some(run) => {
+                let running = || Exp::Identifier(run.clone()).into_spanned(span);
+                if let Some(step) = step {
+                    body.push(Stm::If(running(), step, None).into_spanned(span));
+                }
+                let stop = vec![set(&run, 0, span)];
+                let mut iteration = compute_cond;
+                iteration.push(Stm::If(cond, body, Some(stop)).into_spanned(span));
+                self.stms.push(set(&run, 1, span));
+                self.stms
+                    .push(Stm::While(running(), iteration).into_spanned(span));
+            }
+        }
+    }
+
+    /// Lowers `break` or `continue` to clearing the flags of the innermost
+    /// loop, and `return` to setting the result and clearing every flag.
+    /// Outside of a loop, where resolution reports them, `break` and
+    /// `continue` are kept.
+    fn jump(&mut self, stm: &AstStm) {
+        let mut cleared = Vec::new();
+        match (&stm.node, self.loops.last(), &self.exit) {
+            (Stm::Return(exp), _, Some(exit)) => {
+                let result = exit.result.clone();
+                let value = self.exp(exp);
+                let assign = Stm::IdentifierAssign(result, value);
+                self.stms.push(assign.into_spanned(stm.span()));
+                let exit = self.exit.as_ref().unwrap();
+                cleared.push(exit.running.clone());
+                for flags in &self.loops {
+                    cleared.extend(flags.run.clone());
+                    cleared.extend(flags.go.clone());
+                }
+            }
+            (Stm::Break, Some(flags), _) => {
+                cleared.extend(flags.run.clone());
+                cleared.extend(flags.go.clone());
+            }
+            (Stm::Continue, Some(flags), _) => cleared.extend(flags.go.clone()),
+            _ => {
+                self.stms.push(stm.clone());
+                return;
+            }
+        }
+        for name in cleared {
+            self.stms.push(set(&name, 0, stm.span()));
+        }
+    }
+
+    fn exp(&mut self, exp: &AstExp) -> AstExp {
+        if !has_sugar(exp) {
+            return exp.clone();
+        }
+        let span = exp.span();
+        let node = match &exp.node {
+            Exp::Unary(UnOp::Not, operand) => {
+                let operand = self.exp(operand);
+                return is_zero(operand, span);
+            }
+            Exp::Unary(op, operand) => Exp::Unary(*op, Box::new(self.exp(operand))),
+            Exp::Binary(lhs, op @ (BinOp::And | BinOp::Or), rhs) => {
+                return self.short_circuit(lhs, *op, rhs, span)
+            }
+            Exp::Binary(lhs, op, rhs) => {
+                let (mut lhs, rhs) = self.pair(lhs, rhs);
+                if matches!(op, BinOp::Less | BinOp::GreaterEqual)
+                    && !self.is_stable(&lhs)
+                    && !self.is_stable(&rhs)
+                    && (has_effects(&lhs)
+                        || has_effects(&rhs)
+                        || (may_trap(&lhs) && may_trap(&rhs)))
+                {
+                    lhs = self.temp(lhs);
+                }
+                let binary =
+                    |lhs, op, rhs| Exp::Binary(Box::new(lhs), op, Box::new(rhs)).into_spanned(span);
+                return match op {
+                    BinOp::NotEqual => is_zero(binary(lhs, BinOp::Equal, rhs), span),
+                    BinOp::Less => binary(rhs, BinOp::Greater, lhs),
+                    BinOp::LessEqual => is_zero(binary(lhs, BinOp::Greater, rhs), span),
+                    BinOp::GreaterEqual => is_zero(binary(rhs, BinOp::Greater, lhs), span),
+                    _ => binary(lhs, *op, rhs),
+                };
+            }
+            Exp::Call(callee, args) => {
+                let mut operands = self.operands(std::iter::once(&**callee).chain(args));
+                let callee = operands.remove(0);
+                Exp::Call(Box::new(callee), operands)
+            }
+            Exp::Alloc(inner) => Exp::Alloc(Box::new(self.exp(inner))),
+            Exp::Dereference(inner) => Exp::Dereference(Box::new(self.exp(inner))),
+            Exp::Paren(inner) => Exp::Paren(Box::new(self.exp(inner))),
+            Exp::Record(fields) => {
+                let values = self.operands(fields.iter().map(|field| &*field.value));
+                let fields = fields
+                    .iter()
+                    .zip(values)
+                    .map(|(field, value)| {
+                        Field {
+                            name: field.name.clone(),
+                            value: Box::new(value),
+                        }
+                        .into_spanned(field.span())
+                    })
+                    .collect();
+                Exp::Record(fields)
+            }
+            Exp::Field(record, field) => Exp::Field(Box::new(self.exp(record)), field.clone()),
+            Exp::Array(elements) => Exp::Array(self.operands(elements)),
+            Exp::Index(array, index) => {
+                let (array, index) = self.pair(array, index);
+                Exp::Index(Box::new(array), Box::new(index))
+            }
+            Exp::Int(_) | Exp::Identifier(_) | Exp::Input | Exp::Pointer(_) | Exp::Null => {
+                unreachable!("leaves have no sugar")
+            }
+        };
+        node.into_spanned(span)
+    }
+
+    /// Lowers `lhs && rhs` or `lhs || rhs` to an `if` assigning a temporary,
+    /// and returns the temporary.
+    fn short_circuit(&mut self, lhs: &AstExp, op: BinOp, rhs: &AstExp, span: Span) -> AstExp {
+        let lhs = self.exp(lhs);
+        let lhs_span = lhs.span();
+        let result = self.temp(truth(lhs, lhs_span));
+        let Exp::Identifier(name) = &result.node else {
+            unreachable!()
+        };
+        let name = name.clone();
+        let then = self.block_with(|d| {
+            let rhs = d.exp(rhs);
+            let rhs_span = rhs.span();
+            let assign = Stm::IdentifierAssign(name.clone(), truth(rhs, rhs_span));
+            d.stms.push(assign.into_spanned(rhs_span));
+        });
+        let cond = match op {
+            BinOp::And => result.clone(),
+            _ => is_zero(result.clone(), lhs_span),
+        };
+        self.stms.push(Stm::If(cond, then, None).into_spanned(span));
+        Exp::Identifier(name).into_spanned(span)
+    }
+
+    fn pair(&mut self, first: &AstExp, second: &AstExp) -> (AstExp, AstExp) {
+        let mut exps = self.operands([first, second]);
+        let second = exps.pop().unwrap();
+        (exps.pop().unwrap(), second)
+    }
+
+    /// Desugars `exps`, which are evaluated from left to right.
+    fn operands<'e>(&mut self, exps: impl IntoIterator<Item = &'e AstExp>) -> Vec<AstExp> {
+        let mut desugared: Vec<AstExp> = Vec::new();
+        for exp in exps {
+            let before = self.stms.len();
+            let exp = self.exp(exp);
+            if self.stms.len() > before {
+                // The earlier operands have to be evaluated before the
+                // statements just added.
+                let added = self.stms.split_off(before);
+                let mut find = Find::new(is_effect);
+                for stm in &added {
+                    find.visit_stm(stm);
+                }
+                for earlier in &mut desugared {
+                    if !self.is_stable(earlier) && (find.found || has_effects(earlier)) {
+                        *earlier = self.temp(earlier.clone());
+                    }
+                }
+                self.stms.extend(added);
+            }
+            desugared.push(exp);
+        }
+        desugared
+    }
+
+    /// Whether evaluating `exp` later than where it was written makes no
+    /// difference.
+    fn is_stable(&self, exp: &AstExp) -> bool {
+        match &exp.node {
+            Exp::Int(_) | Exp::Null | Exp::Pointer(_) => true,
+            Exp::Identifier(name) => self.temps.contains(&name.node),
+            Exp::Unary(UnOp::Negative, inner) | Exp::Paren(inner) => self.is_stable(inner),
+            _ => false,
+        }
+    }
+
+    /// Assigns `value` to a new temporary and returns the temporary.
+    fn temp(&mut self, value: AstExp) -> AstExp {
+        let name = self.fresh.name();
+        self.temps.insert(name);
+        let span = value.span();
+        let name = name.into_spanned(span);
+        self.fun_temps.push(name.clone());
+        let assign = Stm::IdentifierAssign(name.clone(), value);
+        self.stms.push(assign.into_spanned(span));
+        Exp::Identifier(name).into_spanned(span)
+    }
+}
+
+/// `exp == 0`.
+fn is_zero(exp: AstExp, span: Span) -> AstExp {
+    let zero = Exp::Int(0.into_spanned(span)).into_spanned(span);
+    Exp::Binary(Box::new(exp), BinOp::Equal, Box::new(zero)).into_spanned(span)
+}
+
+/// `(exp == 0) == 0`, which is 1 if `exp` is true and 0 otherwise.
+fn truth(exp: AstExp, span: Span) -> AstExp {
+    is_zero(is_zero(exp, span), span)
+}
+
+/// `name = value;`.
+fn set(name: &AstSymbol, value: i32, span: Span) -> AstStm {
+    let value = Exp::Int(value.into_spanned(span)).into_spanned(span);
+    Stm::IdentifierAssign(name.clone(), value).into_spanned(span)
+}
+
+fn has_sugar(exp: &AstExp) -> bool {
+    let mut find = Find::new(|exp| {
+        matches!(
+            exp,
+            Exp::Unary(UnOp::Not, _)
+                | Exp::Binary(
+                    _,
+                    BinOp::NotEqual
+                        | BinOp::Less
+                        | BinOp::LessEqual
+                        | BinOp::GreaterEqual
+                        | BinOp::And
+                        | BinOp::Or,
+                    _,
+                )
+        )
+    });
+    find.visit_exp(exp);
+    find.found
+}
+
+/// Whether evaluating `exp` can change the state of the program, or
+/// depends on anything besides it.
+fn has_effects(exp: &AstExp) -> bool {
+    let mut find = Find::new(is_effect);
+    find.visit_exp(exp);
+    find.found
+}
+
+fn is_effect(exp: &Exp) -> bool {
+    matches!(exp, Exp::Call(..) | Exp::Input)
+}
+
+/// Whether evaluating `exp` can stop the program with an error, besides
+/// reading a variable that was never assigned. Only two such operands need
+/// to keep their order, so that the same one fails first.
+fn may_trap(exp: &AstExp) -> bool {
+    let mut find = Find::new(|exp| {
+        matches!(
+            exp,
+            Exp::Unary(..)
+                | Exp::Binary(..)
+                | Exp::Call(..)
+                | Exp::Dereference(_)
+                | Exp::Field(..)
+                | Exp::Index(..)
+        )
+    });
+    find.visit_exp(exp);
+    find.found
+}
+
+/// Finds the `break` and `continue` statements that refer to the loop around
+/// some statements, which are those not inside a nested loop, and the
+/// `return` statements, which leave every loop.
+#[derive(Default)]
+struct Jumps {
+    breaks: bool,
+    continues: bool,
+    returns: bool,
+    /// The number of loops around the current statement.
+    loops: usize,
+}
+
+impl Jumps {
+    fn of(stms: &[AstStm]) -> Jumps {
+        let mut jumps = Jumps::default();
+        for stm in stms {
+            jumps.visit_stm(stm);
+        }
+        jumps
+    }
+
+    fn any(&self) -> bool {
+        self.breaks || self.continues || self.returns
+    }
+}
+
+impl Visitor for Jumps {
+    fn visit_stm(&mut self, stm: &AstStm) {
+        match stm.node {
+            Stm::While(..) | Stm::For(..) => {
+                self.loops += 1;
+                visit::walk_stm(self, stm);
+                self.loops -= 1;
+            }
+            Stm::Break if self.loops == 0 => self.breaks = true,
+            Stm::Continue if self.loops == 0 => self.continues = true,
+            Stm::Return(_) => self.returns = true,
+            _ => visit::walk_stm(self, stm),
+        }
+    }
+
+    fn visit_exp(&mut self, _: &AstExp) {}
+}
+
+/// Looks for an expression matching a predicate.
+struct Find<F> {
+    predicate: F,
+    found: bool,
+}
+
+impl<F: Fn(&Exp) -> bool> Find<F> {
+    fn new(predicate: F) -> Find<F> {
+        Find {
+            predicate,
+            found: false,
+        }
+    }
+}
+
+impl<F: Fn(&Exp) -> bool> Visitor for Find<F> {
+    fn visit_exp(&mut self, exp: &AstExp) {
+        if (self.predicate)(&exp.node) {
+            self.found = true;
+        } else {
+            visit::walk_exp(self, exp);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_prog, printer::print_prog};
+    use expect_test::{expect, Expect};
+
+    fn check(source: &str, expect: Expect) {
+        let prog = parse_prog(source).unwrap();
+        expect.assert_eq(&print_prog(&desugar(&prog.node)));
+    }
+
+    #[test]
+    fn comparisons() {
+        check(
+            "f(a, b) { output !a; output a != b; output a <= b; output a < b; output a >= 1; output f(a, b) < f(b, a); return !(a < b) >= -1; }",
+            expect![[r#"
+                f(a, b) {
+                    var t1;
+                    output a == 0;
+                    output a == b == 0;
+                    output a > b == 0;
+                    output b > a;
+                    output 1 > a == 0;
+                    t1 = f(a, b);
+                    output f(b, a) > t1;
+                    return -1 > (b > a == 0) == 0;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn short_circuits() {
+        check(
+            "f(a, b) { output a && b; output a || f(a, b) && b; while (a > 0 && b > 0) { a = a - 1; } return a + (b || 0); }",
+            expect![[r#"
+                f(a, b) {
+                    var t1, t2, t3, t4, t5;
+                    t1 = a == 0 == 0;
+                    if (t1) {
+                        t1 = b == 0 == 0;
+                    }
+                    output t1;
+                    t2 = a == 0 == 0;
+                    if (t2 == 0) {
+                        t3 = f(a, b) == 0 == 0;
+                        if (t3) {
+                            t3 = b == 0 == 0;
+                        }
+                        t2 = t3 == 0 == 0;
+                    }
+                    output t2;
+                    t4 = a > 0 == 0 == 0;
+                    if (t4) {
+                        t4 = b > 0 == 0 == 0;
+                    }
+                    while (t4) {
+                        a = a - 1;
+                        t4 = a > 0 == 0 == 0;
+                        if (t4) {
+                            t4 = b > 0 == 0 == 0;
+                        }
+                    }
+                    t5 = b == 0 == 0;
+                    if (t5 == 0) {
+                        t5 = 0 == 0 == 0;
+                    }
+                    return a + t5;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn earlier_operands_are_evaluated_first() {
+        check(
+            "f(p, a) { *p = *p + (a && *p); output g(f, *p, a || 1, 2); return g(input, *p, a || f(p, 1)); }",
+            expect![[r#"
+                f(p, a) {
+                    var t1, t2, t3, t4, t5, t6;
+                    t1 = a == 0 == 0;
+                    if (t1) {
+                        t1 = *p == 0 == 0;
+                    }
+                    *p = *p + t1;
+                    t2 = a == 0 == 0;
+                    if (t2 == 0) {
+                        t2 = 1 == 0 == 0;
+                    }
+                    output g(f, *p, t2, 2);
+                    t4 = g;
+                    t5 = input;
+                    t6 = *p;
+                    t3 = a == 0 == 0;
+                    if (t3 == 0) {
+                        t3 = f(p, 1) == 0 == 0;
+                    }
+                    return t4(t5, t6, t3);
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn for_loops() {
+        check(
+            "f(n) { var s, i; s = 0; for (i = 0; i < n; i = i + 1) { s = s + i; } return s; }",
+            expect![[r#"
+                f(n) {
+                    var s, i;
+                    s = 0;
+                    i = 0;
+                    while (n > i) {
+                        s = s + i;
+                        i = i + 1;
+                    }
+                    return s;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn jumps() {
+        check(
+            "f(n) { var s, i; s = 0; for (i = 0; i < n; i = i + 1) { if (i == 2) { continue; } while (1) { break; } if (s > 10) { break; } s = s + i; } while (n > 0) { n = n - 1; if (n == 3) { continue; output n; } output s; } return s; }",
+            expect![[r#"
+                f(n) {
+                    var s, i, t1, t2, t3, t4;
+                    s = 0;
+                    i = 0;
+                    t1 = 1;
+                    while (t1) {
+                        if (n > i) {
+                            t2 = 1;
+                            if (i == 2) {
+                                t2 = 0;
+                            }
+                            if (t2) {
+                                t3 = 1;
+                                while (t3) {
+                                    if (1) {
+                                        t3 = 0;
+                                    } else {
+                                        t3 = 0;
+                                    }
+                                }
+                                if (s > 10) {
+                                    t1 = 0;
+                                    t2 = 0;
+                                }
+                                if (t2) {
+                                    s = s + i;
+                                }
+                            }
+                            if (t1) {
+                                i = i + 1;
+                            }
+                        } else {
+                            t1 = 0;
+                        }
+                    }
+                    while (n > 0) {
+                        t4 = 1;
+                        n = n - 1;
+                        if (n == 3) {
+                            t4 = 0;
+                        }
+                        if (t4) {
+                            output s;
+                        }
+                    }
+                    return s;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn returns() {
+        check(
+            "f(n) { var i; for (i = 0; i < n; i = i + 1) { while (i > 2) { if (i == n) { return i && n; } i = i - 1; } output i; } if (n) { return 0; } output n; return -n; } g(n) { if (n) { return 1; } else { return 2; } }",
+            expect![[r#"
+                f(n) {
+                    var i, t1, t2, t3, t4, t5;
+                    t2 = 1;
+                    i = 0;
+                    t3 = 1;
+                    while (t3) {
+                        if (n > i) {
+                            t4 = 1;
+                            while (t4) {
+                                if (i > 2) {
+                                    if (i == n) {
+                                        t5 = i == 0 == 0;
+                                        if (t5) {
+                                            t5 = n == 0 == 0;
+                                        }
+                                        t1 = t5;
+                                        t2 = 0;
+                                        t3 = 0;
+                                        t4 = 0;
+                                    }
+                                    if (t4) {
+                                        i = i - 1;
+                                    }
+                                } else {
+                                    t4 = 0;
+                                }
+                            }
+                            if (t3) {
+                                output i;
+                            }
+                            if (t3) {
+                                i = i + 1;
+                            }
+                        } else {
+                            t3 = 0;
+                        }
+                    }
+                    if (t2) {
+                        if (n) {
+                            t1 = 0;
+                            t2 = 0;
+                        }
+                        if (t2) {
+                            output n;
+                            t1 = -n;
+                            t2 = 0;
+                        }
+                    }
+                    return t1;
+                }
+
+                g(n) {
+                    var t6, t7;
+                    t7 = 1;
+                    if (n) {
+                        t6 = 1;
+                        t7 = 0;
+                    } else {
+                        t6 = 2;
+                        t7 = 0;
+                    }
+                    return t6;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn programs_without_sugar_are_unchanged() {
+        let source =
+            "f(p, a) { var r; r = {x: *p + a}; while (a > 0) { a = a - 1; } return r.x == a; }";
+        let prog = parse_prog(source).unwrap().node;
+        assert_eq!(desugar(&prog), prog);
+    }
+}
@@ -0,0 +1,269 @@
+use logos::Logos;
+
+use crate::{
+    lexer::Token,
+    parse_prog,
+    printer::{Printer, Trivia},
+};
+
+/// Layout options for `format`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FormatConfig {
+    /// Number of spaces per indentation level.
+    pub indent_width: usize,
+    /// Column after which expressions are broken over several lines.
+    pub max_width: usize,
+}
+
+impl Default for FormatConfig {
+    fn default() -> FormatConfig {
+        FormatConfig {
+            indent_width: 4,
+            max_width: 80,
+        }
+    }
+}
+
+/// Formats a TIP program, keeping its comments next to the statements and
+/// functions they were written beside.
+pub fn format(source: &str, config: &FormatConfig) -> anyhow::Result<String> {
+    let prog = parse_prog(source).map_err(|err| *err)?;
+    let mut printer = Printer::new(config, Some(trivia(source)));
+    printer.prog(&prog.node);
+    Ok(printer.out)
+}
+
+/// Returns whether `source` is already formatted according to `config`.
+pub fn is_formatted(source: &str, config: &FormatConfig) -> anyhow::Result<bool> {
+    Ok(format(source, config)? == source)
+}
+
+fn trivia(source: &str) -> Trivia<'_> {
+    let mut trivia = Trivia {
+        source,
+        comments: Vec::new(),
+        closing_braces: Vec::new(),
+    };
+    let mut lexer = Token::lexer(source);
+    while let Some(token) = lexer.next() {
+        match token {
+            Token::Comment | Token::Allow => trivia.comments.push(lexer.span()),
+            Token::ClosingCurly => trivia.closing_braces.push(lexer.span().start),
+            _ => {}
+        }
+    }
+    trivia
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::{expect, Expect};
+
+    fn check(input: &str, expect: Expect) {
+        check_with(input, &FormatConfig::default(), expect);
+    }
+
+    fn check_with(input: &str, config: &FormatConfig, expect: Expect) {
+        let formatted = format(input, config).unwrap();
+        expect.assert_eq(&formatted);
+        assert!(
+            is_formatted(&formatted, config).unwrap(),
+            "formatting is not idempotent:\n{}",
+            format(&formatted, config).unwrap()
+        );
+    }
+
+    #[test]
+    fn normalizes_layout() {
+        check(
+            "main(){var x,y;x=1;if(x>0){y=2;}else{y=3;}while(y>0){y=y-1;}return x+y;}",
+            expect![[r#"
+                main() {
+                    var x, y;
+                    x = 1;
+                    if (x > 0) {
+                        y = 2;
+                    } else {
+                        y = 3;
+                    }
+                    while (y > 0) {
+                        y = y - 1;
+                    }
+                    return x + y;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_comments() {
+        check(
+            r#"
+// Computes things.
+main() { var x; // the result
+    // Start at one.
+    x = 1; // trailing
+    /* block */ x = 2;
+    while (x > 0) {
+        x = x - 1;
+        // last in loop
+    }
+
+    // before return
+    return x; // done
+    // end of main
+}
+// end of file
+"#,
+            expect![[r#"
+                // Computes things.
+                main() {
+                    var x; // the result
+                    // Start at one.
+                    x = 1; // trailing
+                    /* block */
+                    x = 2;
+                    while (x > 0) {
+                        x = x - 1;
+                        // last in loop
+                    }
+
+                    // before return
+                    return x; // done
+                    // end of main
+                }
+                // end of file
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_comments_without_a_final_return() {
+        check(
+            r#"
+sign(n) {
+    if (n > 0) { return 1; // positive
+    } else { return 0; }
+    // unreachable
+}
+"#,
+            expect![[r#"
+                sign(n) {
+                    if (n > 0) {
+                        return 1; // positive
+                    } else {
+                        return 0;
+                    }
+                    // unreachable
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_comments_on_imports() {
+        check(
+            r#"
+// Helpers.
+import "list.tip"; // cons and nil
+import "math.tip";
+main() { return 0; }
+"#,
+            expect![[r#"
+                // Helpers.
+                import "list.tip"; // cons and nil
+                import "math.tip";
+
+                main() {
+                    return 0;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_comments_in_empty_blocks() {
+        check(
+            "main() { var x; var y; // merged\n while (x) { /* nothing */ } if (x) {} else { // never\n } return 0; }",
+            expect![[r#"
+                main() {
+                    var x, y; // merged
+                    while (x) {
+                        /* nothing */
+                    }
+                    if (x) {} else {
+                        // never
+                    }
+                    return 0;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_single_blank_lines() {
+        check(
+            r#"
+f() {
+    var x;
+
+
+    x = 1;
+    x = 2;
+
+    x = 3;
+    return x;
+}
+g() { output 1; return 1; }
+"#,
+            expect![[r#"
+                f() {
+                    var x;
+
+                    x = 1;
+                    x = 2;
+
+                    x = 3;
+                    return x;
+                }
+
+                g() {
+                    output 1;
+                    return 1;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn reflows_long_expressions() {
+        check_with(
+            "f(a, b) { a = g(a + b, {first: a, second: b}, h(a, b)); b = aaaa * bbbb + cccc * dddd; return b; }",
+            &FormatConfig {
+                indent_width: 2,
+                max_width: 30,
+            },
+            expect![[r#"
+                f(a, b) {
+                  a = g(
+                    a + b,
+                    {first: a, second: b},
+                    h(a, b),
+                  );
+                  b = aaaa * bbbb
+                    + cccc * dddd;
+                  return b;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn check_mode() {
+        let config = FormatConfig::default();
+        assert!(is_formatted("main() {\n    output 0;\n    return 0;\n}\n", &config).unwrap());
+        assert!(!is_formatted("main() { output 0; return 0; }", &config).unwrap());
+        assert!(is_formatted("main() {", &config).is_err());
+    }
+}
@@ -0,0 +1,698 @@
+//! Random generation of TIP programs for property testing.
+//!
+//! Generated programs always parse back from their printed form and resolve
+//! without errors. With `GenConfig::well_typed` they also type check and
+//! terminate: functions only call functions defined before them, every
+//! loop counts a variable of its own down from a small constant, `continue`
+//! only appears in `for` loops, whose step still runs after it, pointers are
+//! only ever made with `alloc` or `&`, and divisors are nonzero constants.
+//! Globals are integers, which `main` comes last to assign constants to
+//! before anything else; it takes no parameters.
+//!
+//! Nodes carry empty spans at position 0; print the program and parse it
+//! again where real positions matter.
+//!
+//! Besides seeds, the generator can be driven by `arbitrary` data with the
+//! `arbitrary` feature, which implements `Arbitrary` for `Prog`, and by
+//! `proptest` with the `proptest` feature, which provides `prog_strategy`.
+
+use rs_tip_errors::Span;
+
+use crate::{ast::*, symbol::Symbol};
+
+/// Controls the size and the features of generated programs.
+#[derive(Clone, Debug)]
+pub struct GenConfig {
+    /// The number of functions besides `main`.
+    pub funs: usize,
+    /// The maximum number of globals.
+    pub globals: usize,
+    /// The maximum number of locals of a function.
+    pub vars: usize,
+    /// The maximum number of statements in a block.
+    pub stms: usize,
+    /// The maximum nesting depth of expressions and of blocks.
+    pub depth: usize,
+    /// The maximum number of pointer indirections in a type, e.g. 2 for
+    /// `↑↑int`. No pointers are generated when 0.
+    pub pointer_depth: usize,
+    pub records: bool,
+    /// Whether arrays of integers are generated.
+    pub arrays: bool,
+    /// Whether functions are stored in variables and called through them.
+    pub function_values: bool,
+    /// Whether the program has to type check. Otherwise subexpressions are
+    /// generated with random types.
+    pub well_typed: bool,
+    /// Whether the operators, loops and early returns that `desugar` lowers
+    /// are used.
+    pub sugar: bool,
+}
+
+impl Default for GenConfig {
+    fn default() -> GenConfig {
+        GenConfig {
+            funs: 3,
+            globals: 2,
+            vars: 4,
+            stms: 4,
+            depth: 3,
+            pointer_depth: 2,
+            records: true,
+            arrays: true,
+            function_values: true,
+            well_typed: true,
+            sugar: true,
+        }
+    }
+}
+
+/// Generates a program from a seed. The same seed and configuration always
+/// produce the same program.
+pub fn generate(seed: u64, config: &GenConfig) -> Prog {
+    Generator::new(SplitMix64(seed), config).prog()
+}
+
+/// Generates a program from unstructured fuzzer input. Exhausted input
+/// makes every remaining choice the simplest one, so short inputs produce
+/// small programs.
+#[cfg(feature = "arbitrary")]
+pub fn arbitrary_prog(u: &mut arbitrary::Unstructured, config: &GenConfig) -> Prog {
+    Generator::new(u, config).prog()
+}
+
+#[cfg(feature = "arbitrary")]
+impl<'a> arbitrary::Arbitrary<'a> for Prog {
+    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Prog> {
+        Ok(arbitrary_prog(u, &GenConfig::default()))
+    }
+}
+
+/// A strategy generating programs from random seeds. Shrinking shrinks the
+/// seed, not the program.
+#[cfg(feature = "proptest")]
+pub fn prog_strategy(config: GenConfig) -> impl proptest::strategy::Strategy<Value = Prog> {
+    use proptest::strategy::Strategy;
+
+    proptest::num::u64::ANY.prop_map(move |seed| generate(seed, &config))
+}
+
+/// A source of random choices.
+trait Choose {
+    /// Returns a number below `n`, which is at least 1.
+    fn below(&mut self, n: usize) -> usize;
+}
+
+struct SplitMix64(u64);
+
+impl Choose for SplitMix64 {
+    fn below(&mut self, n: usize) -> usize {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^= z >> 31;
+        (z % n as u64) as usize
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+impl Choose for &mut arbitrary::Unstructured<'_> {
+    fn below(&mut self, n: usize) -> usize {
+        self.choose_index(n).unwrap_or(0)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Ty {
+    Int,
+    Record,
+    /// An array of `ARRAY_LEN` integers.
+    Array,
+    Pointer(Box<Ty>),
+    /// A function taking this many integers and returning an integer.
+    Fun(usize),
+}
+
+impl Ty {
+    fn pointer_depth(&self) -> usize {
+        match self {
+            Ty::Pointer(inner) => 1 + inner.pointer_depth(),
+            _ => 0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Loop {
+    While,
+    For,
+}
+
+const FIELDS: [&str; 2] = ["f", "g"];
+
+/// The length of every generated array. Most indices are constants below
+/// it, so that few programs index out of bounds.
+const ARRAY_LEN: usize = 3;
+
+struct Generator<'a, R> {
+    rng: R,
+    config: &'a GenConfig,
+    /// The functions generated so far with their arities.
+    funs: Vec<(Symbol, usize)>,
+    globals: Vec<Symbol>,
+    /// The variables of the current function that have a value.
+    vars: Vec<(Symbol, Ty)>,
+    /// The locals of the current function, including loop counters.
+    locals: Vec<Symbol>,
+    /// The innermost loop around the current statement.
+    loop_: Option<Loop>,
+}
+
+impl<'a, R: Choose> Generator<'a, R> {
+    fn new(rng: R, config: &'a GenConfig) -> Generator<'a, R> {
+        Generator {
+            rng,
+            config,
+            funs: Vec::new(),
+            globals: Vec::new(),
+            vars: Vec::new(),
+            locals: Vec::new(),
+            loop_: None,
+        }
+    }
+
+    /// Returns true `percent` times in a hundred, and false on exhausted
+    /// fuzzer input, so callers make the simpler choice on false.
+    fn chance(&mut self, percent: usize) -> bool {
+        self.rng.below(100) >= 100 - percent
+    }
+
+    fn pick<'t, T>(&mut self, items: &'t [T]) -> Option<&'t T> {
+        match items.len() {
+            0 => None,
+            n => Some(&items[self.rng.below(n)]),
+        }
+    }
+
+    fn prog(mut self) -> Prog {
+        self.globals = (0..self.rng.below(self.config.globals + 1))
+            .map(|i| Symbol::intern(&format!("g{}", i)))
+            .collect();
+        let mut funs = Vec::new();
+        for i in 0..self.config.funs {
+            let arity = self.rng.below(3);
+            funs.push(self.fun(Symbol::intern(&format!("f{}", i)), arity));
+        }
+        funs.push(self.fun(Symbol::intern("main"), 0));
+        Prog {
+            imports: Vec::new(),
+            globals: self.globals.iter().map(|&global| node(global)).collect(),
+            funs,
+        }
+    }
+
+    fn fun(&mut self, name: Symbol, arity: usize) -> AstFun {
+        let params: Vec<AstSymbol> = (0..arity)
+            .map(|i| node(Symbol::intern(&format!("a{}", i))))
+            .collect();
+        self.vars = params.iter().map(|param| (param.node, Ty::Int)).collect();
+        self.vars
+            .extend(self.globals.iter().map(|&global| (global, Ty::Int)));
+        self.locals.clear();
+
+        // Every local gets a value before the other statements run, so that
+        // pointers are never dereferenced before they point somewhere.
+        // Constants for the globals keep calls from reading them first.
+        let mut stms = Vec::new();
+        if name.as_str() == "main" {
+            for &global in &self.globals {
+                let value = int(self.rng.below(10) as i32);
+                stms.push(node(Stm::IdentifierAssign(node(global), value)));
+            }
+        }
+        for _ in 0..self.rng.below(self.config.vars + 1) {
+            let ty = self.random_ty();
+            let exp = self.exp(&ty, self.config.depth);
+            let local = self.local("x");
+            stms.push(node(Stm::IdentifierAssign(node(local), exp)));
+            self.vars.push((local, ty));
+        }
+        stms.extend(self.block(self.config.depth));
+        let mut return_ = Some(self.exp(&Ty::Int, self.config.depth));
+        if self.config.sugar && self.chance(25) {
+            // End in an `if` returning from both branches instead.
+            let cond = self.exp(&Ty::Int, self.config.depth);
+            let then = self.exp(&Ty::Int, self.config.depth);
+            let else_ = return_.take().unwrap();
+            stms.push(node(Stm::If(
+                cond,
+                vec![node(Stm::Return(then))],
+                Some(vec![node(Stm::Return(else_))]),
+            )));
+        }
+
+        self.funs.push((name, arity));
+        node(Fun {
+            name: node(name),
+            params,
+            vars: self.locals.iter().map(|&local| node(local)).collect(),
+            stms,
+            return_,
+        })
+    }
+
+    /// Declares a new local named after `prefix`.
+    fn local(&mut self, prefix: &str) -> Symbol {
+        let local = Symbol::intern(&format!("{}{}", prefix, self.locals.len()));
+        self.locals.push(local);
+        local
+    }
+
+    fn random_ty(&mut self) -> Ty {
+        let mut arities: Vec<usize> = self.funs.iter().map(|&(_, arity)| arity).collect();
+        if !self.config.function_values {
+            arities.clear();
+        }
+        let base = match self.rng.below(5) {
+            1 if self.config.records => Ty::Record,
+            3 if self.config.arrays => Ty::Array,
+            2 => match self.pick(&arities) {
+                Some(&arity) => Ty::Fun(arity),
+                None => Ty::Int,
+            },
+            _ => Ty::Int,
+        };
+        if matches!(base, Ty::Fun(_)) {
+            return base;
+        }
+        let depth = self.rng.below(self.config.pointer_depth + 1);
+        (0..depth).fold(base, |ty, _| Ty::Pointer(Box::new(ty)))
+    }
+
+    /// Returns the type to generate for a subexpression of type `ty`.
+    fn child_ty(&mut self, ty: &Ty) -> Ty {
+        if !self.config.well_typed && self.chance(50) {
+            self.random_ty()
+        } else {
+            ty.clone()
+        }
+    }
+
+    fn vars_of(&self, ty: &Ty) -> Vec<Symbol> {
+        self.vars
+            .iter()
+            .filter(|(_, var_ty)| var_ty == ty)
+            .map(|&(var, _)| var)
+            .collect()
+    }
+
+    fn block(&mut self, depth: usize) -> Vec<AstStm> {
+        let mut stms = Vec::new();
+        for _ in 0..self.rng.below(self.config.stms + 1) {
+            self.stm(depth, &mut stms);
+        }
+        stms
+    }
+
+    fn stm(&mut self, depth: usize, stms: &mut Vec<AstStm>) {
+        let var = self.pick(&self.vars.clone()).cloned();
+        let stm = match self.rng.below(6) {
+            1 if depth > 0 => {
+                let cond = self.exp(&Ty::Int, self.config.depth);
+                let then = self.block(depth - 1);
+                let else_ = match self.chance(50) {
+                    true => Some(self.block(depth - 1)),
+                    false => None,
+                };
+                Stm::If(cond, then, else_)
+            }
+            2 if depth > 0 => {
+                let counter = self.local("c");
+                let start = int(self.rng.below(4) as i32);
+                let init = node(Stm::IdentifierAssign(node(counter), start));
+                let cond = binary(ident(counter), BinOp::Greater, int(0));
+                let decrement = binary(ident(counter), BinOp::Subtract, int(1));
+                let step = node(Stm::IdentifierAssign(node(counter), decrement));
+                let kind = match self.config.sugar && self.chance(50) {
+                    true => Loop::For,
+                    false => Loop::While,
+                };
+                let outer = self.loop_.replace(kind);
+                let mut body = self.block(depth - 1);
+                self.loop_ = outer;
+                match kind {
+                    Loop::For => Stm::For(Box::new(init), cond, Box::new(step), body),
+                    Loop::While => {
+                        stms.push(init);
+                        body.push(step);
+                        Stm::While(cond, body)
+                    }
+                }
+            }
+            3 => Stm::Output(self.exp(&Ty::Int, self.config.depth)),
+            4 if self.config.sugar && self.loop_.is_some() && self.chance(50) => {
+                match self.loop_ == Some(Loop::For) && self.chance(50) {
+                    true => Stm::Continue,
+                    false => Stm::Break,
+                }
+            }
+            // Only nested blocks return early, so that the rest of the
+            // function still runs sometimes.
+            5 if self.config.sugar && depth < self.config.depth && self.chance(25) => {
+                Stm::Return(self.exp(&Ty::Int, self.config.depth))
+            }
+            _ => match var {
+                Some((var, Ty::Pointer(inner))) if self.chance(50) => match *inner {
+                    Ty::Record if self.chance(50) => {
+                        let field = self.field();
+                        let exp = self.exp(&Ty::Int, self.config.depth);
+                        Stm::DereferenceFieldAssign(ident(var), field, exp)
+                    }
+                    inner => Stm::PointerAssign(ident(var), self.exp(&inner, self.config.depth)),
+                },
+                Some((var, Ty::Record)) if self.chance(50) => {
+                    let field = self.field();
+                    let exp = self.exp(&Ty::Int, self.config.depth);
+                    Stm::FieldAssign(node(var), field, exp)
+                }
+                Some((var, Ty::Array)) if self.chance(50) => {
+                    let index = self.index(self.config.depth);
+                    let exp = self.exp(&Ty::Int, self.config.depth);
+                    Stm::IndexAssign(node(var), index, exp)
+                }
+                Some((var, ty)) => {
+                    Stm::IdentifierAssign(node(var), self.exp(&ty, self.config.depth))
+                }
+                None => Stm::Output(self.exp(&Ty::Int, self.config.depth)),
+            },
+        };
+        stms.push(node(stm));
+    }
+
+    fn field(&mut self) -> AstSymbol {
+        node(Symbol::intern(FIELDS[self.rng.below(FIELDS.len())]))
+    }
+
+    fn exp(&mut self, ty: &Ty, depth: usize) -> AstExp {
+        let ty = &self.child_ty(ty);
+        let vars = self.vars_of(ty);
+        if depth == 0 || !self.chance(75) {
+            return self.leaf(ty, &vars);
+        }
+        let depth = depth - 1;
+        let exp = match ty {
+            Ty::Int => match self.rng.below(10) {
+                0 | 1 => {
+                    let ops: &[BinOp] = if self.config.sugar {
+                        &[
+                            BinOp::Add,
+                            BinOp::Subtract,
+                            BinOp::Multiply,
+                            BinOp::Greater,
+                            BinOp::Equal,
+                            BinOp::NotEqual,
+                            BinOp::Less,
+                            BinOp::LessEqual,
+                            BinOp::GreaterEqual,
+                            BinOp::And,
+                            BinOp::Or,
+                        ]
+                    } else {
+                        &[
+                            BinOp::Add,
+                            BinOp::Subtract,
+                            BinOp::Multiply,
+                            BinOp::Greater,
+                            BinOp::Equal,
+                        ]
+                    };
+                    let op = ops[self.rng.below(ops.len())];
+                    binary(self.exp(ty, depth), op, self.exp(ty, depth))
+                }
+                2 => binary(
+                    self.exp(ty, depth),
+                    BinOp::Divide,
+                    int(1 + self.rng.below(9) as i32),
+                ),
+                3 => {
+                    let op = if self.config.sugar && self.chance(50) {
+                        UnOp::Not
+                    } else {
+                        UnOp::Negative
+                    };
+                    node(Exp::Unary(op, Box::new(self.exp(ty, depth))))
+                }
+                4 if self.config.pointer_depth > 0 => {
+                    let pointer = self.exp(&Ty::Pointer(Box::new(Ty::Int)), depth);
+                    node(Exp::Dereference(Box::new(pointer)))
+                }
+                5 if self.config.records => {
+                    let record = self.exp(&Ty::Record, depth);
+                    let field = self.field();
+                    node(Exp::Field(Box::new(record), field))
+                }
+                6 if !self.funs.is_empty() => self.call(depth),
+                7 => node(Exp::Paren(Box::new(self.exp(ty, depth)))),
+                8 if self.config.arrays => {
+                    let array = self.exp(&Ty::Array, depth);
+                    let index = self.index(depth);
+                    node(Exp::Index(Box::new(array), Box::new(index)))
+                }
+                _ => self.leaf(ty, &vars),
+            },
+            Ty::Array => {
+                let elements = (0..ARRAY_LEN).map(|_| self.exp(&Ty::Int, depth)).collect();
+                node(Exp::Array(elements))
+            }
+            Ty::Record => {
+                let fields = FIELDS
+                    .iter()
+                    .map(|&name| {
+                        let value = self.exp(&Ty::Int, depth);
+                        node(Field {
+                            name: node(Symbol::intern(name)),
+                            value: Box::new(value),
+                        })
+                    })
+                    .collect();
+                node(Exp::Record(fields))
+            }
+            Ty::Pointer(inner) => {
+                let targets = self.vars_of(inner);
+                match self.rng.below(3) {
+                    0 if !targets.is_empty() => {
+                        let target = *self.pick(&targets).unwrap();
+                        node(Exp::Pointer(node(target)))
+                    }
+                    1 if ty.pointer_depth() < self.config.pointer_depth => {
+                        let pointer = self.exp(&Ty::Pointer(Box::new(ty.clone())), depth);
+                        node(Exp::Dereference(Box::new(pointer)))
+                    }
+                    _ => node(Exp::Alloc(Box::new(self.exp(inner, depth)))),
+                }
+            }
+            Ty::Fun(_) => self.leaf(ty, &vars),
+        };
+        exp
+    }
+
+    /// Returns a variable of type `ty` or, mostly when there is none, a
+    /// small expression of that type that does not need any variable.
+    fn leaf(&mut self, ty: &Ty, vars: &[Symbol]) -> AstExp {
+        if let Some(&var) = self.pick(vars) {
+            if !self.chance(25) {
+                return ident(var);
+            }
+        }
+        match ty {
+            Ty::Int => match self.rng.below(4) {
+                0 => node(Exp::Input),
+                _ => int(self.rng.below(10) as i32),
+            },
+            Ty::Record => {
+                let fields = FIELDS
+                    .iter()
+                    .map(|&name| {
+                        let value = self.leaf(&Ty::Int, &[]);
+                        node(Field {
+                            name: node(Symbol::intern(name)),
+                            value: Box::new(value),
+                        })
+                    })
+                    .collect();
+                node(Exp::Record(fields))
+            }
+            Ty::Array => {
+                let elements = (0..ARRAY_LEN).map(|_| self.leaf(&Ty::Int, &[])).collect();
+                node(Exp::Array(elements))
+            }
+            Ty::Pointer(inner) => node(Exp::Alloc(Box::new(self.leaf(inner, &[])))),
+            Ty::Fun(arity) => {
+                let funs: Vec<Symbol> = self
+                    .funs
+                    .iter()
+                    .filter(|(_, fun_arity)| fun_arity == arity)
+                    .map(|&(fun, _)| fun)
+                    .collect();
+                ident(*self.pick(&funs).unwrap())
+            }
+        }
+    }
+
+    /// Returns an index into an array, mostly a constant within bounds.
+    fn index(&mut self, depth: usize) -> AstExp {
+        match self.chance(25) {
+            true => self.exp(&Ty::Int, depth),
+            false => int(self.rng.below(ARRAY_LEN) as i32),
+        }
+    }
+
+    /// Returns a call of an earlier function, directly or through a variable.
+    fn call(&mut self, depth: usize) -> AstExp {
+        let &(fun, mut arity) = self.pick(&self.funs.clone()).unwrap();
+        let callee = match self.config.function_values && self.chance(50) {
+            true => self.exp(&Ty::Fun(arity), depth),
+            false => ident(fun),
+        };
+        // Functions called by name take their own number of arguments, even
+        // when the callee was generated with a random type.
+        let mut name = &callee;
+        while let Exp::Paren(inner) = &name.node {
+            name = inner;
+        }
+        if let Exp::Identifier(name) = &name.node {
+            if let Some(&(_, fun_arity)) = self.funs.iter().find(|(fun, _)| *fun == name.node) {
+                arity = fun_arity;
+            }
+        }
+        let args = (0..arity).map(|_| self.exp(&Ty::Int, depth)).collect();
+        node(Exp::Call(Box::new(callee), args))
+    }
+}
+
+fn node<T>(node: T) -> Spanned<T> {
+    node.into_spanned(Span::new(0, 0))
+}
+
+fn int(n: i32) -> AstExp {
+    node(Exp::Int(node(n)))
+}
+
+fn ident(name: Symbol) -> AstExp {
+    node(Exp::Identifier(node(name)))
+}
+
+fn binary(lhs: AstExp, op: BinOp, rhs: AstExp) -> AstExp {
+    node(Exp::Binary(Box::new(lhs), op, Box::new(rhs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lexer::Lexer, parser::ProgParser, printer::print_prog, printer::tests::eq_prog,
+        resolve::resolve,
+    };
+    use expect_test::expect;
+    use rs_tip_errors::FileId;
+
+    #[test]
+    fn generates_programs_that_round_trip_and_resolve() {
+        for well_typed in [true, false] {
+            let config = GenConfig {
+                well_typed,
+                ..GenConfig::default()
+            };
+            for seed in 0..200 {
+                let prog = generate(seed, &config);
+                let printed = print_prog(&prog);
+                let reparsed = ProgParser::new()
+                    .parse(FileId::default(), Lexer::new(&printed))
+                    .unwrap_or_else(|err| panic!("{:?} in\n{}", err, printed))
+                    .node;
+                assert!(
+                    eq_prog(&prog, &reparsed),
+                    "does not round-trip:\n{}",
+                    printed
+                );
+
+                let mut diagnostics = Vec::new();
+                resolve(&reparsed.into_spanned(Span::new(0, 0)), &mut diagnostics);
+                assert!(diagnostics.is_empty(), "{:?} in\n{}", diagnostics, printed);
+            }
+        }
+    }
+
+    #[test]
+    fn small_program() {
+        let config = GenConfig {
+            funs: 1,
+            vars: 2,
+            stms: 2,
+            depth: 2,
+            ..GenConfig::default()
+        };
+        expect![[r#"
+            f0() {
+                var c0, c1;
+                output 0 / 4 / 2;
+                if (0) {
+                    c0 = 1;
+                    while (c0 > 0) {
+                        c0 = c0 - 1;
+                    }
+                } else {
+                    c1 = 1;
+                    while (c1 > 0) {
+                        output 7;
+                        output 4;
+                        c1 = c1 - 1;
+                    }
+                }
+                return *(alloc 6);
+            }
+
+            main() {
+                var x0;
+                x0 = alloc (-0);
+                output *x0;
+                if ([1, 7, 5][*x0]) {}
+                return 7;
+            }
+        "#]]
+        .assert_eq(&print_prog(&generate(3, &config)));
+    }
+
+    #[test]
+    fn same_seed_same_program() {
+        let config = GenConfig::default();
+        assert_eq!(generate(7, &config), generate(7, &config));
+        assert_ne!(generate(7, &config), generate(8, &config));
+    }
+
+    #[cfg(feature = "arbitrary")]
+    #[test]
+    fn arbitrary_programs() {
+        let mut u = arbitrary::Unstructured::new(&[]);
+        let prog = arbitrary_prog(&mut u, &GenConfig::default());
+        expect![[r#"
+            f0() {
+                return input;
+            }
+
+            f1() {
+                return input;
+            }
+
+            f2() {
+                return input;
+            }
+
+            main() {
+                return input;
+            }
+        "#]]
+        .assert_eq(&print_prog(&prog));
+    }
+}
@@ -1,28 +1,66 @@
+//! The lexer, which turns source text into the tokens the parser reads.
+//!
+//! Interning identifiers makes lexing slower, not faster: every identifier
+//! costs a hash lookup. On the program of `benches/lexer.rs`, 7 MB with 2.4
+//! million tokens, lexing takes about 31 ms with tokens borrowing their text
+//! from the source, 43 ms with tokens owning a `String` as they used to, and
+//! 52 to 57 ms with interned symbols. Lexing is still a small part of
+//! parsing, which takes about 500 ms. The AST holds `Symbol`s either way, and
+//! interning in the lexer gives `Copy` tokens with no lifetime. Those tokens
+//! allocate nothing once the interner has seen every name, whereas owned
+//! tokens make one allocation per identifier.
+
+use std::fmt;
+
 use logos::Logos;
-use rs_tip_errors::{Diagnostic, Span};
+use rs_tip_errors::{Diagnostic, FileId, Span};
 use thiserror::Error;
 
+use crate::symbol::Symbol;
+
 #[derive(Error, Debug)]
 pub enum LexicalError {
     #[error("Parse error: invalid input `{0}`")]
     InvalidInput(String),
+    #[error("Parse error: integer literal `{0}` does not fit in 32 bits")]
+    IntegerOverflow(String),
+}
+
+impl LexicalError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexicalError::InvalidInput(_) => "S0001",
+            LexicalError::IntegerOverflow(_) => "S0002",
+        }
+    }
 }
 
 pub type Spanned = anyhow::Result<(usize, Token, usize)>;
 
 pub struct Lexer<'source> {
     inner: logos::Lexer<'source, Token>,
+    file: FileId,
 }
 
 impl<'source> Lexer<'source> {
     pub fn new(source: &'source str) -> Self {
         Lexer {
             inner: Token::lexer(source),
+            file: FileId::default(),
         }
     }
 
+    /// Makes the diagnostics of the lexer point into `file`.
+    pub fn with_file(mut self, file: FileId) -> Self {
+        self.file = file;
+        self
+    }
+
     fn err_span<T>(&mut self, err: LexicalError, start: usize, end: usize) -> anyhow::Result<T> {
-        Err(Diagnostic::new(err, Span::new(start, end)).into())
+        let code = err.code();
+        Err(Diagnostic::new(err, Span::in_file(self.file, start, end))
+            .with_code(code)
+            .into())
     }
 }
 
@@ -30,32 +68,61 @@ impl<'source> Iterator for Lexer<'source> {
     type Item = Spanned;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let token = self.inner.next()?;
+        let token = loop {
+            match self.inner.next()? {
+                Token::Comment | Token::Allow => continue,
+                token => break token,
+            }
+        };
         let span = self.inner.span();
 
         Some(if token == Token::Error {
-            self.err_span(
-                LexicalError::InvalidInput(self.inner.slice().to_owned()),
-                span.start,
-                span.end,
-            )
+            let slice = self.inner.slice().to_owned();
+            // Digit runs only fail to lex when they overflow `Token::Int`.
+            let err = if slice.bytes().all(|b| b.is_ascii_digit()) {
+                LexicalError::IntegerOverflow(slice)
+            } else {
+                LexicalError::InvalidInput(slice)
+            };
+            self.err_span(err, span.start, span.end)
         } else {
             Ok((span.start, token, span.end))
         })
     }
 }
 
-#[derive(Logos, Debug, Clone, PartialEq, Eq)]
+/// Tokens own no heap data: identifiers and strings are interned, and
+/// comments are only spans into the source. Once the interner has seen every
+/// name in a program, lexing it allocates nothing.
+#[derive(Logos, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Token {
     #[error]
-    #[regex(r"[ \t\n\f]+", logos::skip)]
+    #[regex(r"[ \t\r\n\f]+", logos::skip)]
     Error,
 
+    // Comments are kept as tokens so the formatter can see them; `Lexer`
+    // filters them out before they reach the parser.
+    #[regex(r"//[^\n]*")]
+    #[token("/*", block_comment)]
+    Comment,
+
+    // A `// tip-allow(code, ...)` comment, naming the warnings to suppress in
+    // the following statement or function. `allow_codes` reads the codes
+    // from the comment's slice.
+    #[regex(r"//[ \t]*tip-allow\([^)\n]*\)[^\n]*")]
+    Allow,
+
     #[regex("[0-9]+", |lex| lex.slice().parse())]
     Int(i32),
 
-    #[regex("[a-z]+", |lex| lex.slice().to_string())]
-    Identifier(String),
+    // String literals only name imported files, so there are no escapes.
+    #[regex(r#""[^"\n]*""#, |lex| Symbol::intern(&lex.slice()[1..lex.slice().len() - 1]))]
+    String(Symbol),
+
+    // Keywords are matched against the whole identifier, so `iffy` or
+    // `input_1` are identifiers rather than a keyword followed by more text.
+    #[regex("[a-zA-Z_][a-zA-Z0-9_]*", |lex| Symbol::intern(lex.slice()))]
+    Identifier(Symbol),
 
     // Keywords
     #[token("input")]
@@ -68,6 +135,12 @@ pub enum Token {
     Else,
     #[token("while")]
     While,
+    #[token("for")]
+    For,
+    #[token("break")]
+    Break,
+    #[token("continue")]
+    Continue,
     #[token("return")]
     Return,
     #[token("var")]
@@ -76,6 +149,8 @@ pub enum Token {
     Alloc,
     #[token("null")]
     Null,
+    #[token("import")]
+    Import,
 
     // Symbols
     #[token(",")]
@@ -98,23 +173,116 @@ pub enum Token {
     Slash,
     #[token(">")]
     Greater,
+    #[token(">=")]
+    GreaterEqual,
+    #[token("<")]
+    Less,
+    #[token("<=")]
+    LessEqual,
     #[token("=")]
     Equal,
     #[token("==")]
     EqualEqual,
+    #[token("!=")]
+    BangEqual,
+    #[token("!")]
+    Bang,
+    #[token("&&")]
+    AmpersandAmpersand,
+    #[token("||")]
+    PipePipe,
     #[token("(")]
     OpeningRound,
     #[token("{")]
     OpeningCurly,
+    #[token("[")]
+    OpeningSquare,
     #[token(")")]
     ClosingRound,
     #[token("}")]
     ClosingCurly,
+    #[token("]")]
+    ClosingSquare,
+}
+
+/// Displays a token as it appears in the source. Comments are never shown to
+/// the parser, so they display as a description rather than their text.
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            Token::Error => "<error>",
+            Token::Comment => "<comment>",
+            Token::Allow => "<tip-allow comment>",
+            Token::Int(value) => return write!(f, "{}", value),
+            Token::String(string) => return write!(f, "\"{}\"", string),
+            Token::Identifier(name) => return write!(f, "{}", name),
+            Token::Input => "input",
+            Token::Output => "output",
+            Token::If => "if",
+            Token::Else => "else",
+            Token::While => "while",
+            Token::For => "for",
+            Token::Break => "break",
+            Token::Continue => "continue",
+            Token::Return => "return",
+            Token::Var => "var",
+            Token::Alloc => "alloc",
+            Token::Null => "null",
+            Token::Import => "import",
+            Token::Comma => ",",
+            Token::Dot => ".",
+            Token::Colon => ":",
+            Token::Semicolon => ";",
+            Token::Ampersand => "&",
+            Token::Plus => "+",
+            Token::Minus => "-",
+            Token::Star => "*",
+            Token::Slash => "/",
+            Token::Greater => ">",
+            Token::GreaterEqual => ">=",
+            Token::Less => "<",
+            Token::LessEqual => "<=",
+            Token::Equal => "=",
+            Token::EqualEqual => "==",
+            Token::BangEqual => "!=",
+            Token::Bang => "!",
+            Token::AmpersandAmpersand => "&&",
+            Token::PipePipe => "||",
+            Token::OpeningRound => "(",
+            Token::OpeningCurly => "{",
+            Token::OpeningSquare => "[",
+            Token::ClosingRound => ")",
+            Token::ClosingCurly => "}",
+            Token::ClosingSquare => "]",
+        };
+        f.write_str(text)
+    }
+}
+
+/// Returns the codes named by the slice of a `Token::Allow` comment.
+pub(crate) fn allow_codes(comment: &str) -> impl Iterator<Item = &str> {
+    let open = comment.find('(').unwrap();
+    let close = comment.find(')').unwrap();
+    comment[open + 1..close]
+        .split(',')
+        .map(str::trim)
+        .filter(|code| !code.is_empty())
+}
+
+fn block_comment(lex: &mut logos::Lexer<Token>) -> bool {
+    match lex.remainder().find("*/") {
+        Some(len) => {
+            lex.bump(len + 2);
+            true
+        }
+        None => false,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use expect_test::{expect, Expect};
 
     #[test]
     fn demo() {
@@ -135,4 +303,136 @@ mod tests {
             eprintln!("{:?}", token);
         }
     }
+
+    fn check(input: &str, expect: Expect) {
+        let tokens: Vec<String> = Lexer::new(input)
+            .map(|token| match token {
+                Ok((start, token, end)) => format!("{}..{} {:?}", start, end, token),
+                Err(err) => format!("error {}", err).trim_end().to_owned(),
+            })
+            .collect();
+        expect.assert_eq(&tokens.join("\n"));
+    }
+
+    #[test]
+    fn identifiers() {
+        check(
+            "x1 my_var Node _tmp __ a1b2",
+            expect![[r#"
+                0..2 Identifier("x1")
+                3..9 Identifier("my_var")
+                10..14 Identifier("Node")
+                15..19 Identifier("_tmp")
+                20..22 Identifier("__")
+                23..27 Identifier("a1b2")"#]],
+        );
+    }
+
+    #[test]
+    fn keywords_match_whole_identifier() {
+        check(
+            "if iffy input input_1 var vars null nullable Return",
+            expect![[r#"
+                0..2 If
+                3..7 Identifier("iffy")
+                8..13 Input
+                14..21 Identifier("input_1")
+                22..25 Var
+                26..30 Identifier("vars")
+                31..35 Null
+                36..44 Identifier("nullable")
+                45..51 Identifier("Return")"#]],
+        );
+    }
+
+    #[test]
+    fn longest_operator_wins() {
+        check(
+            "a>=b<=c<d!=!e&&&f||g==h=i>j",
+            expect![[r#"
+                0..1 Identifier("a")
+                1..3 GreaterEqual
+                3..4 Identifier("b")
+                4..6 LessEqual
+                6..7 Identifier("c")
+                7..8 Less
+                8..9 Identifier("d")
+                9..11 BangEqual
+                11..12 Bang
+                12..13 Identifier("e")
+                13..15 AmpersandAmpersand
+                15..16 Ampersand
+                16..17 Identifier("f")
+                17..19 PipePipe
+                19..20 Identifier("g")
+                20..22 EqualEqual
+                22..23 Identifier("h")
+                23..24 Equal
+                24..25 Identifier("i")
+                25..26 Greater
+                26..27 Identifier("j")"#]],
+        );
+    }
+
+    #[test]
+    fn integer_overflow() {
+        check(
+            "x = 2147483647 + 2147483648;",
+            expect![[r#"
+                0..1 Identifier("x")
+                2..3 Equal
+                4..14 Int(2147483647)
+                15..16 Plus
+                error 17:27: Parse error: integer literal `2147483648` does not fit in 32 bits
+                27..28 Semicolon"#]],
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        check(
+            "x ^ 1",
+            expect![[r#"
+                0..1 Identifier("x")
+                error 2:3: Parse error: invalid input `^`
+                4..5 Int(1)"#]],
+        );
+    }
+
+    #[test]
+    fn imports() {
+        check(
+            r#"import "lib/list.tip"; importer "a"#,
+            expect![[r#"
+                0..6 Import
+                7..21 String("lib/list.tip")
+                21..22 Semicolon
+                23..31 Identifier("importer")
+                error 32:34: Parse error: invalid input `"a`"#]],
+        );
+    }
+
+    #[test]
+    fn allow_comments() {
+        let source = "// tip-allow(W0001, T0002) because\n//tip-allow()\n// tip-allowed(W0001)\nx";
+        let mut lexer = Token::lexer(source);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next() {
+            let codes: Vec<&str> = match token {
+                Token::Allow => allow_codes(lexer.slice()).collect(),
+                _ => vec![],
+            };
+            tokens.push((token, codes));
+        }
+        assert_eq!(
+            tokens,
+            [
+                (Token::Allow, vec!["W0001", "T0002"]),
+                (Token::Allow, vec![]),
+                (Token::Comment, vec![]),
+                (Token::Identifier(Symbol::intern("x")), vec![]),
+            ]
+        );
+        check(source, expect![[r#"71..72 Identifier("x")"#]]);
+    }
 }
@@ -1,9 +1,178 @@
-use lalrpop_util::lalrpop_mod;
+use anyhow::anyhow;
+use lalrpop_util::{lalrpop_mod, ParseError};
+use rs_tip_errors::{Diagnostic, DiagnosticSink, FileId, SourceFile, Span};
+
+use std::ops::Range;
+
+use logos::Logos;
+
+use crate::{
+    ast::{AstExp, AstFun, AstProg, AstStm},
+    lexer::{Lexer, Token},
+};
 
 pub mod ast;
-mod lexer;
+pub mod desugar;
+pub mod format;
+pub mod gen;
+pub mod lexer;
+pub mod loader;
+pub mod normalize;
+pub mod printer;
+pub mod resolve;
+pub mod sexp;
+pub mod suppress;
+pub mod symbol;
 
+#[cfg(test)]
+mod conformance_tests;
 #[cfg(test)]
 mod parser_tests;
 
-lalrpop_mod!(pub parser);
+lalrpop_mod!(#[allow(clippy::all)] pub parser);
+
+/// Parses a whole program, reporting syntax errors to `sink`.
+///
+/// There is no error recovery: parsing stops at the first invalid token or
+/// syntax error, so `sink` receives at most one diagnostic per call. Lexer
+/// errors reach the parser as `ParseError::User` and end it the same way.
+pub fn parse(source: &str, sink: &mut dyn DiagnosticSink) -> Option<AstProg> {
+    parse_prog(source).map_err(|err| sink.emit(*err)).ok()
+}
+
+/// Parses a file of a `SourceMap`, giving the nodes and diagnostics spans in
+/// the file.
+pub fn parse_file(file: &SourceFile, sink: &mut dyn DiagnosticSink) -> Option<AstProg> {
+    parse_in_file(file.source(), file.id(), sink)
+}
+
+/// Parses `source` as the text of `file`, for callers that track files
+/// without a `SourceMap`.
+pub fn parse_in_file(source: &str, file: FileId, sink: &mut dyn DiagnosticSink) -> Option<AstProg> {
+    parse_prog_at(source, file)
+        .map_err(|err| sink.emit(*err))
+        .ok()
+}
+
+/// A node parsed on its own, with the syntax errors found in its source.
+#[derive(Debug)]
+pub struct Parse<T> {
+    /// The node, unless the source has a syntax error.
+    pub node: Option<T>,
+    /// The first error in the source, if any, as parsing does not recover.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// Parses a single expression, such as `x + *p`.
+pub fn parse_exp(source: &str) -> Parse<AstExp> {
+    parse_with(source, |lexer| {
+        parser::ExpParser::new().parse(FileId::default(), lexer)
+    })
+}
+
+/// Parses a single statement, such as `*q = 3;`.
+pub fn parse_stm(source: &str) -> Parse<AstStm> {
+    parse_with(source, |lexer| {
+        parser::StmParser::new().parse(FileId::default(), lexer)
+    })
+}
+
+/// Parses a single function.
+pub fn parse_fun(source: &str) -> Parse<AstFun> {
+    parse_with(source, |lexer| {
+        parser::FunParser::new().parse(FileId::default(), lexer)
+    })
+}
+
+fn parse_with<T>(
+    source: &str,
+    parse: impl FnOnce(Lexer) -> Result<T, ParseError<usize, Token, anyhow::Error>>,
+) -> Parse<T> {
+    match parse(Lexer::new(source)) {
+        Ok(node) => Parse {
+            node: Some(node),
+            diagnostics: Vec::new(),
+        },
+        Err(err) => Parse {
+            node: None,
+            diagnostics: vec![parse_error(err, FileId::default())],
+        },
+    }
+}
+
+/// Splits a program into the source ranges of its top-level items, without
+/// parsing it. An item runs from its first token to the `}` closing its
+/// first brace or, for items without braces such as imports and globals, to
+/// the first `;`. Comments between items belong to none of them.
+pub fn item_ranges(source: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut depth = 0usize;
+    let mut lexer = Token::lexer(source);
+    while let Some(token) = lexer.next() {
+        let span = lexer.span();
+        if matches!(token, Token::Comment | Token::Allow) {
+            continue;
+        }
+        let item_start = *start.get_or_insert(span.start);
+        let item_end = match token {
+            Token::OpeningCurly => {
+                depth += 1;
+                false
+            }
+            Token::ClosingCurly => {
+                depth = depth.saturating_sub(1);
+                depth == 0
+            }
+            Token::Semicolon => depth == 0,
+            _ => false,
+        };
+        if item_end {
+            ranges.push(item_start..span.end);
+            start = None;
+        }
+    }
+    if let Some(item_start) = start {
+        ranges.push(item_start..source.trim_end().len());
+    }
+    ranges
+}
+
+pub(crate) fn parse_prog(source: &str) -> Result<AstProg, Box<Diagnostic>> {
+    parse_prog_at(source, FileId::default())
+}
+
+fn parse_prog_at(source: &str, file: FileId) -> Result<AstProg, Box<Diagnostic>> {
+    parser::ProgParser::new()
+        .parse(file, Lexer::new(source).with_file(file))
+        .map_err(|err| Box::new(parse_error(err, file)))
+}
+
+fn parse_error(err: ParseError<usize, Token, anyhow::Error>, file: FileId) -> Diagnostic {
+    let (message, code, start, end) = match err {
+        // The lexer reports its errors as diagnostics.
+        ParseError::User { error } => match error.downcast::<Diagnostic>() {
+            Ok(diagnostic) => return diagnostic,
+            Err(error) => return Diagnostic::new(error, Span::in_file(file, 0, 0)),
+        },
+        // Only LALRPOP's built-in lexer reports invalid tokens; `Lexer` turns
+        // them into `LexicalError::InvalidInput`, which shares the code.
+        ParseError::InvalidToken { location } => {
+            ("invalid token".to_owned(), "S0001", location, location)
+        }
+        ParseError::UnrecognizedEOF { location, .. } => (
+            "unexpected end of file".to_owned(),
+            "S0004",
+            location,
+            location,
+        ),
+        ParseError::UnrecognizedToken {
+            token: (start, token, end),
+            ..
+        }
+        | ParseError::ExtraToken {
+            token: (start, token, end),
+        } => (format!("unexpected token `{}`", token), "S0003", start, end),
+    };
+    Diagnostic::new(anyhow!(message), Span::in_file(file, start, end)).with_code(code)
+}
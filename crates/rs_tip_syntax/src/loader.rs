@@ -0,0 +1,320 @@
+//! Loading of programs split over several files.
+//!
+//! A file lists the files it needs with `import "path";` declarations before
+//! its globals and functions, with paths relative to the directory of the
+//! importing file. Every file is loaded once however often it is imported,
+//! and the globals and functions of all files are merged into a single
+//! program, those of imported files first. Nodes keep the file they were
+//! parsed from, so passes over the merged program report spans in the right
+//! file.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+use rs_tip_errors::{Diagnostic, DiagnosticSink, SourceMap, Span};
+use thiserror::Error;
+
+use crate::{
+    ast::{AstFun, AstProg, AstSymbol, IntoSpanned, Prog},
+    parse_file,
+};
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+    #[error("cannot read imported file `{path}`: {error}")]
+    Unreadable { path: String, error: io::Error },
+    #[error("import cycle: {}", .0.join(" -> "))]
+    Cycle(Vec<String>),
+}
+
+impl ImportError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ImportError::Unreadable { .. } => "I0001",
+            ImportError::Cycle(_) => "I0002",
+        }
+    }
+}
+
+/// Where the loader reads files from.
+pub trait FileSystem {
+    fn read(&self, path: &Path) -> io::Result<String>;
+}
+
+/// The file system of the machine.
+pub struct RealFileSystem;
+
+impl FileSystem for RealFileSystem {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+}
+
+/// An in-memory file system, keyed by normalized paths.
+impl FileSystem for HashMap<PathBuf, String> {
+    fn read(&self, path: &Path) -> io::Result<String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+    }
+}
+
+/// Loads the program whose main file is `path` along with every file it
+/// imports, adding them to `source_map` and reporting syntax and import
+/// errors to `sink`. The merged program has no imports left.
+///
+/// Fails if the main file cannot be read, and returns `None` if any of the
+/// files has a syntax error.
+pub fn load(
+    path: impl AsRef<Path>,
+    fs: &dyn FileSystem,
+    source_map: &mut SourceMap,
+    sink: &mut dyn DiagnosticSink,
+) -> io::Result<Option<AstProg>> {
+    let path = normalize(path.as_ref());
+    let source = fs.read(&path)?;
+    let mut loader = Loader {
+        fs,
+        source_map,
+        sink,
+        stack: Vec::new(),
+        loaded: HashSet::new(),
+        globals: Vec::new(),
+        funs: Vec::new(),
+        failed: false,
+    };
+    let span = loader.load_file(path, source);
+    Ok(match span {
+        Some(span) if !loader.failed => Some(
+            Prog {
+                imports: Vec::new(),
+                globals: loader.globals,
+                funs: loader.funs,
+            }
+            .into_spanned(span),
+        ),
+        _ => None,
+    })
+}
+
+struct Loader<'a> {
+    fs: &'a dyn FileSystem,
+    source_map: &'a mut SourceMap,
+    sink: &'a mut dyn DiagnosticSink,
+    /// The files being loaded, from the main file to the innermost import.
+    stack: Vec<PathBuf>,
+    loaded: HashSet<PathBuf>,
+    globals: Vec<AstSymbol>,
+    funs: Vec<AstFun>,
+    failed: bool,
+}
+
+impl Loader<'_> {
+    /// Loads a file and its imports, returning the span of the file.
+    fn load_file(&mut self, path: PathBuf, source: String) -> Option<Span> {
+        let id = self.source_map.add_file(path.display().to_string(), source);
+        let Some(prog) = parse_file(self.source_map.file(id), self.sink) else {
+            self.failed = true;
+            return None;
+        };
+        self.loaded.insert(path.clone());
+        self.stack.push(path);
+
+        let dir = self.stack.last().unwrap().parent().unwrap_or(Path::new(""));
+        let imports: Vec<_> = prog
+            .imports
+            .iter()
+            .map(|import| (normalize(&dir.join(import.as_str())), import))
+            .collect();
+        for (target, import) in imports {
+            let span = import.span();
+            if let Some(i) = self.stack.iter().position(|path| *path == target) {
+                let cycle = self.stack[i..]
+                    .iter()
+                    .chain([&target])
+                    .map(|path| path.display().to_string())
+                    .collect();
+                self.error(ImportError::Cycle(cycle), span);
+                continue;
+            }
+            if self.loaded.contains(&target) {
+                continue;
+            }
+            match self.fs.read(&target) {
+                Ok(source) => {
+                    self.load_file(target, source);
+                }
+                Err(error) => {
+                    let path = target.display().to_string();
+                    self.error(ImportError::Unreadable { path, error }, span);
+                }
+            }
+        }
+
+        self.stack.pop();
+        let span = prog.span();
+        self.globals.extend(prog.node.globals);
+        self.funs.extend(prog.node.funs);
+        Some(span)
+    }
+
+    fn error(&mut self, err: ImportError, span: Span) {
+        let code = err.code();
+        self.sink.emit(Diagnostic::new(err, span).with_code(code));
+    }
+}
+
+/// Removes `.` components and resolves `..` against the preceding component
+/// without touching the file system.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resolve::resolve;
+    use expect_test::{expect, Expect};
+    use rs_tip_errors::Renderer;
+
+    fn check(files: &[(&str, &str)], expect: Expect) {
+        let fs: HashMap<PathBuf, String> = files
+            .iter()
+            .map(|(path, source)| (PathBuf::from(path), source.to_string()))
+            .collect();
+        let mut source_map = SourceMap::new();
+        let mut diagnostics = Vec::new();
+        let prog = load(files[0].0, &fs, &mut source_map, &mut diagnostics).unwrap();
+
+        let mut out = String::new();
+        if let Some(prog) = &prog {
+            for fun in &prog.funs {
+                let span = fun.name.span();
+                let file = source_map.file(span.file());
+                out += &format!("{} in {}\n", &fun.name.node, file.name());
+                assert_eq!(&file.source()[span.start()..span.end()], fun.name.as_str());
+            }
+            resolve(prog, &mut diagnostics);
+        }
+        for diagnostic in &diagnostics {
+            out += &Renderer::new(false).render(diagnostic, &source_map);
+        }
+        expect.assert_eq(&out);
+    }
+
+    #[test]
+    fn merges_imported_functions() {
+        check(
+            &[
+                (
+                    "main.tip",
+                    r#"import "lib/list.tip"; import "./lib/../util.tip";
+main() { return cons(one(), 0); }"#,
+                ),
+                (
+                    "lib/list.tip",
+                    r#"import "../util.tip"; cons(h, t) { return {h: h, t: t}; }"#,
+                ),
+                ("util.tip", "one() { return 1; }"),
+            ],
+            expect![[r#"
+                one in util.tip
+                cons in lib/list.tip
+                main in main.tip
+            "#]],
+        );
+    }
+
+    #[test]
+    fn duplicate_functions_across_files() {
+        check(
+            &[
+                ("main.tip", "import \"lib.tip\";\n\nid(x) { return x; }\n"),
+                ("lib.tip", "id(y) {\n    return y;\n}\n"),
+            ],
+            expect![[r#"
+                id in lib.tip
+                id in main.tip
+                error[R0002]: function `id` is defined more than once
+                 --> main.tip:3:1
+                  |
+                3 | id(x) { return x; }
+                  | ^^
+                 ::: lib.tip:1:1
+                  |
+                1 | id(y) {
+                  | -- first defined here
+            "#]],
+        );
+    }
+
+    #[test]
+    fn import_errors() {
+        check(
+            &[
+                (
+                    "main.tip",
+                    "import \"a.tip\";\nimport \"missing.tip\";\nmain() { return f(); }\n",
+                ),
+                ("a.tip", "import \"b.tip\";\nf() { return 1; }\n"),
+                ("b.tip", "import \"main.tip\";\n"),
+            ],
+            expect![[r#"
+                f in a.tip
+                main in main.tip
+                error[I0002]: import cycle: main.tip -> a.tip -> b.tip -> main.tip
+                 --> b.tip:1:8
+                  |
+                1 | import "main.tip";
+                  |        ^^^^^^^^^^
+                error[I0001]: cannot read imported file `missing.tip`: entity not found
+                 --> main.tip:2:8
+                  |
+                2 | import "missing.tip";
+                  |        ^^^^^^^^^^^^^
+            "#]],
+        );
+    }
+
+    #[test]
+    fn syntax_errors_in_imported_files() {
+        check(
+            &[
+                ("main.tip", "import \"lib.tip\";\nmain() { return 0; }\n"),
+                ("lib.tip", "f() { return 0 }\n"),
+            ],
+            expect![[r#"
+                error[S0003]: unexpected token `}`
+                 --> lib.tip:1:16
+                  |
+                1 | f() { return 0 }
+                  |                ^
+            "#]],
+        );
+    }
+
+    #[test]
+    fn main_file_must_exist() {
+        let mut source_map = SourceMap::new();
+        let fs = HashMap::new();
+        assert!(load("main.tip", &fs, &mut source_map, &mut Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,522 @@
+//! Normalization to TIP normal form.
+//!
+//! Programs are desugared first, so only the core operators and loops remain,
+//! and functions only return at the end.
+//!
+//! The pointer analyses of the TIP book work on programs whose statements
+//! do one thing each, so that every pointer operation has a variable for
+//! each of its operands. Normalization introduces fresh temporaries for the
+//! values of nested expressions, leaving only these statements, where `a`
+//! stands for an atom (a variable, an integer constant or `null`) and `x`,
+//! `y` and `f` for variables:
+//!
+//! ```text
+//! x = a           x = input        x = a op b       x = -a
+//! x = alloc a     x = &y           x = *y           x = y.f
+//! x = {f: a, ...} x = f(a, ...)    *x = a           x.f = a
+//! (*x).f = a      x = [a, ...]     x = y[a]         x[a] = b
+//! output a        if (a) ...       while (a) ...
+//! ```
+//!
+//! Functions return an atom. Parentheses are dropped. Expressions are still
+//! evaluated left to right: when an operand calls a function, which could
+//! assign to a variable through a pointer, the variables among the earlier
+//! operands are copied to temporaries before the call. The statements
+//! computing the condition of a `while` loop are repeated at the end of its
+//! body.
+//!
+//! Every node keeps the span of the node it was made from, an expression in
+//! parentheses taking the span of the parentheses, and a temporary
+//! has the span of the expression whose value it holds, as recorded in
+//! `Normalized::temps`.
+
+use std::collections::{HashMap, HashSet};
+
+use rs_tip_errors::Span;
+
+use crate::{
+    ast::{visit, *},
+    desugar::desugar,
+    symbol::Symbol,
+};
+
+/// A normalized program.
+#[derive(Debug)]
+pub struct Normalized {
+    pub prog: Prog,
+    /// The span of the expression each temporary introduced by
+    /// normalization holds the value of. Temporaries are named uniquely
+    /// across the program.
+    pub temps: HashMap<Symbol, Span>,
+}
+
+/// Normalizes a program, desugaring it first.
+pub fn normalize(prog: &Prog) -> Normalized {
+    let prog = &desugar(prog);
+    let mut normalizer = Normalizer {
+        fresh: Fresh::new(prog),
+        funs: prog.funs.iter().map(|fun| fun.name.node).collect(),
+        locals: HashSet::new(),
+        temps: HashMap::new(),
+        fun_temps: Vec::new(),
+        stms: Vec::new(),
+    };
+    let funs = prog.funs.iter().map(|fun| normalizer.fun(fun)).collect();
+    Normalized {
+        prog: Prog {
+            imports: prog.imports.clone(),
+            globals: prog.globals.clone(),
+            funs,
+        },
+        temps: normalizer.temps,
+    }
+}
+
+struct Normalizer {
+    fresh: Fresh,
+    funs: HashSet<Symbol>,
+    /// The parameters and locals of the current function.
+    locals: HashSet<Symbol>,
+    temps: HashMap<Symbol, Span>,
+    /// The temporaries declared so far in the current function.
+    fun_temps: Vec<AstSymbol>,
+    /// The statements of the current block.
+    stms: Vec<AstStm>,
+}
+
+impl Normalizer {
+    fn fun(&mut self, fun: &AstFun) -> AstFun {
+        self.locals = fun.params.iter().chain(&fun.vars).map(|v| v.node).collect();
+        for stm in &fun.stms {
+            self.stm(stm);
+        }
+        let return_ = fun.return_.as_ref().map(|return_| self.atom(return_));
+        let stms = std::mem::take(&mut self.stms);
+        let mut vars = fun.vars.clone();
+        vars.append(&mut self.fun_temps);
+        Spanned {
+            start: fun.start,
+            end: fun.end,
+            file: fun.file,
+            node: Fun {
+                name: fun.name.clone(),
+                params: fun.params.clone(),
+                vars,
+                stms,
+                return_,
+            },
+        }
+    }
+
+    fn block(&mut self, stms: &[AstStm]) -> Vec<AstStm> {
+        let outer = std::mem::take(&mut self.stms);
+        for stm in stms {
+            self.stm(stm);
+        }
+        std::mem::replace(&mut self.stms, outer)
+    }
+
+    fn stm(&mut self, stm: &AstStm) {
+        let node = match &stm.node {
+            Stm::IdentifierAssign(name, exp) => {
+                let value = self.value(exp);
+                Stm::IdentifierAssign(name.clone(), value)
+            }
+            Stm::PointerAssign(target, exp) => {
+                let (target, value) = self.pair(target, exp);
+                Stm::PointerAssign(self.variable(target), value)
+            }
+            Stm::FieldAssign(name, field, exp) => {
+                let value = self.atom(exp);
+                Stm::FieldAssign(name.clone(), field.clone(), value)
+            }
+            Stm::DereferenceFieldAssign(target, field, exp) => {
+                let (target, value) = self.pair(target, exp);
+                Stm::DereferenceFieldAssign(self.variable(target), field.clone(), value)
+            }
+            Stm::IndexAssign(name, index, exp) => {
+                let (index, value) = self.pair(index, exp);
+                Stm::IndexAssign(name.clone(), index, value)
+            }
+            Stm::Output(exp) => Stm::Output(self.atom(exp)),
+            Stm::If(cond, then, else_) => {
+                let cond = self.atom(cond);
+                let then = self.block(then);
+                let else_ = else_.as_ref().map(|else_| self.block(else_));
+                Stm::If(cond, then, else_)
+            }
+            Stm::While(cond, body) => {
+                let before = self.stms.len();
+                let cond = self.atom(cond);
+                let compute_cond = self.stms[before..].to_vec();
+                let mut body = self.block(body);
+                body.extend(compute_cond);
+                Stm::While(cond, body)
+            }
+            Stm::For(..) | Stm::Return(_) => unreachable!("desugared: {:?}", stm.node),
+            Stm::Break | Stm::Continue => stm.node.clone(),
+        };
+        self.push(stm.span(), node);
+    }
+
+    fn push(&mut self, span: Span, stm: Stm) {
+        self.stms.push(stm.into_spanned(span));
+    }
+
+    /// Normalizes `exp` to the right-hand side of an assignment.
+    fn value(&mut self, exp: &AstExp) -> AstExp {
+        let node = match &exp.node {
+            // The expression takes the place of the parentheses, span and all.
+            Exp::Paren(inner) => self.value(inner).node,
+            Exp::Int(_) | Exp::Identifier(_) | Exp::Input | Exp::Pointer(_) | Exp::Null => {
+                exp.node.clone()
+            }
+            Exp::Unary(op, inner) => match inner.node {
+                Exp::Int(_) => exp.node.clone(),
+                _ => Exp::Unary(*op, Box::new(self.atom(inner))),
+            },
+            Exp::Binary(lhs, op, rhs) => {
+                let (lhs, rhs) = self.pair(lhs, rhs);
+                Exp::Binary(Box::new(lhs), *op, Box::new(rhs))
+            }
+            Exp::Call(callee, args) => {
+                let mut operands = self.operands(std::iter::once(&**callee).chain(args));
+                let callee = operands.remove(0);
+                let callee = self.variable(callee);
+                Exp::Call(Box::new(callee), operands)
+            }
+            Exp::Alloc(inner) => Exp::Alloc(Box::new(self.atom(inner))),
+            Exp::Dereference(inner) => {
+                let inner = self.atom(inner);
+                Exp::Dereference(Box::new(self.variable(inner)))
+            }
+            Exp::Record(fields) => {
+                let values = self.operands(fields.iter().map(|field| &*field.value));
+                let fields = fields
+                    .iter()
+                    .zip(values)
+                    .map(|(field, value)| {
+                        Field {
+                            name: field.name.clone(),
+                            value: Box::new(value),
+                        }
+                        .into_spanned(field.span())
+                    })
+                    .collect();
+                Exp::Record(fields)
+            }
+            Exp::Field(record, field) => {
+                let record = self.atom(record);
+                Exp::Field(Box::new(self.variable(record)), field.clone())
+            }
+            Exp::Array(elements) => Exp::Array(self.operands(elements)),
+            Exp::Index(array, index) => {
+                let (array, index) = self.pair(array, index);
+                Exp::Index(Box::new(self.variable(array)), Box::new(index))
+            }
+        };
+        node.into_spanned(exp.span())
+    }
+
+    /// Normalizes `exp` to an atom, storing its value in a temporary unless
+    /// it is one already.
+    fn atom(&mut self, exp: &AstExp) -> AstExp {
+        let value = self.value(exp);
+        if is_atom(&value) {
+            value
+        } else {
+            self.temp(value)
+        }
+    }
+
+    /// Makes sure `atom` is a variable, which is what pointer operations,
+    /// field accesses and calls take as operands.
+    fn variable(&mut self, atom: AstExp) -> AstExp {
+        match atom.node {
+            Exp::Identifier(_) => atom,
+            _ => self.temp(atom),
+        }
+    }
+
+    fn pair(&mut self, first: &AstExp, second: &AstExp) -> (AstExp, AstExp) {
+        let mut atoms = self.operands([first, second]);
+        let second = atoms.pop().unwrap();
+        (atoms.pop().unwrap(), second)
+    }
+
+    /// Normalizes `exps` to atoms, evaluated from left to right.
+    fn operands<'e>(&mut self, exps: impl IntoIterator<Item = &'e AstExp>) -> Vec<AstExp> {
+        let mut atoms: Vec<AstExp> = Vec::new();
+        for exp in exps {
+            let before = self.stms.len();
+            let atom = self.atom(exp);
+            if self.stms[before..].iter().any(is_call) {
+                // The call could assign to the variables among the earlier
+                // operands through a pointer, so copy them first.
+                let computed = self.stms.split_off(before);
+                for earlier in &mut atoms {
+                    if self.is_mutable(earlier) {
+                        *earlier = self.temp(earlier.clone());
+                    }
+                }
+                self.stms.extend(computed);
+            }
+            atoms.push(atom);
+        }
+        atoms
+    }
+
+    /// Whether `atom` is a variable that a call could assign to. Functions
+    /// and temporaries never change while an expression is evaluated.
+    fn is_mutable(&self, atom: &AstExp) -> bool {
+        match &atom.node {
+            Exp::Identifier(name) => {
+                !self.temps.contains_key(&name.node)
+                    && (self.locals.contains(&name.node) || !self.funs.contains(&name.node))
+            }
+            _ => false,
+        }
+    }
+
+    /// Assigns `value` to a new temporary and returns the temporary.
+    fn temp(&mut self, value: AstExp) -> AstExp {
+        let name = self.fresh.name();
+        let span = value.span();
+        self.temps.insert(name, span);
+        self.locals.insert(name);
+        let name = name.into_spanned(span);
+        self.fun_temps.push(name.clone());
+        self.push(span, Stm::IdentifierAssign(name.clone(), value));
+        Exp::Identifier(name).into_spanned(span)
+    }
+}
+
+fn is_call(stm: &AstStm) -> bool {
+    matches!(&stm.node, Stm::IdentifierAssign(_, value) if matches!(value.node, Exp::Call(..)))
+}
+
+fn is_atom(exp: &Exp) -> bool {
+    match exp {
+        Exp::Int(_) | Exp::Identifier(_) | Exp::Null => true,
+        Exp::Unary(UnOp::Negative, inner) => matches!(inner.node, Exp::Int(_)),
+        _ => false,
+    }
+}
+
+/// Makes up names for temporaries that are not used anywhere in a program.
+pub(crate) struct Fresh {
+    used: HashSet<Symbol>,
+    next: usize,
+}
+
+impl Fresh {
+    pub(crate) fn new(prog: &Prog) -> Fresh {
+        let mut names = Names::default();
+        names.visit_prog(prog);
+        Fresh {
+            used: names.0,
+            next: 0,
+        }
+    }
+
+    pub(crate) fn name(&mut self) -> Symbol {
+        loop {
+            self.next += 1;
+            let name = Symbol::intern(&format!("t{}", self.next));
+            if !self.used.contains(&name) {
+                return name;
+            }
+        }
+    }
+}
+
+/// Collects every name declared or used in a program.
+#[derive(Default)]
+struct Names(HashSet<Symbol>);
+
+impl Visitor for Names {
+    fn visit_prog(&mut self, prog: &Prog) {
+        self.0.extend(prog.globals.iter().map(|global| global.node));
+        visit::walk_prog(self, prog);
+    }
+
+    fn visit_fun(&mut self, fun: &AstFun) {
+        self.0.insert(fun.name.node);
+        self.0
+            .extend(fun.params.iter().chain(&fun.vars).map(|v| v.node));
+        visit::walk_fun(self, fun);
+    }
+
+    fn visit_stm(&mut self, stm: &AstStm) {
+        if let Stm::IdentifierAssign(name, _)
+        | Stm::FieldAssign(name, _, _)
+        | Stm::IndexAssign(name, _, _) = &stm.node
+        {
+            self.0.insert(name.node);
+        }
+        visit::walk_stm(self, stm);
+    }
+
+    fn visit_exp(&mut self, exp: &AstExp) {
+        if let Exp::Identifier(name) | Exp::Pointer(name) = &exp.node {
+            self.0.insert(name.node);
+        }
+        visit::walk_exp(self, exp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_prog, printer::print_prog};
+    use expect_test::{expect, Expect};
+
+    fn check(source: &str, expect: Expect) {
+        let prog = parse_prog(source).unwrap();
+        let normalized = normalize(&prog.node);
+        expect.assert_eq(&print_prog(&normalized.prog));
+    }
+
+    #[test]
+    fn nested_expressions() {
+        check(
+            "f(p, x) { var r; r = *(*p) + x(p, -1).g * (2); *p = alloc {a: *r, b: input}; (*x(p)).a = &r; return r.a.b; }",
+            expect![[r#"
+                f(p, x) {
+                    var r, t1, t2, t3, t4, t5, t6, t7, t8, t9, t10, t11, t12, t13;
+                    t1 = *p;
+                    t2 = *t1;
+                    t3 = x(p, -1);
+                    t4 = t3.g;
+                    t5 = t4 * 2;
+                    r = t2 + t5;
+                    t6 = *r;
+                    t7 = input;
+                    t8 = {a: t6, b: t7};
+                    t9 = alloc t8;
+                    *p = t9;
+                    t10 = x(p);
+                    t11 = &r;
+                    (*t10).a = t11;
+                    t12 = r.a;
+                    t13 = t12.b;
+                    return t13;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn calls_come_after_copies_of_earlier_operands() {
+        check(
+            "f(p) { var x; x = x + set(&x) + x; *p = set(p); output g(x, f) + h(x); return f(p) > 0; }",
+            expect![[r#"
+                f(p) {
+                    var x, t1, t2, t3, t4, t5, t6, t7, t8, t9, t10, t11;
+                    t3 = x;
+                    t1 = &x;
+                    t2 = set(t1);
+                    t4 = t3 + t2;
+                    x = t4 + x;
+                    t6 = p;
+                    t5 = set(p);
+                    *t6 = t5;
+                    t7 = g(x, f);
+                    t8 = h(x);
+                    t9 = t7 + t8;
+                    output t9;
+                    t10 = f(p);
+                    t11 = t10 > 0;
+                    return t11;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn arrays() {
+        check(
+            "f(a, i) { var b; b = [a[i], -1, f(a, i + 1)]; b[a[0]] = [b][0][1]; return (*a)[i]; }",
+            expect![[r#"
+                f(a, i) {
+                    var b, t1, t2, t3, t4, t5, t6, t7, t8, t9;
+                    t1 = a[i];
+                    t2 = i + 1;
+                    t3 = f(a, t2);
+                    b = [t1, -1, t3];
+                    t4 = a[0];
+                    t5 = [b];
+                    t6 = t5[0];
+                    t7 = t6[1];
+                    b[t4] = t7;
+                    t8 = *a;
+                    t9 = t8[i];
+                    return t9;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn while_conditions_are_computed_before_every_iteration() {
+        check(
+            "f(p) { while (*p > 0) { *p = *p - 1; } if ((*p)) { output 1; } return 0; }",
+            expect![[r#"
+                f(p) {
+                    var t1, t2, t3, t4, t5;
+                    t1 = *p;
+                    t2 = t1 > 0;
+                    while (t2) {
+                        t3 = *p;
+                        t4 = t3 - 1;
+                        *p = t4;
+                        t1 = *p;
+                        t2 = t1 > 0;
+                    }
+                    t5 = *p;
+                    if (t5) {
+                        output 1;
+                    }
+                    return 0;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn temporaries_have_fresh_names_and_spans() {
+        let source = "t2() { return 1; } f(t1) { var t4; t4 = t1 + *(t2)() + t4.f; return t4; }";
+        let prog = parse_prog(source).unwrap();
+        let normalized = normalize(&prog.node);
+        expect![[r#"
+            t2() {
+                return 1;
+            }
+
+            f(t1) {
+                var t4, t3, t5, t6, t7, t8;
+                t6 = t1;
+                t3 = t2();
+                t5 = *t3;
+                t7 = t6 + t5;
+                t8 = t4.f;
+                t4 = t7 + t8;
+                return t4;
+            }
+        "#]]
+        .assert_eq(&print_prog(&normalized.prog));
+        let mut temps: Vec<_> = normalized.temps.iter().collect();
+        temps.sort_unstable_by_key(|(temp, _)| (temp.as_str().len(), temp.as_str()));
+        let temps: Vec<String> = temps
+            .into_iter()
+            .map(|(temp, span)| format!("{temp}: {}", &source[span.start()..span.end()]))
+            .collect();
+        expect![[r#"
+            t3: (t2)()
+            t5: *(t2)()
+            t6: t1
+            t7: t1 + *(t2)()
+            t8: t4.f"#]]
+        .assert_eq(&temps.join("\n"));
+    }
+}
@@ -1,22 +1,37 @@
-use crate::{ast::Spanned, lexer::Lexer, parser::ProgParser};
+use crate::{
+    ast::Spanned,
+    lexer::Lexer,
+    parser::ProgParser,
+    sexp::{sexp_exp, sexp_fun, sexp_prog, sexp_stm},
+};
 use expect_test::{expect, Expect};
+use rs_tip_errors::FileId;
 
 fn check(input: &str, expect: Expect) {
     let lexer = Lexer::new(input);
-    let Spanned { node: prog, .. } = ProgParser::new().parse(lexer).unwrap();
-    expect.assert_eq(&format!("{:?}", prog));
+    let Spanned { node: prog, .. } = ProgParser::new().parse(FileId::default(), lexer).unwrap();
+    expect.assert_eq(&sexp_prog(&prog));
 }
 
 #[test]
 fn smoke_test() {
     check(
         "foo (a, b) { var x, y; x = null; return null; }",
-        expect![[
-            r#"Prog { funs: [Spanned { start: 0, end: 47, node: Fun { name: Spanned { start: 0, end: 3, node: "foo" }, params: [Spanned { start: 5, end: 6, node: "a" }, Spanned { start: 8, end: 9, node: "b" }], vars: [Spanned { start: 17, end: 18, node: "x" }, Spanned { start: 20, end: 21, node: "y" }], stms: [Spanned { start: 23, end: 32, node: IdentifierAssign(Spanned { start: 23, end: 24, node: "x" }, Spanned { start: 27, end: 31, node: Null }) }], return_: Spanned { start: 40, end: 44, node: Null } } }] }"#
-        ]],
+        expect![[r#"
+            (prog
+              (fun foo (a b)
+                (vars x y)
+                (assign x null)
+                (return null)))"#]],
     );
 }
 
+#[test]
+fn spans() {
+    let stm = crate::parse_stm("x = y;").node.unwrap();
+    expect![[r#"Spanned { start: 0, end: 6, file: FileId(0), node: IdentifierAssign(Spanned { start: 0, end: 1, file: FileId(0), node: "x" }, Spanned { start: 4, end: 5, file: FileId(0), node: Identifier(Spanned { start: 4, end: 5, file: FileId(0), node: "y" }) }) }"#]].assert_eq(&format!("{:?}", stm));
+}
+
 #[test]
 fn test_complicated() {
     check(
@@ -38,8 +53,223 @@ main() {
     return foo(&n,foo);
 }
 "#,
-        expect![[
-            r#"Prog { funs: [Spanned { start: 1, end: 148, node: Fun { name: Spanned { start: 1, end: 4, node: "foo" }, params: [Spanned { start: 5, end: 6, node: "p" }, Spanned { start: 7, end: 8, node: "x" }], vars: [Spanned { start: 20, end: 21, node: "f" }, Spanned { start: 22, end: 23, node: "q" }], stms: [Spanned { start: 29, end: 132, node: If(Spanned { start: 33, end: 38, node: Binary(Spanned { start: 33, end: 35, node: Dereference(Spanned { start: 34, end: 35, node: Identifier(Spanned { start: 34, end: 35, node: "p" }) }) }, Equal, Spanned { start: 37, end: 38, node: Int(Spanned { start: 37, end: 38, node: 0 }) }) }, [Spanned { start: 42, end: 46, node: IdentifierAssign(Spanned { start: 42, end: 43, node: "f" }, Spanned { start: 44, end: 45, node: Int(Spanned { start: 44, end: 45, node: 1 }) }) }], Some([Spanned { start: 68, end: 80, node: IdentifierAssign(Spanned { start: 68, end: 69, node: "q" }, Spanned { start: 72, end: 79, node: Alloc(Spanned { start: 78, end: 79, node: Int(Spanned { start: 78, end: 79, node: 0 }) }) }) }, Spanned { start: 89, end: 101, node: PointerAssign(Spanned { start: 90, end: 91, node: Identifier(Spanned { start: 90, end: 91, node: "q" }) }, Spanned { start: 94, end: 100, node: Binary(Spanned { start: 94, end: 98, node: Paren(Spanned { start: 95, end: 97, node: Dereference(Spanned { start: 96, end: 97, node: Identifier(Spanned { start: 96, end: 97, node: "p" }) }) }) }, Subtract, Spanned { start: 99, end: 100, node: Int(Spanned { start: 99, end: 100, node: 1 }) }) }) }, Spanned { start: 110, end: 126, node: IdentifierAssign(Spanned { start: 110, end: 111, node: "f" }, Spanned { start: 112, end: 125, node: Binary(Spanned { start: 112, end: 116, node: Paren(Spanned { start: 113, end: 115, node: Dereference(Spanned { start: 114, end: 115, node: Identifier(Spanned { start: 114, end: 115, node: "p" }) }) }) }, Multiply, Spanned { start: 117, end: 125, node: Paren(Spanned { start: 118, end: 124, node: Call(Spanned { start: 118, end: 119, node: Identifier(Spanned { start: 118, end: 119, node: "x" }) }, [Spanned { start: 120, end: 121, node: Identifier(Spanned { start: 120, end: 121, node: "q" }) }, Spanned { start: 122, end: 123, node: Identifier(Spanned { start: 122, end: 123, node: "x" }) }]) }) }) }) }])) }], return_: Spanned { start: 144, end: 145, node: Identifier(Spanned { start: 144, end: 145, node: "f" }) } } }, Spanned { start: 150, end: 210, node: Fun { name: Spanned { start: 150, end: 154, node: "main" }, params: [], vars: [Spanned { start: 167, end: 168, node: "n" }], stms: [Spanned { start: 174, end: 184, node: IdentifierAssign(Spanned { start: 174, end: 175, node: "n" }, Spanned { start: 178, end: 183, node: Input }) }], return_: Spanned { start: 196, end: 207, node: Call(Spanned { start: 196, end: 199, node: Identifier(Spanned { start: 196, end: 199, node: "foo" }) }, [Spanned { start: 200, end: 202, node: Pointer(Spanned { start: 201, end: 202, node: "n" }) }, Spanned { start: 203, end: 206, node: Identifier(Spanned { start: 203, end: 206, node: "foo" }) }]) } } }] }"#
-        ]],
+        expect![[r#"
+            (prog
+              (fun foo (p x)
+                (vars f q)
+                (if (== (deref p) 0)
+                  (block
+                    (assign f 1))
+                  (block
+                    (assign q (alloc 0))
+                    (assign (deref q) (- (paren (deref p)) 1))
+                    (assign f (* (paren (deref p)) (paren (call x q x))))))
+                (return f))
+              (fun main ()
+                (vars n)
+                (assign n input)
+                (return (call foo (ref n) foo))))"#]],
+    );
+}
+
+#[test]
+fn item_ranges() {
+    let source = r#"
+import "lib.tip";
+// A comment between items.
+f(x) { if (x > 0) { x = 0; } return x; }
+
+main() { return f(1); } junk = 1; g( {"#;
+    let items: Vec<&str> = crate::item_ranges(source)
+        .into_iter()
+        .map(|range| &source[range])
+        .collect();
+    expect![[r#"
+        [
+            "import \"lib.tip\";",
+            "f(x) { if (x > 0) { x = 0; } return x; }",
+            "main() { return f(1); }",
+            "junk = 1;",
+            "g( {",
+        ]
+    "#]]
+    .assert_debug_eq(&items);
+}
+
+fn check_parse<T>(parse: crate::Parse<Spanned<T>>, print: fn(&T) -> String, expect: Expect) {
+    let mut out = match parse.node {
+        Some(node) => print(&node),
+        None => String::new(),
+    };
+    for diagnostic in &parse.diagnostics {
+        out += &diagnostic.to_string();
+    }
+    expect.assert_eq(&out);
+}
+
+#[test]
+fn parse_exp() {
+    check_parse(
+        crate::parse_exp("x + *p"),
+        sexp_exp,
+        expect!["(+ x (deref p))"],
+    );
+    check_parse(
+        crate::parse_exp("x +"),
+        sexp_exp,
+        expect![[r#"
+            3:3: unexpected end of file
+        "#]],
+    );
+    check_parse(
+        crate::parse_exp("x;"),
+        sexp_exp,
+        expect![[r#"
+            1:2: unexpected token `;`
+        "#]],
+    );
+}
+
+#[test]
+fn parse_stm() {
+    check_parse(
+        crate::parse_stm("*q = 3;"),
+        sexp_stm,
+        expect!["(assign (deref q) 3)"],
+    );
+    check_parse(
+        crate::parse_stm("return 3;"),
+        sexp_stm,
+        expect!["(return 3)"],
+    );
+    check_parse(
+        crate::parse_stm("var x;"),
+        sexp_stm,
+        expect![[r#"
+            0:3: unexpected token `var`
+        "#]],
+    );
+}
+
+#[test]
+fn parse_for() {
+    check_parse(
+        crate::parse_stm("for (i = 0; i < n; (*p).f = i) { if (i) { break; } continue; }"),
+        sexp_stm,
+        expect![[r#"
+            (for (assign i 0) (< i n) (assign (field (deref p) f) i)
+              (block
+                (if i
+                  (block
+                    (break)))
+                (continue)))"#]],
     );
+    check_parse(
+        crate::parse_stm("for (output i; i; i = i) { }"),
+        sexp_stm,
+        expect![[r#"
+            5:11: unexpected token `output`
+        "#]],
+    );
+}
+
+#[test]
+fn parse_fun() {
+    check_parse(
+        crate::parse_fun("id(x) { return x; }"),
+        sexp_fun,
+        expect![[r#"
+            (fun id (x)
+              (return x))"#]],
+    );
+    check_parse(
+        crate::parse_fun("f() { return 1; } g"),
+        sexp_fun,
+        expect![[r#"
+            18:19: unexpected token `g`
+        "#]],
+    );
+}
+
+#[test]
+fn every_node() {
+    check(
+        r#"
+import "lib.tip";
+var g, h;
+main(a) {
+    var r, p;
+    r = {f: -1, g: input};
+    p = alloc r;
+    (*p).f = r.g;
+    r.f = null;
+    while (a > 0) {
+        output a / 2;
+        a = a - 1;
+    }
+    return main(&r);
+}
+"#,
+        expect![[r#"
+            (prog
+              (import "lib.tip")
+              (globals g h)
+              (fun main (a)
+                (vars r p)
+                (assign r (record (f (- 1)) (g input)))
+                (assign p (alloc r))
+                (assign (field (deref p) f) (field r g))
+                (assign (field r f) null)
+                (while (> a 0)
+                  (block
+                    (output (/ a 2))
+                    (assign a (- a 1))))
+                (return (call main (ref r)))))"#]],
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json() {
+    let exp = crate::parse_exp("-x").node.unwrap();
+    let json = serde_json::to_string(&exp).unwrap();
+    expect![[r#"{"start":0,"end":2,"file":0,"node":{"Unary":["Negative",{"start":1,"end":2,"file":0,"node":{"Identifier":{"start":1,"end":2,"file":0,"node":"x"}}}]}}"#]].assert_eq(&json);
+
+    // Parse into a second file, so the file round-trips too.
+    let mut source_map = rs_tip_errors::SourceMap::new();
+    source_map.add_file("lib.tip", "");
+    let main = source_map.add_file(
+        "main.tip",
+        "import \"lib.tip\"; main(a) { var r; r = {f: &a}; (*r).f = *a; return r.f; }",
+    );
+    let prog = crate::parse_file(source_map.file(main), &mut Vec::new()).unwrap();
+    assert_eq!(prog.file, main);
+    let json = serde_json::to_string(&prog).unwrap();
+    assert_eq!(
+        serde_json::from_str::<crate::ast::AstProg>(&json).unwrap(),
+        prog
+    );
+}
+
+#[test]
+fn stops_at_first_error() {
+    // Neither lexer nor parser errors are recovered from, so only the first
+    // of several errors is reported.
+    let mut sink = Vec::new();
+    assert!(crate::parse("main() { x = $; y = #; return 0; }", &mut sink).is_none());
+    let mut sink = sink.iter().map(|d| format!("{:?} {}", d.code, d));
+    expect![[r#"
+        Some("S0001") 13:14: Parse error: invalid input `$`
+    "#]]
+    .assert_eq(&sink.next().unwrap());
+    assert!(sink.next().is_none());
+
+    let mut sink = Vec::new();
+    assert!(crate::parse("main() { x = ; y = ; return 0; }", &mut sink).is_none());
+    let mut sink = sink.iter().map(|d| format!("{:?} {}", d.code, d));
+    expect![[r#"
+        Some("S0003") 13:14: unexpected token `;`
+    "#]]
+    .assert_eq(&sink.next().unwrap());
+    assert!(sink.next().is_none());
 }
@@ -0,0 +1,1047 @@
+use std::{fmt::Write, ops::Range};
+
+use crate::{ast::*, format::FormatConfig};
+
+/// Binding strength of an expression, mirroring the precedence levels of the
+/// grammar from loosest (`OrExp`) to tightest (`PrimaryExp`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Or,
+    And,
+    Comp,
+    Arith,
+    Factor,
+    Term,
+    Call,
+    Primary,
+}
+
+impl Prec {
+    fn of(exp: &Exp) -> Prec {
+        match exp {
+            Exp::Binary(_, op, _) => Prec::of_binary(op),
+            Exp::Unary(_, _) | Exp::Alloc(_) | Exp::Dereference(_) => Prec::Term,
+            Exp::Call(_, _) | Exp::Field(_, _) | Exp::Index(_, _) => Prec::Call,
+            Exp::Paren(inner) => Prec::of(inner),
+            Exp::Int(_)
+            | Exp::Identifier(_)
+            | Exp::Input
+            | Exp::Pointer(_)
+            | Exp::Null
+            | Exp::Record(_)
+            | Exp::Array(_) => Prec::Primary,
+        }
+    }
+
+    fn of_binary(op: &BinOp) -> Prec {
+        match op {
+            BinOp::Or => Prec::Or,
+            BinOp::And => Prec::And,
+            BinOp::Equal
+            | BinOp::NotEqual
+            | BinOp::Greater
+            | BinOp::GreaterEqual
+            | BinOp::Less
+            | BinOp::LessEqual => Prec::Comp,
+            BinOp::Add | BinOp::Subtract => Prec::Arith,
+            BinOp::Multiply | BinOp::Divide => Prec::Factor,
+        }
+    }
+
+    fn next(self) -> Prec {
+        match self {
+            Prec::Or => Prec::And,
+            Prec::And => Prec::Comp,
+            Prec::Comp => Prec::Arith,
+            Prec::Arith => Prec::Factor,
+            Prec::Factor => Prec::Term,
+            Prec::Term => Prec::Call,
+            Prec::Call | Prec::Primary => Prec::Primary,
+        }
+    }
+}
+
+/// The configuration of the canonical printers, which keep every expression
+/// on one line however long; breaking lines is left to `tipfmt`.
+fn canonical() -> FormatConfig {
+    FormatConfig {
+        max_width: usize::MAX,
+        ..FormatConfig::default()
+    }
+}
+
+/// Prints a program as canonical TIP source.
+pub fn print_prog(prog: &Prog) -> String {
+    let mut p = Printer::new(&canonical(), None);
+    p.prog(prog);
+    p.out
+}
+
+/// Prints a single function as canonical TIP source.
+pub fn print_fun(fun: &Fun) -> String {
+    let mut p = Printer::new(&canonical(), None);
+    p.fun(fun);
+    p.out
+}
+
+/// Prints a single statement, without a trailing newline.
+pub fn print_stm(stm: &Stm) -> String {
+    let mut p = Printer::new(&canonical(), None);
+    p.stm(stm);
+    p.out
+}
+
+/// Prints an expression using the fewest parentheses that preserve its
+/// structure. Explicit `Exp::Paren` nodes are dropped unless required.
+pub fn print_exp(exp: &Exp) -> String {
+    let mut p = Printer::new(&canonical(), None);
+    p.exp(exp, Prec::Or);
+    p.out
+}
+
+/// The parts of the original source that are not in the AST but that the
+/// formatter has to reproduce.
+pub(crate) struct Trivia<'a> {
+    pub(crate) source: &'a str,
+    /// Spans of all comments, in source order.
+    pub(crate) comments: Vec<Range<usize>>,
+    /// Offsets of all `}` tokens, in source order.
+    pub(crate) closing_braces: Vec<usize>,
+}
+
+pub(crate) struct Printer<'a> {
+    pub(crate) out: String,
+    level: usize,
+    indent: String,
+    max_width: usize,
+    /// The column an expression being tried on the rest of the line has to
+    /// end before. Printing gives up once past it.
+    flat_limit: Option<usize>,
+    trivia: Option<Trivia<'a>>,
+    next_comment: usize,
+    last_pos: usize,
+}
+
+impl<'a> Printer<'a> {
+    pub(crate) fn new(config: &FormatConfig, trivia: Option<Trivia<'a>>) -> Printer<'a> {
+        Printer {
+            out: String::new(),
+            level: 0,
+            indent: " ".repeat(config.indent_width),
+            max_width: config.max_width,
+            flat_limit: None,
+            trivia,
+            next_comment: 0,
+            last_pos: 0,
+        }
+    }
+
+    pub(crate) fn prog(&mut self, prog: &Prog) {
+        for import in &prog.imports {
+            self.leading(import.start);
+            self.out.push_str("import \"");
+            self.out.push_str(import.as_str());
+            self.out.push_str("\";");
+            self.line_end(import.end);
+        }
+        if let (Some(first), Some(last)) = (prog.globals.first(), prog.globals.last()) {
+            if !prog.imports.is_empty() {
+                self.out.push('\n');
+            }
+            self.leading(first.start);
+            self.out.push_str("var ");
+            self.names(&prog.globals);
+            self.out.push(';');
+            self.line_end(last.end);
+        }
+        for (i, fun) in prog.funs.iter().enumerate() {
+            if i > 0 || !prog.imports.is_empty() || !prog.globals.is_empty() {
+                self.out.push('\n');
+            }
+            self.fun(fun);
+        }
+        self.leading(usize::MAX);
+    }
+
+    fn fun(&mut self, fun: &Fun) {
+        self.leading(fun.name.start);
+        self.out.push_str(fun.name.as_str());
+        self.out.push('(');
+        self.names(&fun.params);
+        self.out.push_str(") {\n");
+        self.level += 1;
+        if let (Some(first), Some(last)) = (fun.vars.first(), fun.vars.last()) {
+            self.leading(first.start);
+            self.indent();
+            self.out.push_str("var ");
+            self.names(&fun.vars);
+            self.out.push(';');
+            self.line_end(last.end);
+        }
+        self.stms(&fun.stms);
+        if let Some(return_) = &fun.return_ {
+            self.leading(return_.start);
+            self.indent();
+            self.stm(&Stm::Return(return_.clone()));
+            self.line_end(return_.end);
+        }
+        let end = match (&fun.return_, fun.stms.last(), fun.vars.last()) {
+            (Some(return_), _, _) => return_.end,
+            (None, Some(stm), _) => stm.end,
+            (None, None, Some(var)) => var.end,
+            (None, None, None) => fun.name.end,
+        };
+        let close = self.closing_brace(end);
+        if let Some(close) = close {
+            self.leading(close);
+        }
+        self.level -= 1;
+        self.out.push('}');
+        self.line_end(close.map_or(end, |close| close + 1));
+    }
+
+    fn stms(&mut self, stms: &[AstStm]) {
+        for stm in stms {
+            self.leading(stm.start);
+            self.indent();
+            self.stm(stm);
+            self.line_end(stm.end);
+        }
+    }
+
+    fn stm(&mut self, stm: &Stm) {
+        match stm {
+            Stm::IdentifierAssign(..)
+            | Stm::PointerAssign(..)
+            | Stm::FieldAssign(..)
+            | Stm::DereferenceFieldAssign(..)
+            | Stm::IndexAssign(..) => {
+                self.assign(stm);
+                self.out.push(';');
+            }
+            Stm::Output(exp) => {
+                self.out.push_str("output ");
+                self.exp(exp, Prec::Or);
+                self.out.push(';');
+            }
+            Stm::Return(exp) => {
+                self.out.push_str("return ");
+                self.exp(exp, Prec::Or);
+                self.out.push(';');
+            }
+            Stm::If(cond, then, else_) => {
+                self.out.push_str("if (");
+                self.exp(cond, Prec::Or);
+                self.out.push_str(") ");
+                let close = self.block(then, cond.end);
+                if let Some(else_) = else_ {
+                    self.out.push_str(" else ");
+                    self.block(else_, close.map_or(cond.end, |close| close + 1));
+                }
+            }
+            Stm::While(cond, body) => {
+                self.out.push_str("while (");
+                self.exp(cond, Prec::Or);
+                self.out.push_str(") ");
+                self.block(body, cond.end);
+            }
+            Stm::For(init, cond, step, body) => {
+                self.out.push_str("for (");
+                self.assign(init);
+                self.out.push_str("; ");
+                self.exp(cond, Prec::Or);
+                self.out.push_str("; ");
+                self.assign(step);
+                self.out.push_str(") ");
+                self.block(body, step.end);
+            }
+            Stm::Break => self.out.push_str("break;"),
+            Stm::Continue => self.out.push_str("continue;"),
+        }
+    }
+
+    /// Prints an assignment without the `;` that ends it as a statement.
+    fn assign(&mut self, stm: &Stm) {
+        match stm {
+            Stm::IdentifierAssign(name, exp) => {
+                self.out.push_str(name.as_str());
+                self.out.push_str(" = ");
+                self.exp(exp, Prec::Or);
+            }
+            Stm::PointerAssign(target, exp) => {
+                self.out.push('*');
+                self.exp(target, Prec::Or);
+                self.out.push_str(" = ");
+                self.exp(exp, Prec::Or);
+            }
+            Stm::FieldAssign(name, field, exp) => {
+                write!(self.out, "{}.{} = ", name.node, field.node).unwrap();
+                self.exp(exp, Prec::Or);
+            }
+            Stm::DereferenceFieldAssign(target, field, exp) => {
+                self.out.push_str("(*");
+                self.exp(target, Prec::Or);
+                write!(self.out, ").{} = ", field.node).unwrap();
+                self.exp(exp, Prec::Or);
+            }
+            Stm::IndexAssign(name, index, exp) => {
+                self.out.push_str(name.as_str());
+                self.out.push('[');
+                self.exp(index, Prec::Or);
+                self.out.push_str("] = ");
+                self.exp(exp, Prec::Or);
+            }
+            _ => unreachable!("not an assignment: {:?}", stm),
+        }
+    }
+
+    /// Prints a braced block whose opening brace follows `after` in the
+    /// source, returning the offset of its closing brace if known.
+    fn block(&mut self, stms: &[AstStm], after: usize) -> Option<usize> {
+        let close = self.closing_brace(stms.last().map_or(after, |stm| stm.end));
+        let has_comments = match (self.peek_comment(), close) {
+            (Some(comment), Some(close)) => comment.start < close,
+            _ => false,
+        };
+        if stms.is_empty() && !has_comments {
+            self.out.push_str("{}");
+            if let Some(close) = close {
+                self.last_pos = close + 1;
+            }
+            return close;
+        }
+        self.out.push_str("{\n");
+        self.level += 1;
+        self.stms(stms);
+        if let Some(close) = close {
+            self.leading(close);
+            self.last_pos = close + 1;
+        }
+        self.level -= 1;
+        self.indent();
+        self.out.push('}');
+        close
+    }
+
+    fn exp(&mut self, exp: &Exp, min: Prec) {
+        if let Exp::Paren(inner) = exp {
+            return self.exp(inner, min);
+        }
+        if Prec::of(exp) < min {
+            self.out.push('(');
+            self.exp(exp, Prec::Or);
+            self.out.push(')');
+            return;
+        }
+        if let Some(limit) = self.flat_limit {
+            if self.column() >= limit {
+                return;
+            }
+        } else if self.max_width != usize::MAX {
+            let start = self.out.len();
+            self.flat_limit = Some(self.max_width);
+            self.exp(exp, min);
+            self.flat_limit = None;
+            if self.column() < self.max_width {
+                return;
+            }
+            self.out.truncate(start);
+            if self.broken_exp(exp) {
+                return;
+            }
+        }
+        match exp {
+            Exp::Int(int) => write!(self.out, "{}", int.node).unwrap(),
+            Exp::Identifier(name) => self.out.push_str(name.as_str()),
+            Exp::Unary(op, operand) => {
+                self.out.push_str(unop_str(op));
+                self.exp(operand, Prec::Call);
+            }
+            Exp::Binary(lhs, op, rhs) => {
+                let prec = Prec::of_binary(op);
+                self.exp(lhs, prec);
+                self.out.push(' ');
+                self.out.push_str(binop_str(op));
+                self.out.push(' ');
+                self.exp(rhs, prec.next());
+            }
+            Exp::Input => self.out.push_str("input"),
+            Exp::Call(callee, args) => {
+                self.exp(callee, Prec::Call);
+                self.out.push('(');
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.exp(arg, Prec::Or);
+                }
+                self.out.push(')');
+            }
+            Exp::Alloc(exp) => {
+                self.out.push_str("alloc ");
+                self.exp(exp, Prec::Call);
+            }
+            Exp::Pointer(name) => {
+                self.out.push('&');
+                self.out.push_str(name.as_str());
+            }
+            Exp::Dereference(exp) => {
+                self.out.push('*');
+                self.exp(exp, Prec::Call);
+            }
+            Exp::Null => self.out.push_str("null"),
+            Exp::Record(fields) => {
+                self.out.push('{');
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.out.push_str(field.name.as_str());
+                    self.out.push_str(": ");
+                    self.exp(&field.value, Prec::Or);
+                }
+                self.out.push('}');
+            }
+            Exp::Field(exp, field) => {
+                self.exp(exp, Prec::Call);
+                self.out.push('.');
+                self.out.push_str(field.as_str());
+            }
+            Exp::Array(elements) => {
+                self.out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.exp(element, Prec::Or);
+                }
+                self.out.push(']');
+            }
+            Exp::Index(array, index) => {
+                self.exp(array, Prec::Call);
+                self.out.push('[');
+                self.exp(index, Prec::Or);
+                self.out.push(']');
+            }
+            Exp::Paren(_) => unreachable!(),
+        }
+    }
+
+    /// Prints an expression that does not fit on the current line over
+    /// several lines. Returns `false` if `exp` has no place to break.
+    fn broken_exp(&mut self, exp: &Exp) -> bool {
+        match exp {
+            Exp::Binary(lhs, op, rhs) => {
+                let prec = Prec::of_binary(op);
+                self.exp(lhs, prec);
+                self.level += 1;
+                self.newline();
+                self.out.push_str(binop_str(op));
+                self.out.push(' ');
+                self.exp(rhs, prec.next());
+                self.level -= 1;
+            }
+            Exp::Call(callee, args) if !args.is_empty() => {
+                self.exp(callee, Prec::Call);
+                self.out.push('(');
+                self.level += 1;
+                for arg in args {
+                    self.newline();
+                    self.exp(arg, Prec::Or);
+                    self.out.push(',');
+                }
+                self.level -= 1;
+                self.newline();
+                self.out.push(')');
+            }
+            Exp::Record(fields) if !fields.is_empty() => {
+                self.out.push('{');
+                self.level += 1;
+                for field in fields {
+                    self.newline();
+                    self.out.push_str(field.name.as_str());
+                    self.out.push_str(": ");
+                    self.exp(&field.value, Prec::Or);
+                    self.out.push(',');
+                }
+                self.level -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            Exp::Array(elements) if !elements.is_empty() => {
+                self.out.push('[');
+                self.level += 1;
+                for element in elements {
+                    self.newline();
+                    self.exp(element, Prec::Or);
+                    self.out.push(',');
+                }
+                self.level -= 1;
+                self.newline();
+                self.out.push(']');
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn names(&mut self, names: &[AstSymbol]) {
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.out.push_str(name.as_str());
+        }
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.level {
+            self.out.push_str(&self.indent);
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        self.indent();
+    }
+
+    fn column(&self) -> usize {
+        self.out.len() - self.out.rfind('\n').map_or(0, |i| i + 1)
+    }
+
+    fn peek_comment(&self) -> Option<Range<usize>> {
+        let trivia = self.trivia.as_ref()?;
+        trivia.comments.get(self.next_comment).cloned()
+    }
+
+    fn comment_text(&self, comment: &Range<usize>) -> &'a str {
+        let source = self.trivia.as_ref().unwrap().source;
+        source[comment.clone()].trim_end()
+    }
+
+    fn closing_brace(&self, after: usize) -> Option<usize> {
+        let trivia = self.trivia.as_ref()?;
+        trivia
+            .closing_braces
+            .iter()
+            .find(|&&brace| brace >= after)
+            .cloned()
+    }
+
+    /// Emits the comments that start before `pos` on lines of their own.
+    fn leading(&mut self, pos: usize) {
+        while let Some(comment) = self.peek_comment() {
+            if comment.start >= pos {
+                break;
+            }
+            self.blank_line(comment.start);
+            self.indent();
+            self.out.push_str(self.comment_text(&comment));
+            self.out.push('\n');
+            self.last_pos = comment.end;
+            self.next_comment += 1;
+        }
+        if pos != usize::MAX {
+            self.blank_line(pos);
+        }
+    }
+
+    /// Ends the line of a node that finishes at `end`, keeping any comments
+    /// that were inside it or that follow it on the same source line.
+    fn line_end(&mut self, end: usize) {
+        let mut after_line_comment = false;
+        while let Some(comment) = self.peek_comment() {
+            let source = self.trivia.as_ref().unwrap().source;
+            let same_line = comment.start >= end
+                && source[end..comment.start]
+                    .chars()
+                    .all(|c| c == ';' || (c.is_whitespace() && c != '\n'));
+            if comment.start >= end && !same_line {
+                break;
+            }
+            if after_line_comment {
+                self.newline();
+            } else {
+                self.out.push(' ');
+            }
+            let text = self.comment_text(&comment);
+            after_line_comment = text.starts_with("//");
+            self.out.push_str(text);
+            self.last_pos = self.last_pos.max(comment.end);
+            self.next_comment += 1;
+        }
+        self.last_pos = self.last_pos.max(end);
+        self.out.push('\n');
+    }
+
+    /// Keeps a single blank line before `pos` if the source had one.
+    fn blank_line(&mut self, pos: usize) {
+        let Some(trivia) = &self.trivia else {
+            return;
+        };
+        if self.out.is_empty() || self.out.ends_with("\n\n") || self.out.ends_with("{\n") {
+            return;
+        }
+        let between = &trivia.source[self.last_pos.min(pos)..pos];
+        let has_blank = between
+            .split('\n')
+            .skip(1)
+            .take(between.matches('\n').count().saturating_sub(1))
+            .any(|line| line.trim().is_empty());
+        if has_blank {
+            self.out.push('\n');
+        }
+    }
+}
+
+pub(crate) fn binop_str(op: &BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Subtract => "-",
+        BinOp::Multiply => "*",
+        BinOp::Divide => "/",
+        BinOp::Greater => ">",
+        BinOp::Equal => "==",
+        BinOp::NotEqual => "!=",
+        BinOp::Less => "<",
+        BinOp::LessEqual => "<=",
+        BinOp::GreaterEqual => ">=",
+        BinOp::And => "&&",
+        BinOp::Or => "||",
+    }
+}
+
+pub(crate) fn unop_str(op: &UnOp) -> &'static str {
+    match op {
+        UnOp::Negative => "-",
+        UnOp::Not => "!",
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::ProgParser};
+    use expect_test::{expect, Expect};
+    use rs_tip_errors::FileId;
+
+    fn parse(input: &str) -> Prog {
+        ProgParser::new()
+            .parse(FileId::default(), Lexer::new(input))
+            .unwrap()
+            .node
+    }
+
+    fn check(input: &str, expect: Expect) {
+        let prog = parse(input);
+        let printed = print_prog(&prog);
+        expect.assert_eq(&printed);
+
+        let reparsed = parse(&printed);
+        assert!(
+            eq_prog(&prog, &reparsed),
+            "round trip changed the AST:\n{:?}\n{:?}",
+            prog,
+            reparsed
+        );
+        assert_eq!(printed, print_prog(&reparsed));
+    }
+
+    // Structural equality that ignores spans and explicit parentheses.
+
+    pub(crate) fn eq_prog(a: &Prog, b: &Prog) -> bool {
+        eq_names(&a.imports, &b.imports)
+            && eq_names(&a.globals, &b.globals)
+            && eq_all(&a.funs, &b.funs, eq_fun)
+    }
+
+    fn eq_fun(a: &Fun, b: &Fun) -> bool {
+        a.name.node == b.name.node
+            && eq_names(&a.params, &b.params)
+            && eq_names(&a.vars, &b.vars)
+            && eq_stms(&a.stms, &b.stms)
+            && match (&a.return_, &b.return_) {
+                (Some(r1), Some(r2)) => eq_exp(r1, r2),
+                (None, None) => true,
+                _ => false,
+            }
+    }
+
+    fn eq_stms(a: &[AstStm], b: &[AstStm]) -> bool {
+        eq_all(a, b, eq_stm)
+    }
+
+    fn eq_stm(a: &Stm, b: &Stm) -> bool {
+        match (a, b) {
+            (Stm::IdentifierAssign(n1, e1), Stm::IdentifierAssign(n2, e2)) => {
+                n1.node == n2.node && eq_exp(e1, e2)
+            }
+            (Stm::PointerAssign(t1, e1), Stm::PointerAssign(t2, e2)) => {
+                eq_exp(t1, t2) && eq_exp(e1, e2)
+            }
+            (Stm::FieldAssign(n1, f1, e1), Stm::FieldAssign(n2, f2, e2)) => {
+                n1.node == n2.node && f1.node == f2.node && eq_exp(e1, e2)
+            }
+            (Stm::DereferenceFieldAssign(t1, f1, e1), Stm::DereferenceFieldAssign(t2, f2, e2)) => {
+                eq_exp(t1, t2) && f1.node == f2.node && eq_exp(e1, e2)
+            }
+            (Stm::IndexAssign(n1, i1, e1), Stm::IndexAssign(n2, i2, e2)) => {
+                n1.node == n2.node && eq_exp(i1, i2) && eq_exp(e1, e2)
+            }
+            (Stm::Output(e1), Stm::Output(e2)) | (Stm::Return(e1), Stm::Return(e2)) => {
+                eq_exp(e1, e2)
+            }
+            (Stm::If(c1, t1, e1), Stm::If(c2, t2, e2)) => {
+                eq_exp(c1, c2)
+                    && eq_stms(t1, t2)
+                    && match (e1, e2) {
+                        (Some(e1), Some(e2)) => eq_stms(e1, e2),
+                        (None, None) => true,
+                        _ => false,
+                    }
+            }
+            (Stm::While(c1, b1), Stm::While(c2, b2)) => eq_exp(c1, c2) && eq_stms(b1, b2),
+            (Stm::For(i1, c1, s1, b1), Stm::For(i2, c2, s2, b2)) => {
+                eq_stm(i1, i2) && eq_exp(c1, c2) && eq_stm(s1, s2) && eq_stms(b1, b2)
+            }
+            (Stm::Break, Stm::Break) | (Stm::Continue, Stm::Continue) => true,
+            _ => false,
+        }
+    }
+
+    fn eq_exp(a: &Exp, b: &Exp) -> bool {
+        match (a, b) {
+            (Exp::Paren(a), b) => eq_exp(a, b),
+            (a, Exp::Paren(b)) => eq_exp(a, b),
+            (Exp::Int(i1), Exp::Int(i2)) => i1.node == i2.node,
+            (Exp::Identifier(n1), Exp::Identifier(n2)) | (Exp::Pointer(n1), Exp::Pointer(n2)) => {
+                n1.node == n2.node
+            }
+            (Exp::Unary(op1, e1), Exp::Unary(op2, e2)) => op1 == op2 && eq_exp(e1, e2),
+            (Exp::Alloc(e1), Exp::Alloc(e2)) | (Exp::Dereference(e1), Exp::Dereference(e2)) => {
+                eq_exp(e1, e2)
+            }
+            (Exp::Binary(l1, op1, r1), Exp::Binary(l2, op2, r2)) => {
+                op1 == op2 && eq_exp(l1, l2) && eq_exp(r1, r2)
+            }
+            (Exp::Input, Exp::Input) | (Exp::Null, Exp::Null) => true,
+            (Exp::Call(c1, a1), Exp::Call(c2, a2)) => eq_exp(c1, c2) && eq_all(a1, a2, eq_exp),
+            (Exp::Record(f1), Exp::Record(f2)) => eq_all(f1, f2, |a, b| {
+                a.name.node == b.name.node && eq_exp(&a.value, &b.value)
+            }),
+            (Exp::Field(e1, f1), Exp::Field(e2, f2)) => f1.node == f2.node && eq_exp(e1, e2),
+            (Exp::Array(e1), Exp::Array(e2)) => eq_all(e1, e2, eq_exp),
+            (Exp::Index(a1, i1), Exp::Index(a2, i2)) => eq_exp(a1, a2) && eq_exp(i1, i2),
+            _ => false,
+        }
+    }
+
+    fn eq_names(a: &[AstSymbol], b: &[AstSymbol]) -> bool {
+        eq_all(a, b, |a, b| a == b)
+    }
+
+    fn eq_all<T>(a: &[Spanned<T>], b: &[Spanned<T>], eq: impl Fn(&T, &T) -> bool) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| eq(a, b))
+    }
+
+    #[test]
+    fn prints_functions() {
+        check(
+            "foo(a,b){var x,y;x=null;output a+b;return x;} main(){var n;n=input;return foo(n,&n);}",
+            expect![[r#"
+                foo(a, b) {
+                    var x, y;
+                    x = null;
+                    output a + b;
+                    return x;
+                }
+
+                main() {
+                    var n;
+                    n = input;
+                    return foo(n, &n);
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_long_expressions_on_one_line() {
+        let long = "first_operand * second_operand + third_operand * fourth_operand - fifth_operand(sixth_operand, seventh_operand)";
+        check(
+            &format!("main() {{ output {}; return 0; }}", long),
+            expect![[r#"
+                main() {
+                    output first_operand * second_operand + third_operand * fourth_operand - fifth_operand(sixth_operand, seventh_operand);
+                    return 0;
+                }
+            "#]],
+        );
+        let exp = crate::parse_exp(long).node.unwrap();
+        assert_eq!(print_exp(&exp), long);
+    }
+
+    #[test]
+    fn prints_imports() {
+        check(
+            r#"import "list.tip";import "../util/math.tip"; main(){return 0;}"#,
+            expect![[r#"
+                import "list.tip";
+                import "../util/math.tip";
+
+                main() {
+                    return 0;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_globals() {
+        check(
+            r#"import "lib.tip"; var g; var h,k; main(){ g = h; return k; }"#,
+            expect![[r#"
+                import "lib.tip";
+
+                var g, h, k;
+
+                main() {
+                    g = h;
+                    return k;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn drops_redundant_parens() {
+        check(
+            "f(a,b,c){ a = ((a+b)+c); b = (a*(b)); c = ((a)); return ((((a)))); }",
+            expect![[r#"
+                f(a, b, c) {
+                    a = a + b + c;
+                    b = a * b;
+                    c = a;
+                    return a;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn keeps_required_parens() {
+        check(
+            "f(a,b,c){ a = a-(b-c); b = (a+b)*c; c = (a==b)==c; a = a>(b>c); return (a*b)/(c*a); }",
+            expect![[r#"
+                f(a, b, c) {
+                    a = a - (b - c);
+                    b = (a + b) * c;
+                    c = a == b == c;
+                    a = a > (b > c);
+                    return a * b / (c * a);
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_logical_operators() {
+        check(
+            "f(a,b,c){ a = (a||b)||c; b = a||(b||c); c = (a<b)&&(b<=c)||(a!=c); a = a&&(b||c); b = (a>=b)==(b<c); return !(a&&b)+!(!c); }",
+            expect![[r#"
+                f(a, b, c) {
+                    a = a || b || c;
+                    b = a || (b || c);
+                    c = a < b && b <= c || a != c;
+                    a = a && (b || c);
+                    b = a >= b == (b < c);
+                    return !(a && b) + !(!c);
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_arrays() {
+        check(
+            "f(a,i){ a = [1,[],(a)]; a[i+1] = (a[i])[0]; i = -a[i]; i = (*a)[0]; a = [a][0][f(a,i)[i]]; return (a.f)[i]; }",
+            expect![[r#"
+                f(a, i) {
+                    a = [1, [], a];
+                    a[i + 1] = a[i][0];
+                    i = -a[i];
+                    i = (*a)[0];
+                    a = [a][0][f(a, i)[i]];
+                    return a.f[i];
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_loops() {
+        check(
+            "f(n,p){ for(i=0;i<n;i=i+1){ if(i==2){continue;} *p=i; } for (*p = 1; !(*p); p.f = p) { break; } return n; }",
+            expect![[r#"
+                f(n, p) {
+                    for (i = 0; i < n; i = i + 1) {
+                        if (i == 2) {
+                            continue;
+                        }
+                        *p = i;
+                    }
+                    for (*p = 1; !(*p); p.f = p) {
+                        break;
+                    }
+                    return n;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_returns() {
+        check(
+            "f(n){ while(n>0){ if(n==3){return n;} n=n-1; } if (n) { return 1; } else { return 2; } }\ng(){return 0;}\nh(){ }",
+            expect![[r#"
+                f(n) {
+                    while (n > 0) {
+                        if (n == 3) {
+                            return n;
+                        }
+                        n = n - 1;
+                    }
+                    if (n) {
+                        return 1;
+                    } else {
+                        return 2;
+                    }
+                }
+
+                g() {
+                    return 0;
+                }
+
+                h() {
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_unary_operands() {
+        check(
+            "f(p,q){ p = -(-p); q = *(*q); p = alloc (alloc 1); q = -(p+q); p = *(p.f); q = (*p).f; return -f(p)(q); }",
+            expect![[r#"
+                f(p, q) {
+                    p = -(-p);
+                    q = *(*q);
+                    p = alloc (alloc 1);
+                    q = -(p + q);
+                    p = *p.f;
+                    q = (*p).f;
+                    return -f(p)(q);
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_empty_blocks() {
+        check(
+            "main() { var x; var y, z; while (x > 0) { } if (x) { } else { } return 0; } f() { return 1; }",
+            expect![[r#"
+                main() {
+                    var x, y, z;
+                    while (x > 0) {}
+                    if (x) {} else {}
+                    return 0;
+                }
+
+                f() {
+                    return 1;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn prints_statements() {
+        check(
+            r#"
+f(p, r) {
+    var x;
+    *p = *p + 1;
+    r.a = {a: 1, b: {c: null}};
+    (*p).b = r.b.c;
+    if (x > 0) { x = 1; } else { if (x == 0) { x = 2; } }
+    while (x > 0) { x = x - 1; output x; }
+    return (x);
+}
+"#,
+            expect![[r#"
+                f(p, r) {
+                    var x;
+                    *p = *p + 1;
+                    r.a = {a: 1, b: {c: null}};
+                    (*p).b = r.b.c;
+                    if (x > 0) {
+                        x = 1;
+                    } else {
+                        if (x == 0) {
+                            x = 2;
+                        }
+                    }
+                    while (x > 0) {
+                        x = x - 1;
+                        output x;
+                    }
+                    return x;
+                }
+            "#]],
+        );
+    }
+
+    #[test]
+    fn round_trips_complicated() {
+        check(
+            r#"
+foo(p,x) {
+    var f,q;
+    if (*p==0) { f=1; }
+    else {
+        q = alloc 0;
+        *q = (*p)-1;
+        f=(*p)*(x(q,x));
+    }
+    return f;
+}
+
+main() {
+    var n;
+    n = input;
+    return foo(&n,foo);
+}
+"#,
+            expect![[r#"
+                foo(p, x) {
+                    var f, q;
+                    if (*p == 0) {
+                        f = 1;
+                    } else {
+                        q = alloc 0;
+                        *q = *p - 1;
+                        f = *p * x(q, x);
+                    }
+                    return f;
+                }
+
+                main() {
+                    var n;
+                    n = input;
+                    return foo(&n, foo);
+                }
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,563 @@
+//! Name resolution.
+//!
+//! Binds every identifier occurrence in a program to the function, global,
+//! parameter or local variable it refers to, and reports names that are
+//! undeclared, declared twice, or used in a way their definition does not
+//! allow. Also reports `break` and `continue` outside of loops, which have
+//! no loop to refer to, and functions whose body can end without a `return`.
+
+use std::collections::HashMap;
+
+use rs_tip_errors::{Diagnostic, DiagnosticSink, Span};
+use thiserror::Error;
+
+use crate::{
+    ast::{visit, AstExp, AstFun, AstStm, AstSymbol, Exp, Prog, Spanned, Stm, Visitor},
+    symbol::Symbol,
+};
+
+#[derive(Error, Debug)]
+pub enum ResolveError {
+    #[error("undeclared identifier `{0}`")]
+    Undeclared(String),
+    #[error("function `{0}` is defined more than once")]
+    DuplicateFunction(String),
+    #[error("parameter `{0}` is declared more than once")]
+    DuplicateParam(String),
+    #[error("variable `{0}` is declared more than once")]
+    DuplicateLocal(String),
+    #[error("cannot take the address of function `{0}`")]
+    AddressOfFunction(String),
+    #[error("cannot assign to function `{0}`")]
+    AssignToFunction(String),
+    #[error("function `{name}` takes {expected} argument(s) but {found} were supplied")]
+    ArityMismatch {
+        name: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("`{0}` outside of a loop")]
+    OutsideLoop(&'static str),
+    #[error("function `{0}` can reach the end of its body without returning")]
+    MissingReturn(String),
+    #[error("global variable `{0}` has the same name as a function")]
+    GlobalNamesFunction(String),
+}
+
+impl ResolveError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ResolveError::Undeclared(_) => "R0001",
+            ResolveError::DuplicateFunction(_) => "R0002",
+            ResolveError::DuplicateParam(_) => "R0003",
+            ResolveError::DuplicateLocal(_) => "R0004",
+            ResolveError::AddressOfFunction(_) => "R0005",
+            ResolveError::AssignToFunction(_) => "R0006",
+            ResolveError::ArityMismatch { .. } => "R0007",
+            ResolveError::OutsideLoop(_) => "R0008",
+            ResolveError::MissingReturn(_) => "R0009",
+            ResolveError::GlobalNamesFunction(_) => "R0010",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DefId(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DefKind {
+    Function { arity: usize },
+    Global,
+    Param,
+    Local,
+}
+
+#[derive(Debug)]
+pub struct Def {
+    /// The defining occurrence of the name.
+    pub name: AstSymbol,
+    pub kind: DefKind,
+    /// The function a parameter or local belongs to; `None` for functions
+    /// and globals.
+    pub owner: Option<DefId>,
+}
+
+/// The def-use table of a program.
+///
+/// Identifier occurrences are keyed by the span of their `AstSymbol`. The
+/// parser gives every symbol a span of its own, so this identifies each
+/// occurrence of a parsed program; an `Exp::Identifier` shares its span with
+/// its symbol, which is the occurrence it stands for. Programs rebuilt by
+/// `desugar` or `normalize` break that assumption, as the nodes they
+/// introduce reuse the spans of the nodes they came from, so resolve
+/// programs before lowering them.
+#[derive(Debug, Default)]
+pub struct Resolution {
+    defs: Vec<Def>,
+    uses: HashMap<Span, DefId>,
+    /// The spans of the occurrences of every definition, in source order.
+    occurrences: Vec<Vec<Span>>,
+}
+
+impl Resolution {
+    pub fn def(&self, id: DefId) -> &Def {
+        &self.defs[id.0 as usize]
+    }
+
+    pub fn defs(&self) -> impl Iterator<Item = (DefId, &Def)> {
+        self.defs
+            .iter()
+            .enumerate()
+            .map(|(i, def)| (DefId(i as u32), def))
+    }
+
+    /// Returns the definition an identifier occurrence refers to. Defining
+    /// occurrences resolve to their own definition.
+    pub fn resolve(&self, name: &AstSymbol) -> Option<DefId> {
+        self.uses.get(&name.span()).copied()
+    }
+
+    /// Returns the spans of every occurrence bound to `def`, in source order.
+    pub fn uses(&self, def: DefId) -> &[Span] {
+        &self.occurrences[def.0 as usize]
+    }
+
+    fn bind(&mut self, name: &AstSymbol, def: DefId) {
+        self.uses.insert(name.span(), def);
+        self.occurrences[def.0 as usize].push(name.span());
+    }
+}
+
+/// Resolves every name in `prog`, returning the def-use table and reporting
+/// the errors found to `sink`. Unresolved occurrences are left out of the
+/// table.
+pub fn resolve(prog: &Prog, sink: &mut dyn DiagnosticSink) -> Resolution {
+    let functions: Vec<(AstSymbol, usize)> = prog
+        .funs
+        .iter()
+        .map(|fun| (fun.name.clone(), fun.params.len()))
+        .collect();
+    duplicate_functions(&functions, sink);
+    duplicate_globals(&prog.globals, &functions, sink);
+    let mut resolver = Resolver::default();
+    resolver.define_functions(&functions);
+    resolver.define_globals(&prog.globals);
+    resolver.visit_prog(prog);
+    resolver.finish(sink)
+}
+
+/// Resolves the names in `fun` alone, given the name and arity of every
+/// function of its program and its globals. Functions and globals declared
+/// more than once are not reported, as `duplicate_functions` and
+/// `duplicate_globals` do that for the whole program.
+pub fn resolve_fun(
+    fun: &AstFun,
+    functions: &[(AstSymbol, usize)],
+    globals: &[AstSymbol],
+    sink: &mut dyn DiagnosticSink,
+) -> Resolution {
+    let mut resolver = Resolver::default();
+    resolver.define_functions(functions);
+    resolver.define_globals(globals);
+    resolver.visit_fun(fun);
+    resolver.finish(sink)
+}
+
+/// Reports every function, given by name and arity, whose name is taken by
+/// an earlier one.
+pub fn duplicate_functions(functions: &[(AstSymbol, usize)], sink: &mut dyn DiagnosticSink) {
+    let mut seen: HashMap<Symbol, &AstSymbol> = HashMap::new();
+    for (name, _) in functions {
+        match seen.get(&name.node) {
+            Some(first) => sink.emit(
+                error(ResolveError::DuplicateFunction(name.to_string()), name)
+                    .with_secondary(first.span(), "first defined here"),
+            ),
+            None => {
+                seen.insert(name.node, name);
+            }
+        }
+    }
+}
+
+/// Reports every global declared more than once and every global named
+/// like one of the `functions`.
+pub fn duplicate_globals(
+    globals: &[AstSymbol],
+    functions: &[(AstSymbol, usize)],
+    sink: &mut dyn DiagnosticSink,
+) {
+    let functions: HashMap<Symbol, &AstSymbol> = functions
+        .iter()
+        .rev()
+        .map(|(name, _)| (name.node, name))
+        .collect();
+    let mut seen: HashMap<Symbol, &AstSymbol> = HashMap::new();
+    for name in globals {
+        if let Some(first) = seen.get(&name.node) {
+            sink.emit(
+                error(ResolveError::DuplicateLocal(name.to_string()), name)
+                    .with_secondary(first.span(), "first declared here"),
+            );
+            continue;
+        }
+        seen.insert(name.node, name);
+        if let Some(function) = functions.get(&name.node) {
+            sink.emit(
+                error(ResolveError::GlobalNamesFunction(name.to_string()), name)
+                    .with_secondary(function.span(), "function defined here"),
+            );
+        }
+    }
+}
+
+#[derive(Default)]
+struct Resolver {
+    resolution: Resolution,
+    diagnostics: Vec<Diagnostic>,
+    functions: HashMap<Symbol, DefId>,
+    globals: HashMap<Symbol, DefId>,
+    locals: HashMap<Symbol, DefId>,
+    /// The number of loops around the current statement.
+    loops: usize,
+}
+
+impl Resolver {
+    /// Defines the functions of the program, keeping the first of several
+    /// functions with the same name.
+    fn define_functions(&mut self, functions: &[(AstSymbol, usize)]) {
+        for (name, arity) in functions {
+            if !self.functions.contains_key(&name.node) {
+                let id = self.define(name, DefKind::Function { arity: *arity }, None);
+                self.functions.insert(name.node, id);
+            }
+        }
+    }
+
+    /// Defines the globals of the program, keeping the first of several
+    /// globals with the same name.
+    fn define_globals(&mut self, globals: &[AstSymbol]) {
+        for name in globals {
+            if !self.globals.contains_key(&name.node) {
+                let id = self.define(name, DefKind::Global, None);
+                self.globals.insert(name.node, id);
+            }
+        }
+    }
+
+    fn finish(self, sink: &mut dyn DiagnosticSink) -> Resolution {
+        for diagnostic in self.diagnostics {
+            sink.emit(diagnostic);
+        }
+        let mut resolution = self.resolution;
+        for occurrences in &mut resolution.occurrences {
+            occurrences.sort_unstable();
+        }
+        resolution
+    }
+
+    fn define(&mut self, name: &AstSymbol, kind: DefKind, owner: Option<DefId>) -> DefId {
+        let id = DefId(self.resolution.defs.len() as u32);
+        self.resolution.defs.push(Def {
+            name: name.clone(),
+            kind,
+            owner,
+        });
+        self.resolution.occurrences.push(Vec::new());
+        self.resolution.bind(name, id);
+        id
+    }
+
+    fn define_local(&mut self, name: &AstSymbol, kind: DefKind, owner: Option<DefId>) {
+        if self.locals.contains_key(&name.node) {
+            let err = match kind {
+                DefKind::Param => ResolveError::DuplicateParam(name.to_string()),
+                _ => ResolveError::DuplicateLocal(name.to_string()),
+            };
+            self.error(err, name);
+            return;
+        }
+        let id = self.define(name, kind, owner);
+        self.locals.insert(name.node, id);
+    }
+
+    /// Binds an occurrence of `name`, preferring locals over globals and
+    /// globals over functions.
+    fn bind(&mut self, name: &AstSymbol) -> Option<DefId> {
+        let id = self
+            .locals
+            .get(&name.node)
+            .or_else(|| self.globals.get(&name.node))
+            .or_else(|| self.functions.get(&name.node))
+            .copied();
+        match id {
+            Some(id) => self.resolution.bind(name, id),
+            None => self.error(ResolveError::Undeclared(name.to_string()), name),
+        }
+        id
+    }
+
+    fn is_function(&self, id: Option<DefId>) -> Option<usize> {
+        match self.resolution.def(id?).kind {
+            DefKind::Function { arity } => Some(arity),
+            _ => None,
+        }
+    }
+
+    fn error(&mut self, err: ResolveError, name: &AstSymbol) {
+        self.diagnostics.push(error(err, name));
+    }
+}
+
+fn error<T>(err: ResolveError, node: &Spanned<T>) -> Diagnostic {
+    let code = err.code();
+    Diagnostic::new(err, node.span()).with_code(code)
+}
+
+/// Whether running `stms` ends in a `return` on every path. Loops are
+/// assumed to possibly not run at all.
+fn always_returns(stms: &[AstStm]) -> bool {
+    stms.iter().any(|stm| match &stm.node {
+        Stm::Return(_) => true,
+        Stm::If(_, then, Some(else_)) => always_returns(then) && always_returns(else_),
+        _ => false,
+    })
+}
+
+impl Visitor for Resolver {
+    fn visit_fun(&mut self, fun: &AstFun) {
+        let owner = self.resolution.resolve(&fun.name);
+        self.locals.clear();
+        for param in &fun.params {
+            self.define_local(param, DefKind::Param, owner);
+        }
+        for var in &fun.vars {
+            self.define_local(var, DefKind::Local, owner);
+        }
+        visit::walk_fun(self, fun);
+        if fun.return_.is_none() && !always_returns(&fun.stms) {
+            let err = ResolveError::MissingReturn(fun.name.to_string());
+            self.error(err, &fun.name);
+        }
+    }
+
+    fn visit_stm(&mut self, stm: &AstStm) {
+        if let Stm::IdentifierAssign(name, _)
+        | Stm::FieldAssign(name, _, _)
+        | Stm::IndexAssign(name, _, _) = &stm.node
+        {
+            let id = self.bind(name);
+            if self.is_function(id).is_some() {
+                self.error(ResolveError::AssignToFunction(name.to_string()), name);
+            }
+        }
+        match &stm.node {
+            Stm::While(..) | Stm::For(..) => {
+                self.loops += 1;
+                visit::walk_stm(self, stm);
+                self.loops -= 1;
+                return;
+            }
+            Stm::Break | Stm::Continue if self.loops == 0 => {
+                let keyword = match stm.node {
+                    Stm::Break => "break",
+                    _ => "continue",
+                };
+                self.diagnostics
+                    .push(error(ResolveError::OutsideLoop(keyword), stm));
+            }
+            _ => {}
+        }
+        visit::walk_stm(self, stm);
+    }
+
+    fn visit_exp(&mut self, exp: &AstExp) {
+        match &exp.node {
+            Exp::Identifier(name) => {
+                self.bind(name);
+            }
+            Exp::Pointer(name) => {
+                let id = self.bind(name);
+                if self.is_function(id).is_some() {
+                    self.error(ResolveError::AddressOfFunction(name.to_string()), name);
+                }
+            }
+            Exp::Call(callee, args) => {
+                let mut callee = &**callee;
+                while let Exp::Paren(inner) = &callee.node {
+                    callee = inner;
+                }
+                if let Exp::Identifier(name) = &callee.node {
+                    let id = self.bind(name);
+                    if let Some(expected) = self.is_function(id) {
+                        if expected != args.len() {
+                            let err = ResolveError::ArityMismatch {
+                                name: name.to_string(),
+                                expected,
+                                found: args.len(),
+                            };
+                            self.error(err, name);
+                        }
+                    }
+                    for arg in args {
+                        self.visit_exp(arg);
+                    }
+                    return;
+                }
+            }
+            _ => {}
+        }
+        visit::walk_exp(self, exp);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::Lexer, parser::ProgParser};
+    use expect_test::{expect, Expect};
+    use rs_tip_errors::FileId;
+
+    fn check(input: &str, expect: Expect) {
+        let prog = ProgParser::new()
+            .parse(FileId::default(), Lexer::new(input))
+            .unwrap();
+        let mut diagnostics = Vec::new();
+        let resolution = resolve(&prog.node, &mut diagnostics);
+        let mut out = String::new();
+        for (id, def) in resolution.defs() {
+            let uses: Vec<String> = resolution
+                .uses(id)
+                .iter()
+                .map(|span| format!("{}..{}", span.start(), span.end()))
+                .collect();
+            out += &format!(
+                "{:?} {} {:?} owner={:?} uses=[{}]\n",
+                id,
+                def.name.node,
+                def.kind,
+                def.owner,
+                uses.join(", ")
+            );
+        }
+        for diagnostic in diagnostics {
+            out += &diagnostic.to_string();
+        }
+        expect.assert_eq(&out);
+    }
+
+    #[test]
+    fn binds_names() {
+        check(
+            "f(a) { var b; b = &a; return g(b); } g(x) { output f(x); return x; }",
+            expect![[r#"
+                DefId(0) f Function { arity: 1 } owner=None uses=[0..1, 51..52]
+                DefId(1) g Function { arity: 1 } owner=None uses=[29..30, 37..38]
+                DefId(2) a Param owner=Some(DefId(0)) uses=[2..3, 19..20]
+                DefId(3) b Local owner=Some(DefId(0)) uses=[11..12, 14..15, 31..32]
+                DefId(4) x Param owner=Some(DefId(1)) uses=[39..40, 53..54, 64..65]
+            "#]],
+        );
+    }
+
+    #[test]
+    fn locals_shadow_functions() {
+        check(
+            "f(g) { output g(1); return 0; } g() { output 0; return 0; }",
+            expect![[r#"
+                DefId(0) f Function { arity: 1 } owner=None uses=[0..1]
+                DefId(1) g Function { arity: 0 } owner=None uses=[32..33]
+                DefId(2) g Param owner=Some(DefId(0)) uses=[2..3, 14..15]
+            "#]],
+        );
+    }
+
+    #[test]
+    fn binds_globals() {
+        check(
+            "var g, h; f(h) { g = h; return g; } main() { var g; g = 1; h = f(g); return h; }",
+            expect![[r#"
+                DefId(0) f Function { arity: 1 } owner=None uses=[10..11, 63..64]
+                DefId(1) main Function { arity: 0 } owner=None uses=[36..40]
+                DefId(2) g Global owner=None uses=[4..5, 17..18, 31..32]
+                DefId(3) h Global owner=None uses=[7..8, 59..60, 76..77]
+                DefId(4) h Param owner=Some(DefId(0)) uses=[12..13, 21..22]
+                DefId(5) g Local owner=Some(DefId(1)) uses=[49..50, 52..53, 65..66]
+            "#]],
+        );
+    }
+
+    #[test]
+    fn reports_duplicate_globals() {
+        check(
+            "var g, f; var g; f() { return g + f; }",
+            expect![[r#"
+                DefId(0) f Function { arity: 0 } owner=None uses=[17..18]
+                DefId(1) g Global owner=None uses=[4..5, 30..31]
+                DefId(2) f Global owner=None uses=[7..8, 34..35]
+                7:8: global variable `f` has the same name as a function
+                14:15: variable `g` is declared more than once
+            "#]],
+        );
+    }
+
+    #[test]
+    fn reports_errors() {
+        check(
+            r#"
+f(a, a) {
+    var b, a, b;
+    c = a;
+    f = 1;
+    b = &f;
+    output f(1, 2) + (f)();
+    return d.x;
+}
+f() { output 0; return 0; }
+"#,
+            expect![[r#"
+                DefId(0) f Function { arity: 2 } owner=None uses=[1..2, 43..44, 59..60, 73..74, 84..85]
+                DefId(1) a Param owner=Some(DefId(0)) uses=[3..4, 36..37]
+                DefId(2) b Local owner=Some(DefId(0)) uses=[19..20, 54..55]
+                108:109: function `f` is defined more than once
+                6:7: parameter `a` is declared more than once
+                22:23: variable `a` is declared more than once
+                25:26: variable `b` is declared more than once
+                32:33: undeclared identifier `c`
+                43:44: cannot assign to function `f`
+                59:60: cannot take the address of function `f`
+                84:85: function `f` takes 2 argument(s) but 0 were supplied
+                101:102: undeclared identifier `d`
+            "#]],
+        );
+    }
+
+    #[test]
+    fn reports_jumps_outside_of_loops() {
+        check(
+            "f(n) { if (n) { break; } while (n) { for (n = 0; n; n = n) { continue; } break; } continue; return n; }",
+            expect![[r#"
+                DefId(0) f Function { arity: 1 } owner=None uses=[0..1]
+                DefId(1) n Param owner=Some(DefId(0)) uses=[2..3, 11..12, 32..33, 42..43, 49..50, 52..53, 56..57, 99..100]
+                16:22: `break` outside of a loop
+                82:91: `continue` outside of a loop
+            "#]],
+        );
+    }
+
+    #[test]
+    fn reports_missing_returns() {
+        check(
+            "f(n) { if (n) { return 1; } else { while (n) { return 2; } } } g(n) { if (n) { return 1; } else { output n; return 2; } } h() { }",
+            expect![[r#"
+                DefId(0) f Function { arity: 1 } owner=None uses=[0..1]
+                DefId(1) g Function { arity: 1 } owner=None uses=[63..64]
+                DefId(2) h Function { arity: 0 } owner=None uses=[122..123]
+                DefId(3) n Param owner=Some(DefId(0)) uses=[2..3, 11..12, 42..43]
+                DefId(4) n Param owner=Some(DefId(1)) uses=[65..66, 74..75, 105..106]
+                0:1: function `f` can reach the end of its body without returning
+                122:123: function `h` can reach the end of its body without returning
+            "#]],
+        );
+    }
+}
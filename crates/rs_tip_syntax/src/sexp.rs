@@ -0,0 +1,231 @@
+//! S-expression dumps of the AST, for snapshot tests and debugging.
+//!
+//! Expressions are printed on one line, `(+ x (deref p))`, and everything
+//! that contains statements is broken over indented lines with one statement
+//! per line. Spans are left out; the `Debug` output has them.
+//!
+//! ```text
+//! (prog
+//!   (fun main ()
+//!     (vars x)
+//!     (assign x (alloc 1))
+//!     (return (deref x))))
+//! ```
+
+use std::fmt::Write;
+
+use crate::{
+    ast::*,
+    printer::{binop_str, unop_str},
+};
+
+pub fn sexp_prog(prog: &Prog) -> String {
+    let mut p = SexpPrinter::default();
+    p.prog(prog);
+    p.out
+}
+
+pub fn sexp_fun(fun: &Fun) -> String {
+    let mut p = SexpPrinter::default();
+    p.fun(fun);
+    p.out
+}
+
+pub fn sexp_stm(stm: &Stm) -> String {
+    let mut p = SexpPrinter::default();
+    p.stm(stm);
+    p.out
+}
+
+pub fn sexp_exp(exp: &Exp) -> String {
+    let mut p = SexpPrinter::default();
+    p.exp(exp);
+    p.out
+}
+
+#[derive(Default)]
+struct SexpPrinter {
+    out: String,
+    level: usize,
+}
+
+impl SexpPrinter {
+    fn line(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.level {
+            self.out.push_str("  ");
+        }
+    }
+
+    /// Prints `items` on lines of their own, one level deeper.
+    fn nested<T>(&mut self, items: &[T], mut f: impl FnMut(&mut Self, &T)) {
+        self.level += 1;
+        for item in items {
+            self.line();
+            f(self, item);
+        }
+        self.level -= 1;
+    }
+
+    fn prog(&mut self, prog: &Prog) {
+        self.out.push_str("(prog");
+        self.nested(&prog.imports, |p, import| {
+            write!(p.out, "(import {:?})", import.as_str()).unwrap();
+        });
+        if !prog.globals.is_empty() {
+            self.level += 1;
+            self.line();
+            self.out.push_str("(globals ");
+            self.names(&prog.globals);
+            self.out.push(')');
+            self.level -= 1;
+        }
+        self.nested(&prog.funs, |p, fun| p.fun(fun));
+        self.out.push(')');
+    }
+
+    fn fun(&mut self, fun: &Fun) {
+        write!(self.out, "(fun {} (", fun.name.node).unwrap();
+        self.names(&fun.params);
+        self.out.push(')');
+        self.level += 1;
+        if !fun.vars.is_empty() {
+            self.line();
+            self.out.push_str("(vars ");
+            self.names(&fun.vars);
+            self.out.push(')');
+        }
+        for stm in &fun.stms {
+            self.line();
+            self.stm(stm);
+        }
+        if let Some(return_) = &fun.return_ {
+            self.line();
+            self.out.push_str("(return ");
+            self.exp(return_);
+            self.out.push(')');
+        }
+        self.out.push(')');
+        self.level -= 1;
+    }
+
+    fn names(&mut self, names: &[AstSymbol]) {
+        for (i, name) in names.iter().enumerate() {
+            if i > 0 {
+                self.out.push(' ');
+            }
+            self.out.push_str(name.as_str());
+        }
+    }
+
+    fn block(&mut self, stms: &[AstStm]) {
+        self.out.push_str("(block");
+        self.nested(stms, |p, stm| p.stm(stm));
+        self.out.push(')');
+    }
+
+    fn stm(&mut self, stm: &Stm) {
+        match stm {
+            Stm::IdentifierAssign(name, exp) => {
+                write!(self.out, "(assign {} ", name.node).unwrap();
+                self.exp(exp);
+            }
+            Stm::PointerAssign(target, exp) => {
+                self.out.push_str("(assign (deref ");
+                self.exp(target);
+                self.out.push_str(") ");
+                self.exp(exp);
+            }
+            Stm::FieldAssign(name, field, exp) => {
+                write!(self.out, "(assign (field {} {}) ", name.node, field.node).unwrap();
+                self.exp(exp);
+            }
+            Stm::DereferenceFieldAssign(target, field, exp) => {
+                self.out.push_str("(assign (field (deref ");
+                self.exp(target);
+                write!(self.out, ") {}) ", field.node).unwrap();
+                self.exp(exp);
+            }
+            Stm::IndexAssign(name, index, exp) => {
+                write!(self.out, "(assign (index {} ", name.node).unwrap();
+                self.exp(index);
+                self.out.push_str(") ");
+                self.exp(exp);
+            }
+            Stm::Output(exp) => {
+                self.out.push_str("(output ");
+                self.exp(exp);
+            }
+            Stm::Return(exp) => {
+                self.out.push_str("(return ");
+                self.exp(exp);
+            }
+            Stm::If(cond, then, else_) => {
+                self.out.push_str("(if ");
+                self.exp(cond);
+                self.nested(
+                    &[then].into_iter().chain(else_).collect::<Vec<_>>(),
+                    |p, block| p.block(block),
+                );
+            }
+            Stm::While(cond, body) => {
+                self.out.push_str("(while ");
+                self.exp(cond);
+                self.nested(&[body], |p, body| p.block(body));
+            }
+            Stm::For(init, cond, step, body) => {
+                self.out.push_str("(for ");
+                self.stm(init);
+                self.out.push(' ');
+                self.exp(cond);
+                self.out.push(' ');
+                self.stm(step);
+                self.nested(&[body], |p, body| p.block(body));
+            }
+            Stm::Break => self.out.push_str("(break"),
+            Stm::Continue => self.out.push_str("(continue"),
+        }
+        self.out.push(')');
+    }
+
+    fn exp(&mut self, exp: &Exp) {
+        match exp {
+            Exp::Int(n) => write!(self.out, "{}", n.node).unwrap(),
+            Exp::Identifier(name) => self.out.push_str(name.as_str()),
+            Exp::Input => self.out.push_str("input"),
+            Exp::Null => self.out.push_str("null"),
+            Exp::Unary(op, exp) => self.list(unop_str(op), [&**exp]),
+            Exp::Binary(lhs, op, rhs) => self.list(binop_str(op), [&**lhs, &**rhs]),
+            Exp::Call(callee, args) => self.list("call", std::iter::once(&**callee).chain(args)),
+            Exp::Alloc(exp) => self.list("alloc", [&**exp]),
+            Exp::Pointer(name) => write!(self.out, "(ref {})", name.node).unwrap(),
+            Exp::Dereference(exp) => self.list("deref", [&**exp]),
+            Exp::Paren(exp) => self.list("paren", [&**exp]),
+            Exp::Array(elements) => self.list("array", elements),
+            Exp::Index(array, index) => self.list("index", [&**array, &**index]),
+            Exp::Field(exp, field) => {
+                self.out.push_str("(field ");
+                self.exp(exp);
+                write!(self.out, " {})", field.node).unwrap();
+            }
+            Exp::Record(fields) => {
+                self.out.push_str("(record");
+                for field in fields {
+                    write!(self.out, " ({} ", field.name.node).unwrap();
+                    self.exp(&field.value);
+                    self.out.push(')');
+                }
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn list<'e>(&mut self, head: &str, exps: impl IntoIterator<Item = &'e AstExp>) {
+        write!(self.out, "({}", head).unwrap();
+        for exp in exps {
+            self.out.push(' ');
+            self.exp(exp);
+        }
+        self.out.push(')');
+    }
+}
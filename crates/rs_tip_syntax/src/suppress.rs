@@ -0,0 +1,163 @@
+//! `// tip-allow(code)` comments.
+//!
+//! A suppression comment applies to the statement or function that directly
+//! follows it, with nothing but whitespace and other comments in between.
+//! Comments that are not followed by one get an empty scope, so they end up
+//! reported as unused.
+
+use logos::Logos;
+use rs_tip_errors::{SourceFile, Span, Suppression};
+
+use crate::{
+    ast::{visit, AstExp, AstFun, AstStm, Prog, Visitor},
+    lexer::{allow_codes, Token},
+};
+
+/// Returns the suppressions written in `file`, whose functions are part of
+/// `prog`.
+pub fn suppressions(file: &SourceFile, prog: &Prog) -> Vec<Suppression> {
+    let source = file.source();
+    let mut items = Items {
+        file,
+        spans: Vec::new(),
+    };
+    items.visit_prog(prog);
+    // Outer items come first among items starting at the same offset.
+    items
+        .spans
+        .sort_unstable_by_key(|span| (span.start(), std::cmp::Reverse(span.end())));
+
+    let mut suppressions = Vec::new();
+    let mut lexer = Token::lexer(source);
+    while let Some(token) = lexer.next() {
+        if token != Token::Allow {
+            continue;
+        }
+        let comment = lexer.span();
+        let next = items.spans.iter().find(|item| item.start() >= comment.end);
+        let scope = match next {
+            Some(&item) if only_comments(&source[comment.end..item.start()]) => item,
+            _ => Span::in_file(file.id(), comment.end, comment.end),
+        };
+        for code in allow_codes(lexer.slice()) {
+            suppressions.push(Suppression {
+                code: code.to_owned(),
+                span: Span::in_file(file.id(), comment.start, comment.end),
+                scope,
+            });
+        }
+    }
+    suppressions
+}
+
+fn only_comments(source: &str) -> bool {
+    Token::lexer(source).all(|token| matches!(token, Token::Comment | Token::Allow))
+}
+
+/// The spans of all functions and statements in `file`.
+struct Items<'a> {
+    file: &'a SourceFile,
+    spans: Vec<Span>,
+}
+
+impl Items<'_> {
+    fn push(&mut self, span: Span) {
+        if span.file() == self.file.id() {
+            self.spans.push(span);
+        }
+    }
+
+    /// Returns the span of the `return` statement ending `fun`, from the
+    /// keyword to the `;`. The parser keeps only the returned expression.
+    fn return_span(&self, fun: &AstFun, exp: &AstExp) -> Span {
+        let source = self.file.source();
+        let mut lexer = Token::lexer(&source[fun.start..exp.start]);
+        let mut start = exp.start;
+        while let Some(token) = lexer.next() {
+            if token == Token::Return {
+                start = fun.start + lexer.span().start;
+            }
+        }
+        let mut lexer = Token::lexer(&source[exp.end..]);
+        let end = match lexer.find(|token| *token == Token::Semicolon) {
+            Some(_) => exp.end + lexer.span().end,
+            None => exp.end,
+        };
+        Span::in_file(self.file.id(), start, end)
+    }
+}
+
+impl Visitor for Items<'_> {
+    fn visit_fun(&mut self, fun: &AstFun) {
+        self.push(fun.span());
+        if let Some(return_) = &fun.return_ {
+            if fun.file == self.file.id() {
+                let span = self.return_span(fun, return_);
+                self.push(span);
+            }
+        }
+        visit::walk_fun(self, fun);
+    }
+
+    fn visit_stm(&mut self, stm: &AstStm) {
+        self.push(stm.span());
+        visit::walk_stm(self, stm);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use expect_test::{expect, Expect};
+    use rs_tip_errors::SourceMap;
+
+    fn check(source: &str, expect: Expect) {
+        let mut source_map = SourceMap::new();
+        // Parse the program as a second file, so its spans are not in the
+        // default file.
+        source_map.add_file("lib.tip", "f() { return 0; }");
+        let main = source_map.add_file("main.tip", source);
+        let file = source_map.file(main);
+        let prog = crate::parse_file(file, &mut Vec::new()).unwrap();
+        let mut out = String::new();
+        for suppression in suppressions(file, &prog.node) {
+            let scope = suppression.scope;
+            assert_eq!(scope.file(), file.id());
+            out += &format!(
+                "{} {:?}\n",
+                suppression.code,
+                &source[scope.start()..scope.end()]
+            );
+        }
+        expect.assert_eq(&out);
+    }
+
+    #[test]
+    fn attaches_to_next_item() {
+        check(
+            r#"
+// tip-allow(W0002)
+f(x) {
+    var y;
+    // tip-allow(W0003, W0004)
+    // another comment
+    if (x > 0) {
+        // tip-allow(W0005)
+        y = x;
+    }
+    // tip-allow(W0006)
+    return y;
+}
+// tip-allow(W0007)
+"#,
+            expect![[r#"
+                W0002 "f(x) {\n    var y;\n    // tip-allow(W0003, W0004)\n    // another comment\n    if (x > 0) {\n        // tip-allow(W0005)\n        y = x;\n    }\n    // tip-allow(W0006)\n    return y;\n}"
+                W0003 "if (x > 0) {\n        // tip-allow(W0005)\n        y = x;\n    }"
+                W0004 "if (x > 0) {\n        // tip-allow(W0005)\n        y = x;\n    }"
+                W0005 "y = x;"
+                W0006 "return y;"
+                W0007 ""
+            "#]],
+        );
+    }
+}
@@ -0,0 +1,199 @@
+//! Interned identifiers.
+//!
+//! The lexer interns every identifier and string literal it reads, so the
+//! AST holds `Symbol`s: four-byte handles that are copied instead of cloned
+//! and compared without looking at the text.
+//!
+//! The interner is global and never frees its strings, which keeps
+//! `Symbol::as_str` free of lifetimes at the cost of memory: a process holds
+//! every distinct name it has ever lexed until it exits. For a compiler run
+//! that is the names of one program, but a long-lived `rs_tip_db::Database`
+//! also keeps every name an edit passed through, such as each prefix of an
+//! identifier typed character by character. Every thread that reads symbols
+//! additionally keeps its own copy of the table of strings, one pointer per
+//! symbol.
+
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    hash::{BuildHasherDefault, Hasher},
+    sync::Mutex,
+};
+
+/// Symbols are deliberately not ordered: their indices follow the order of
+/// interning, not the text, so use `as_str` to sort them.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Symbol(u32);
+
+impl Symbol {
+    /// Returns the symbol for `string`, interning it on first use.
+    pub fn intern(string: &str) -> Symbol {
+        // Each thread remembers the symbols it has looked up, so that the
+        // lexer only takes the interner's lock for names new to the thread.
+        thread_local! {
+            static CACHE: RefCell<SymbolMap> = RefCell::default();
+        }
+        CACHE.with(|cache| {
+            if let Some(&symbol) = cache.borrow().get(string) {
+                return symbol;
+            }
+            let (string, symbol) = Symbol::intern_global(string);
+            cache.borrow_mut().insert(string, symbol);
+            symbol
+        })
+    }
+
+    /// Interns `string` in the interner shared by all threads, returning the
+    /// interned copy along with its symbol.
+    fn intern_global(string: &str) -> (&'static str, Symbol) {
+        with_interner(|interner| {
+            if let Some((&string, &symbol)) = interner.symbols.get_key_value(string) {
+                return (string, symbol);
+            }
+            let symbol = Symbol(interner.strings.len() as u32);
+            let string: &'static str = Box::leak(string.into());
+            interner.strings.push(string);
+            interner.symbols.insert(string, symbol);
+            (string, symbol)
+        })
+    }
+
+    pub fn as_str(self) -> &'static str {
+        // Each thread keeps a copy of the interned strings, so that only
+        // symbols interned since the last lookup take the lock.
+        thread_local! {
+            static STRINGS: RefCell<Vec<&'static str>> = RefCell::default();
+        }
+        STRINGS.with(|strings| {
+            let index = self.0 as usize;
+            if let Some(&string) = strings.borrow().get(index) {
+                return string;
+            }
+            let mut strings = strings.borrow_mut();
+            with_interner(|interner| {
+                let known = strings.len();
+                strings.extend_from_slice(&interner.strings[known..]);
+            });
+            strings[index]
+        })
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(string: &str) -> Symbol {
+        Symbol::intern(string)
+    }
+}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+/// Prints the text, so that syntax trees print as if they held strings.
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+/// Symbols serialize as their text, so that serialized syntax trees do not
+/// depend on the order names were interned in.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Symbol {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Symbol {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Symbol, D::Error> {
+        let string = <std::borrow::Cow<str>>::deserialize(deserializer)?;
+        Ok(Symbol::intern(&string))
+    }
+}
+
+type SymbolMap = HashMap<&'static str, Symbol, BuildHasherDefault<FxHasher>>;
+
+#[derive(Default)]
+struct Interner {
+    symbols: SymbolMap,
+    strings: Vec<&'static str>,
+}
+
+/// Runs `f` with the interner locked, creating the interner on first use.
+/// `HashMap::with_hasher` is not `const` on the pinned toolchain, so the static
+/// starts out empty.
+fn with_interner<T>(f: impl FnOnce(&mut Interner) -> T) -> T {
+    static INTERNER: Mutex<Option<Interner>> = Mutex::new(None);
+    f(INTERNER
+        .lock()
+        .unwrap()
+        .get_or_insert_with(Interner::default))
+}
+
+/// The hash rustc uses for its interner. Identifiers are short and never
+/// chosen by an attacker, so SipHash's resistance to collisions is not worth
+/// its cost on every identifier the lexer reads.
+#[derive(Default)]
+struct FxHasher(u64);
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let word = u64::from_le_bytes(chunk.try_into().unwrap());
+            self.0 = (self.0.rotate_left(5) ^ word).wrapping_mul(SEED);
+        }
+        for &byte in chunks.remainder() {
+            self.0 = (self.0.rotate_left(5) ^ byte as u64).wrapping_mul(SEED);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning() {
+        let a = Symbol::intern("interning_a");
+        let b = Symbol::intern("interning_b");
+        assert_ne!(a, b);
+        assert_eq!(a, Symbol::intern(&String::from("interning_a")));
+        assert_eq!(a.as_str(), "interning_a");
+        assert_eq!(format!("{} {:?}", b, b), "interning_b \"interning_b\"");
+    }
+
+    #[test]
+    fn symbols_from_other_threads() {
+        let a = Symbol::intern("threads_a");
+        assert_eq!(a.as_str(), "threads_a");
+        // This thread's copy of the strings has to catch up with `b`.
+        let b = std::thread::spawn(|| Symbol::intern("threads_b"))
+            .join()
+            .unwrap();
+        assert_eq!(b.as_str(), "threads_b");
+        assert_eq!(Symbol::intern("threads_b"), b);
+    }
+}
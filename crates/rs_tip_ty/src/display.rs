@@ -0,0 +1,104 @@
+use std::collections::HashMap;
+
+use rs_tip_syntax::symbol::Symbol;
+
+use crate::{Ty, TyCtxt, TyKind, VarId};
+
+const VAR_NAMES: [char; 8] = ['α', 'β', 'γ', 'δ', 'ε', 'ζ', 'η', 'θ'];
+
+/// Formats a closed type. Variables are named in order of appearance, and
+/// record fields are named after `fields` with absent fields left out.
+pub(crate) fn display_ty(tcx: TyCtxt<'_>, fields: &[Symbol], ty: Ty) -> String {
+    TyPrinter::new(tcx, fields).display(ty)
+}
+
+/// Formats several types, naming the variables they share consistently.
+pub(crate) struct TyPrinter<'a, 'tcx> {
+    tcx: TyCtxt<'tcx>,
+    fields: &'a [Symbol],
+    names: HashMap<VarId, String>,
+    out: String,
+}
+
+impl<'a, 'tcx> TyPrinter<'a, 'tcx> {
+    pub(crate) fn new(tcx: TyCtxt<'tcx>, fields: &'a [Symbol]) -> TyPrinter<'a, 'tcx> {
+        TyPrinter {
+            tcx,
+            fields,
+            names: HashMap::new(),
+            out: String::new(),
+        }
+    }
+
+    pub(crate) fn display(&mut self, ty: Ty) -> String {
+        self.ty(ty);
+        std::mem::take(&mut self.out)
+    }
+
+    fn ty(&mut self, ty: Ty) {
+        match &*ty.kind(self.tcx) {
+            TyKind::Int => self.out.push_str("int"),
+            TyKind::Function(params, ret) => {
+                self.out.push('(');
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.ty(*param);
+                }
+                self.out.push_str(") -> ");
+                self.ty(*ret);
+            }
+            TyKind::Pointer(of) => {
+                self.out.push('↑');
+                self.ty(*of);
+            }
+            TyKind::Array(of) => {
+                self.out.push_str("[]");
+                self.ty(*of);
+            }
+            TyKind::Record(field_tys) => {
+                let absent = TyKind::AbsentField.intern(self.tcx);
+                self.out.push('{');
+                let mut first = true;
+                for (name, field_ty) in self.fields.iter().zip(field_tys) {
+                    if *field_ty == absent {
+                        continue;
+                    }
+                    if !first {
+                        self.out.push_str(", ");
+                    }
+                    first = false;
+                    self.out.push_str(name.as_str());
+                    self.out.push_str(": ");
+                    self.ty(*field_ty);
+                }
+                self.out.push('}');
+            }
+            TyKind::AbsentField => self.out.push('◇'),
+            TyKind::Var(var) => self.var(*var),
+            TyKind::FreshVar(var) => {
+                self.out.push('?');
+                self.out.push_str(&var.0.to_string());
+            }
+            TyKind::Recursive(var, body) => {
+                self.out.push('μ');
+                self.var(*var);
+                self.out.push('.');
+                self.ty(*body);
+            }
+        }
+    }
+
+    fn var(&mut self, var: VarId) {
+        let count = self.names.len();
+        let name = self.names.entry(var).or_insert_with(|| {
+            let letter = VAR_NAMES[count % VAR_NAMES.len()];
+            match count / VAR_NAMES.len() {
+                0 => letter.to_string(),
+                n => format!("{}{}", letter, n),
+            }
+        });
+        self.out.push_str(name);
+    }
+}
@@ -1,235 +1,520 @@
 use std::{collections::HashMap, fmt, ops::Deref};
 
-use crate::{hash::RefMap, solvers::unionfind::UnionFindSolver, Ty, TyCtxt, TyKind};
-use rs_tip_syntax::ast::{self, AstString};
+use crate::{
+    display::{display_ty, TyPrinter},
+    hash::hash,
+    solvers::unionfind::UnionFindSolver,
+    Ty, TyCtxt, TyKind, VarId,
+};
+use rs_tip_errors::{Diagnostic, DiagnosticSink, Span};
+use rs_tip_syntax::{
+    ast::{self, visit, AstSymbol, Visitor},
+    symbol::Symbol,
+};
 
 #[derive(Debug)]
 pub enum InferenceError {
     UndefinedVariable(String),
+    Mismatch { expected: String, found: String },
 }
 
 impl fmt::Display for InferenceError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             InferenceError::UndefinedVariable(name) => write!(f, "undefined variable: {}", name),
+            InferenceError::Mismatch { expected, found } => write!(
+                f,
+                "mismatched types: expected `{}`, found `{}`",
+                expected, found
+            ),
         }
     }
 }
 
-pub type Result<T> = std::result::Result<T, InferenceError>;
+impl std::error::Error for InferenceError {}
 
 #[derive(Default)]
 struct Scope {
-    name_to_ty: HashMap<String, Ty>,
+    name_to_ty: HashMap<Symbol, Ty>,
 }
 
 impl Scope {
-    fn add_names(&mut self, names: &[impl Deref<Target = String>], interner: TyCtxt) {
-        for name in names.into_iter() {
-            self.name_to_ty.insert(
-                name.to_string(),
-                TyKind::make_var(interner).intern(interner),
-            );
+    fn add_names(&mut self, names: &[impl Deref<Target = Symbol>], interner: TyCtxt) {
+        for name in names.iter() {
+            self.name_to_ty
+                .insert(**name, TyKind::make_var(interner).intern(interner));
         }
     }
 }
 
 pub struct InferenceResult {
     pub expr_to_ty: HashMap<u64, Ty>,
+    pub fun_to_ty: HashMap<Symbol, Ty>,
+    pub global_to_ty: HashMap<Symbol, Ty>,
+    /// Every field name in the program, in the order record types list them.
+    pub fields: Vec<Symbol>,
 }
 
-pub fn infer(tcx: TyCtxt<'_>, prog: &ast::AstProg) -> InferenceResult {
-    let cx = InferenceContext::new(tcx);
+impl InferenceResult {
+    pub fn exp_ty(&self, exp: &ast::AstExp) -> Option<Ty> {
+        self.expr_to_ty.get(&hash(exp)).copied()
+    }
+
+    /// Formats a type using the TIP notation, e.g. `(int, ↑int) -> int`.
+    pub fn display_ty(&self, tcx: TyCtxt<'_>, ty: Ty) -> String {
+        display_ty(tcx, &self.fields, ty)
+    }
+}
 
-    InferenceResult {
-        expr_to_ty: HashMap::default(),
+/// Infers the types of every expression and function in `prog`. Type errors
+/// are reported to `sink` with the locations of both conflicting types where
+/// known.
+pub fn infer(
+    tcx: TyCtxt<'_>,
+    prog: &ast::AstProg,
+    sink: &mut dyn DiagnosticSink,
+) -> InferenceResult {
+    let mut cx = InferenceContext::new(tcx);
+    cx.infer_prog(prog);
+    let (result, diagnostics) = cx.finish();
+    for diagnostic in diagnostics {
+        sink.emit(diagnostic);
     }
+    result
 }
 
 struct InferenceContext<'a> {
     tcx: TyCtxt<'a>,
-    expr_to_ty: RefMap<ast::AstExp, Ty>,
-    free_var_to_fresh_var: HashMap<Ty, Ty>,
+    expr_to_ty: Vec<(u64, Ty)>,
     solver: UnionFindSolver,
+    /// The scopes around the current node, the first of which holds the
+    /// functions and globals of the program.
     scopes: Vec<Scope>,
+    globals: Vec<Symbol>,
+    fields: Vec<Symbol>,
+    diagnostics: Vec<Diagnostic>,
+    /// The type returned by the function being inferred, which every one of
+    /// its `return`s has.
+    ret_ty: Option<Ty>,
 }
 
 impl<'a> InferenceContext<'a> {
-    fn new(tcx: TyCtxt<'_>) -> InferenceContext {
+    fn new(tcx: TyCtxt<'_>) -> InferenceContext<'_> {
         let global_scope = Scope::default();
         InferenceContext {
             tcx,
-            expr_to_ty: RefMap::new(),
-            free_var_to_fresh_var: HashMap::new(),
+            expr_to_ty: Vec::new(),
             solver: UnionFindSolver::default(),
             scopes: vec![global_scope],
+            globals: Vec::new(),
+            fields: Vec::new(),
+            diagnostics: Vec::new(),
+            ret_ty: None,
         }
     }
 
-    fn infer(self, prog: &ast::AstProg) -> Result<InferenceResult> {
-        self.infer_prog(prog)?;
+    fn finish(mut self) -> (InferenceResult, Vec<Diagnostic>) {
+        let expr_to_ty = std::mem::take(&mut self.expr_to_ty)
+            .into_iter()
+            .map(|(exp, ty)| (exp, self.close(ty)))
+            .collect();
+        let mut fun_to_ty = HashMap::new();
+        let mut global_to_ty = HashMap::new();
+        let names: Vec<(Symbol, Ty)> = self.scopes[0].name_to_ty.drain().collect();
+        for (name, ty) in names {
+            let ty = self.close(ty);
+            if self.globals.contains(&name) {
+                global_to_ty.insert(name, ty);
+            } else {
+                fun_to_ty.insert(name, ty);
+            }
+        }
+        let result = InferenceResult {
+            expr_to_ty,
+            fun_to_ty,
+            global_to_ty,
+            fields: self.fields,
+        };
+        (result, self.diagnostics)
     }
 
-    fn infer_prog(&mut self, prog: &ast::AstProg) -> Result<()> {
+    fn infer_prog(&mut self, prog: &ast::AstProg) {
+        let mut fields = Fields::default();
+        fields.visit_prog(&prog.node);
+        fields.0.sort_unstable_by_key(|field| field.as_str());
+        fields.0.dedup();
+        self.fields = fields.0;
+
+        let names: Vec<&Symbol> = prog.node.funs.iter().map(|fun| &fun.name.node).collect();
+        self.add_names(&names);
+        self.globals = prog.node.globals.iter().map(|global| global.node).collect();
+        self.add_names(&prog.node.globals);
         for fun in &prog.node.funs {
-            self.infer_fun(fun)?;
+            self.infer_fun(fun);
         }
-        Ok(())
     }
 
-    fn infer_fun(&mut self, fun: &ast::AstFun) -> Result<()> {
+    fn infer_fun(&mut self, fun: &ast::AstFun) {
         self.scopes.push(Scope::default());
         self.add_names(&fun.params);
         self.add_names(&fun.vars);
 
-        self.infer_stms(&fun.stms)?;
+        let params = fun
+            .params
+            .iter()
+            .map(|param| self.lookup(param))
+            .collect::<Vec<Ty>>();
+        let ret_ty = self.fresh_var();
+        let fun_ty = TyKind::Function(params, ret_ty).intern(self.tcx);
+        let name_ty = self.lookup(&fun.name);
+        self.unify(name_ty, fun_ty, fun.name.span());
+
+        self.ret_ty = Some(ret_ty);
+        self.infer_stms(&fun.stms);
+        if let Some(return_) = &fun.return_ {
+            self.infer_return(return_);
+        }
+        self.ret_ty = None;
 
         self.scopes.pop();
-        Ok(())
     }
 
-    fn infer_stms(&mut self, stms: &Vec<ast::AstStm>) -> Result<()> {
+    fn infer_return(&mut self, exp: &ast::AstExp) {
+        let exp_ty = self.infer_exp(exp);
+        let ret_ty = self.ret_ty.expect("`return` outside of a function");
+        self.unify(ret_ty, exp_ty, exp.span());
+    }
+
+    fn infer_stms(&mut self, stms: &Vec<ast::AstStm>) {
         for stm in stms {
-            self.infer_stm(stm)?;
+            self.infer_stm(stm);
         }
-        Ok(())
     }
 
-    fn infer_stm(&mut self, stm: &ast::AstStm) -> Result<()> {
+    fn infer_stm(&mut self, stm: &ast::AstStm) {
         let node = &stm.node;
         match node {
             ast::Stm::IdentifierAssign(name, exp) => {
-                let name_ty = self.lookup(name)?;
-                let exp_ty = self.infer_exp(exp)?;
-                // self.solver.unify(self.tcx, name_ty, exp_ty).unwrap();
-                self.unify(name_ty, exp_ty);
+                let name_ty = self.lookup(name);
+                let exp_ty = self.infer_exp(exp);
+                self.unify(name_ty, exp_ty, exp.span());
             }
             ast::Stm::PointerAssign(target, exp) => {
-                let target_ty = self.infer_exp(target)?;
-                let pointer_ty = TyKind::Pointer(self.infer_exp(exp)?).intern(self.tcx);
-                self.solver.unify(self.tcx, target_ty, pointer_ty).unwrap()
+                let target_ty = self.infer_exp(target);
+                let pointer_ty = TyKind::Pointer(self.infer_exp(exp)).intern(self.tcx);
+                self.unify(pointer_ty, target_ty, target.span());
+            }
+            ast::Stm::FieldAssign(name, field, exp) => {
+                let name_ty = self.lookup(name);
+                let exp_ty = self.infer_exp(exp);
+                let record_ty = self.record_with(field, exp_ty);
+                self.unify(record_ty, name_ty, name.span());
+            }
+            ast::Stm::DereferenceFieldAssign(target, field, exp) => {
+                let target_ty = self.infer_exp(target);
+                let exp_ty = self.infer_exp(exp);
+                let record_ty = self.record_with(field, exp_ty);
+                let pointer_ty = TyKind::Pointer(record_ty).intern(self.tcx);
+                self.unify(pointer_ty, target_ty, target.span());
+            }
+            ast::Stm::IndexAssign(name, index, exp) => {
+                let name_ty = self.lookup(name);
+                let index_ty = self.infer_exp(index);
+                self.unify_int(index_ty, index.span());
+                let array_ty = TyKind::Array(self.infer_exp(exp)).intern(self.tcx);
+                self.unify(array_ty, name_ty, name.span());
             }
-            ast::Stm::FieldAssign(_, _, _) => todo!(),
-            ast::Stm::DereferenceFieldAssign(_, _, _) => todo!(),
             ast::Stm::Output(exp) => {
-                let exp_ty = self.infer_exp(exp)?;
-                self.solver
-                    .unify(self.tcx, exp_ty, self.tcx.common().int())
-                    .unwrap();
+                let exp_ty = self.infer_exp(exp);
+                self.unify_int(exp_ty, exp.span());
             }
             ast::Stm::If(cond, then, else_) => {
-                let exp_ty = self.infer_exp(cond)?;
-                self.solver
-                    .unify(self.tcx, exp_ty, self.tcx.common().int())
-                    .unwrap();
-                self.infer_stms(then)?;
+                let exp_ty = self.infer_exp(cond);
+                self.unify_int(exp_ty, cond.span());
+                self.infer_stms(then);
                 if let Some(else_) = else_ {
-                    self.infer_stms(else_)?;
+                    self.infer_stms(else_);
                 }
             }
             ast::Stm::While(cond, body) => {
-                let exp_ty = self.infer_exp(cond)?;
-                self.solver
-                    .unify(self.tcx, exp_ty, self.tcx.common().int())
-                    .unwrap();
-                self.infer_stms(body)?;
+                let exp_ty = self.infer_exp(cond);
+                self.unify_int(exp_ty, cond.span());
+                self.infer_stms(body);
+            }
+            ast::Stm::For(init, cond, step, body) => {
+                self.infer_stm(init);
+                let exp_ty = self.infer_exp(cond);
+                self.unify_int(exp_ty, cond.span());
+                self.infer_stm(step);
+                self.infer_stms(body);
             }
+            ast::Stm::Break | ast::Stm::Continue => {}
+            ast::Stm::Return(exp) => self.infer_return(exp),
         };
-        Ok(())
     }
 
-    fn infer_exp(&mut self, exp: &ast::AstExp) -> Result<Ty> {
+    fn infer_exp(&mut self, exp: &ast::AstExp) -> Ty {
         let node = &exp.node;
 
         if let ast::Exp::Identifier(name) = node {
-            return self.lookup(name);
+            let ty = self.lookup(name);
+            return self.record_exp_ty(exp, ty);
         }
 
         // Assign the expression a new type variable.
-        let exp_ty = TyKind::make_var(self.tcx).intern(self.tcx);
+        let exp_ty = self.fresh_var();
         self.record_exp_ty(exp, exp_ty);
+        let exp_span = exp.span();
 
         match node {
-            ast::Exp::Int(_) => {
-                self.unify_int(exp_ty);
+            ast::Exp::Int(_) | ast::Exp::Input => {
+                self.unify_int(exp_ty, exp_span);
             }
             ast::Exp::Unary(_, operand) => {
-                let operand_ty = self.infer_exp(operand)?;
-                self.unify_int(operand_ty);
-                self.unify_int(exp_ty);
+                let operand_ty = self.infer_exp(operand);
+                self.unify_int(operand_ty, operand.span());
+                self.unify_int(exp_ty, exp_span);
             }
-            ast::Exp::Binary(lhs, _, rhs) => {
-                let lhs_ty = self.infer_exp(lhs)?;
-                self.unify_int(lhs_ty);
-                let rhs_ty = self.infer_exp(rhs)?;
-                self.unify_int(rhs_ty);
-                self.unify_int(exp_ty);
+            ast::Exp::Binary(lhs, ast::BinOp::Equal | ast::BinOp::NotEqual, rhs) => {
+                let lhs_ty = self.infer_exp(lhs);
+                let rhs_ty = self.infer_exp(rhs);
+                self.unify(lhs_ty, rhs_ty, rhs.span());
+                self.unify_int(exp_ty, exp_span);
             }
-            ast::Exp::Input => {
-                self.unify_int(exp_ty);
+            ast::Exp::Binary(lhs, _, rhs) => {
+                let lhs_ty = self.infer_exp(lhs);
+                self.unify_int(lhs_ty, lhs.span());
+                let rhs_ty = self.infer_exp(rhs);
+                self.unify_int(rhs_ty, rhs.span());
+                self.unify_int(exp_ty, exp_span);
             }
             ast::Exp::Call(callee, args) => {
-                let args_tys: Vec<Ty> =
-                    Result::from_iter(args.iter().map(|arg| self.infer_exp(arg)))?;
-                let return_ty = TyKind::make_var(self.tcx).intern(self.tcx);
-                let callee_ty = self.infer_exp(callee)?;
-                let fun_ty = TyKind::Function(args_tys, return_ty).intern(self.tcx);
-                self.unify(callee_ty, fun_ty);
-                self.unify(exp_ty, return_ty);
+                let args_tys = args
+                    .iter()
+                    .map(|arg| self.infer_exp(arg))
+                    .collect::<Vec<Ty>>();
+                let callee_ty = self.infer_exp(callee);
+                let fun_ty = TyKind::Function(args_tys, exp_ty).intern(self.tcx);
+                self.unify(callee_ty, fun_ty, exp_span);
             }
             ast::Exp::Alloc(alloc) => {
-                let alloc_ty = self.infer_exp(alloc)?;
-                self.unify(exp_ty, TyKind::Pointer(alloc_ty).intern(self.tcx));
+                let alloc_ty = self.infer_exp(alloc);
+                let pointer_ty = TyKind::Pointer(alloc_ty).intern(self.tcx);
+                self.unify(exp_ty, pointer_ty, exp_span);
             }
             ast::Exp::Pointer(name) => {
-                self.unify(exp_ty, TyKind::Pointer(self.lookup(name)?).intern(self.tcx));
+                let pointer_ty = TyKind::Pointer(self.lookup(name)).intern(self.tcx);
+                self.unify(exp_ty, pointer_ty, exp_span);
             }
             ast::Exp::Dereference(deref) => {
-                let inner_ty = TyKind::make_var(self.tcx).intern(self.tcx);
-                let pointer_ty = TyKind::Pointer(inner_ty).intern(self.tcx);
-                let deref_ty = self.infer_exp(deref)?;
-                self.unify(pointer_ty, deref_ty);
-                self.unify(exp_ty, inner_ty);
+                let pointer_ty = TyKind::Pointer(exp_ty).intern(self.tcx);
+                let deref_ty = self.infer_exp(deref);
+                self.unify(pointer_ty, deref_ty, deref.span());
             }
             ast::Exp::Null => {
-                self.unify(exp_ty, TyKind::make_var(self.tcx).intern(self.tcx));
+                let pointer_ty = TyKind::Pointer(self.fresh_var()).intern(self.tcx);
+                self.unify(exp_ty, pointer_ty, exp_span);
             }
             ast::Exp::Paren(inner) => {
-                let inner_ty = self.infer_exp(inner)?;
-                self.unify(exp_ty, inner_ty);
+                let inner_ty = self.infer_exp(inner);
+                self.unify(exp_ty, inner_ty, exp_span);
+            }
+            ast::Exp::Record(fields) => {
+                let mut field_tys = HashMap::new();
+                for field in fields {
+                    field_tys.insert(field.name.node, self.infer_exp(&field.value));
+                }
+                let absent = TyKind::AbsentField.intern(self.tcx);
+                let record_ty = TyKind::Record(
+                    self.fields
+                        .iter()
+                        .map(|name| field_tys.get(name).copied().unwrap_or(absent))
+                        .collect(),
+                )
+                .intern(self.tcx);
+                self.unify(exp_ty, record_ty, exp_span);
+            }
+            ast::Exp::Field(record, field) => {
+                let record_ty = self.infer_exp(record);
+                let expected = self.record_with(field, exp_ty);
+                self.unify(expected, record_ty, record.span());
+            }
+            ast::Exp::Array(elements) => {
+                // Every element has the type of the first.
+                let element_ty = self.fresh_var();
+                for element in elements {
+                    let ty = self.infer_exp(element);
+                    self.unify(element_ty, ty, element.span());
+                }
+                let array_ty = TyKind::Array(element_ty).intern(self.tcx);
+                self.unify(exp_ty, array_ty, exp_span);
+            }
+            ast::Exp::Index(array, index) => {
+                let array_ty = self.infer_exp(array);
+                let expected = TyKind::Array(exp_ty).intern(self.tcx);
+                self.unify(expected, array_ty, array.span());
+                let index_ty = self.infer_exp(index);
+                self.unify_int(index_ty, index.span());
             }
-            ast::Exp::Record(_) => todo!(),
-            ast::Exp::Field(_, _) => todo!(),
 
             // Handled above.
             ast::Exp::Identifier(_) => unreachable!(),
         };
-        Ok(exp_ty)
+        exp_ty
     }
 
     fn record_exp_ty(&mut self, exp: &ast::AstExp, ty: Ty) -> Ty {
-        self.expr_to_ty.insert(exp, ty);
+        self.expr_to_ty.push((hash(exp), ty));
         ty
     }
 
-    fn add_names(&mut self, names: &[AstString]) {
+    fn add_names(&mut self, names: &[impl Deref<Target = Symbol>]) {
         let scope = self.scopes.last_mut().unwrap();
         scope.add_names(names, self.tcx);
     }
 
-    fn lookup(&self, name: &str) -> Result<Ty> {
-        self.scopes
+    /// Returns the type of `name`. An undefined name is reported and bound to
+    /// a fresh type variable in the current scope, so inference carries on
+    /// and later uses of the name are not reported again.
+    fn lookup(&mut self, name: &AstSymbol) -> Ty {
+        let found = self
+            .scopes
             .iter()
             .rev()
-            .find_map(|scope| scope.name_to_ty.get(name).cloned())
-            .ok_or_else(|| InferenceError::UndefinedVariable(name.to_string()))
+            .find_map(|scope| scope.name_to_ty.get(&name.node).cloned());
+        if let Some(ty) = found {
+            return ty;
+        }
+        let err = InferenceError::UndefinedVariable(name.to_string());
+        self.diagnostics
+            .push(Diagnostic::new(err, name.span()).with_code("T0002"));
+        let ty = self.fresh_var();
+        let scope = self.scopes.last_mut().unwrap();
+        scope.name_to_ty.insert(name.node, ty);
+        ty
     }
 
-    fn unify(&mut self, t1: Ty, t2: Ty) {
-        self.solver.unify(self.tcx, t1, t2).unwrap();
+    fn fresh_var(&self) -> Ty {
+        TyKind::make_var(self.tcx).intern(self.tcx)
+    }
+
+    /// Returns a record type whose `field` has type `ty` and whose other
+    /// fields are unconstrained.
+    fn record_with(&self, field: &AstSymbol, ty: Ty) -> Ty {
+        let field_tys = self
+            .fields
+            .iter()
+            .map(|name| {
+                if *name == field.node {
+                    ty
+                } else {
+                    self.fresh_var()
+                }
+            })
+            .collect();
+        TyKind::Record(field_tys).intern(self.tcx)
+    }
+
+    /// Unifies the type that the code at `span` is `expected` to have with
+    /// the type it was `found` to have.
+    fn unify(&mut self, expected: Ty, found: Ty, span: Span) {
+        if self.solver.unify(self.tcx, expected, found, span).is_ok() {
+            return;
+        }
+        let mut printer = TyPrinter::new(self.tcx, &self.fields);
+        let expected_str = printer.display(self.close(expected));
+        let found_str = printer.display(self.close(found));
+        let mut diagnostic = Diagnostic::new(
+            InferenceError::Mismatch {
+                expected: expected_str.clone(),
+                found: found_str.clone(),
+            },
+            span,
+        )
+        .with_code("T0001")
+        .with_label(format!("this has type `{}`", found_str));
+        for (ty, ty_str) in [(expected, expected_str), (found, found_str)] {
+            if let Some(bound_at) = self.solver.bound_at(ty) {
+                if bound_at != span {
+                    diagnostic = diagnostic
+                        .with_secondary(bound_at, format!("inferred to be `{}` here", ty_str));
+                }
+            }
+        }
+        self.diagnostics.push(diagnostic);
+    }
+
+    fn unify_int(&mut self, ty: Ty, span: Span) {
+        self.unify(self.tcx.common().int(), ty, span);
+    }
+
+    /// Replaces every type variable in `ty` by the solution found for it,
+    /// introducing recursive types for cyclic solutions.
+    fn close(&self, ty: Ty) -> Ty {
+        self.close_with(ty, &mut Vec::new())
+    }
+
+    fn close_with(&self, ty: Ty, visiting: &mut Vec<(Ty, Option<VarId>)>) -> Ty {
+        let tcx = self.tcx;
+        let rep = self.solver.find(ty);
+        if let Some((_, var)) = visiting.iter_mut().find(|(visited, _)| *visited == rep) {
+            let var = *var.get_or_insert_with(|| tcx.inner.alloc_var_id());
+            return TyKind::Var(var).intern(tcx);
+        }
+        let kind = rep.kind(tcx);
+        visiting.push((rep, None));
+        let closed = match &*kind {
+            TyKind::Function(params, ret) => TyKind::Function(
+                params
+                    .iter()
+                    .map(|param| self.close_with(*param, visiting))
+                    .collect(),
+                self.close_with(*ret, visiting),
+            ),
+            TyKind::Pointer(of) => TyKind::Pointer(self.close_with(*of, visiting)),
+            TyKind::Array(of) => TyKind::Array(self.close_with(*of, visiting)),
+            TyKind::Record(fields) => TyKind::Record(
+                fields
+                    .iter()
+                    .map(|field| self.close_with(*field, visiting))
+                    .collect(),
+            ),
+            _ => {
+                visiting.pop();
+                return rep;
+            }
+        };
+        let closed = closed.intern(tcx);
+        match visiting.pop() {
+            Some((_, Some(var))) => TyKind::Recursive(var, closed).intern(tcx),
+            _ => closed,
+        }
+    }
+}
+
+/// Collects the names of all fields mentioned in a program.
+#[derive(Default)]
+struct Fields(Vec<Symbol>);
+
+impl Visitor for Fields {
+    fn visit_stm(&mut self, stm: &ast::AstStm) {
+        if let ast::Stm::FieldAssign(_, field, _) | ast::Stm::DereferenceFieldAssign(_, field, _) =
+            &stm.node
+        {
+            self.0.push(field.node);
+        }
+        visit::walk_stm(self, stm);
+    }
+
+    fn visit_exp(&mut self, exp: &ast::AstExp) {
+        if let ast::Exp::Field(_, field) = &exp.node {
+            self.0.push(field.node);
+        }
+        visit::walk_exp(self, exp);
     }
 
-    fn unify_int(&mut self, ty: Ty) {
-        self.unify(ty, self.tcx.common().int());
+    fn visit_field(&mut self, field: &ast::AstField) {
+        self.0.push(field.name.node);
+        visit::walk_field(self, field);
     }
 }
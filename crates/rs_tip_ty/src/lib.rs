@@ -7,6 +7,7 @@ use std::{
 
 use hash::hash;
 
+mod display;
 mod hash;
 pub mod infer;
 pub mod solvers;
@@ -30,10 +31,9 @@ pub struct Interners {
 impl Interners {
     fn intern_ty_kind(&mut self, kind: TyKind) -> u32 {
         self.counter += 1;
-        self.ty_kinds_to_interned
-            .insert(hash(&TyKind::Int), self.counter);
+        self.ty_kinds_to_interned.insert(hash(&kind), self.counter);
         self.interned_to_ty_kinds
-            .insert(self.counter, Rc::new(TyKind::Int));
+            .insert(self.counter, Rc::new(kind));
         self.counter
     }
 }
@@ -44,6 +44,10 @@ pub struct TyCtxt<'tcx> {
 }
 
 impl<'tcx> TyCtxt<'tcx> {
+    pub fn new(inner: &'tcx TyCtxtInner) -> TyCtxt<'tcx> {
+        TyCtxt { inner }
+    }
+
     pub fn intern_ty_kind(self, kind: TyKind) -> u32 {
         self.inner.intern_ty_kind(kind)
     }
@@ -62,19 +66,17 @@ impl<'tcx> TyCtxt<'tcx> {
 }
 
 pub struct TyCtxtInner {
-    counter: u32,
     interners: RefCell<Interners>,
     common: CommonTypes,
     next_var_id: Cell<u32>,
 }
 
 impl TyCtxtInner {
-    fn new() -> TyCtxtInner {
+    pub fn new() -> TyCtxtInner {
         let mut interners = Interners::default();
         let interned = interners.intern_ty_kind(TyKind::Int);
 
         TyCtxtInner {
-            counter: 0,
             interners: RefCell::new(interners),
             common: CommonTypes {
                 int: Ty { interned },
@@ -125,6 +127,12 @@ impl TyCtxtInner {
     }
 }
 
+impl Default for TyCtxtInner {
+    fn default() -> TyCtxtInner {
+        TyCtxtInner::new()
+    }
+}
+
 pub struct CommonTypes {
     int: Ty,
 }
@@ -148,6 +156,8 @@ pub enum TyKind {
     Function(Vec<Ty>, Ty),
     Pointer(Ty),
     Record(Vec<Ty>),
+    /// An array with elements of the given type.
+    Array(Ty),
     AbsentField,
     Var(VarId),
     FreshVar(FreshVarId),
@@ -162,6 +172,7 @@ impl TyKind {
             | TyKind::Function(_, _)
             | TyKind::Pointer(_)
             | TyKind::Record(_)
+            | TyKind::Array(_)
             | TyKind::AbsentField => TermKind::Cons,
             TyKind::Recursive(_, _) => TermKind::Mu,
         }
@@ -183,6 +194,9 @@ impl TyKind {
             TyKind::Pointer(of) => {
                 Ty::intern(interner, TyKind::Pointer(of.substitute(interner, from, to)))
             }
+            TyKind::Array(of) => {
+                Ty::intern(interner, TyKind::Array(of.substitute(interner, from, to)))
+            }
             TyKind::Record(args) => Ty::intern(
                 interner,
                 TyKind::Record(
@@ -194,9 +208,9 @@ impl TyKind {
             TyKind::AbsentField => Ty::for_kind(interner, self),
             TyKind::Var(v) => {
                 if *v == from {
-                    Ty::for_kind(interner, self)
-                } else {
                     to
+                } else {
+                    Ty::for_kind(interner, self)
                 }
             }
             TyKind::FreshVar(_) => todo!(),
@@ -216,11 +230,27 @@ impl TyKind {
     fn arity(&self) -> usize {
         match self {
             TyKind::Function(params, _) => params.len() + 1,
-            TyKind::Pointer(_) => 1,
+            TyKind::Pointer(_) | TyKind::Array(_) => 1,
+            TyKind::Record(fields) => fields.len(),
             _ => 0,
         }
     }
 
+    /// Returns the type arguments of a constructor, in a fixed order so
+    /// that those of two matching constructors can be unified pairwise.
+    fn children(&self) -> Vec<Ty> {
+        match self {
+            TyKind::Function(params, ret) => params
+                .iter()
+                .copied()
+                .chain(std::iter::once(*ret))
+                .collect(),
+            TyKind::Pointer(of) | TyKind::Array(of) => vec![*of],
+            TyKind::Record(fields) => fields.clone(),
+            _ => Vec::new(),
+        }
+    }
+
     fn matches(&self, other: &TyKind) -> bool {
         if self.term_kind() != TermKind::Cons || other.term_kind() != TermKind::Cons {
             return false;
@@ -230,6 +260,7 @@ impl TyKind {
             | (TyKind::Function(_, _), TyKind::Function(_, _))
             | (TyKind::Pointer(_), TyKind::Pointer(_))
             | (TyKind::Record(_), TyKind::Record(_))
+            | (TyKind::Array(_), TyKind::Array(_))
             | (TyKind::AbsentField, TyKind::AbsentField) => self.arity() == other.arity(),
             _ => false,
         }
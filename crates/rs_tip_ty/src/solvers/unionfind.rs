@@ -1,6 +1,7 @@
 use crate::{TermKind, Ty, TyCtxt};
 use anyhow::{anyhow, Result};
-use std::{fmt, hash::Hash};
+use rs_tip_errors::Span;
+use std::{collections::HashMap, fmt, hash::Hash};
 
 #[derive(Debug, Clone)]
 struct UnionFind<K> {
@@ -29,6 +30,12 @@ where
         UnionFind { parent }
     }
 
+    /// Adds singleton sets until there are `n` of them.
+    fn grow(&mut self, n: usize) {
+        let len = self.parent.len();
+        self.parent.extend((len..n).map(K::new));
+    }
+
     fn find(&self, x: K) -> K {
         assert!(x.index() < self.parent.len());
         unsafe {
@@ -80,10 +87,13 @@ where
     }
 }
 
+/// # Safety
+///
+/// `index` must return the value passed to `new`, as `UnionFind` skips
+/// bounds checks on indices of sets it created itself.
 unsafe trait IndexType: Copy + Default + Hash + Ord + fmt::Debug + 'static {
     fn new(x: usize) -> Self;
     fn index(&self) -> usize;
-    fn max() -> Self;
 }
 
 unsafe impl IndexType for u32 {
@@ -95,44 +105,105 @@ unsafe impl IndexType for u32 {
     fn index(&self) -> usize {
         *self as usize
     }
-    #[inline(always)]
-    fn max() -> Self {
-        ::std::u32::MAX
-    }
 }
 
+/// Solves type equations by unification, remembering for every type
+/// variable where it was first given a type constructor.
 pub struct UnionFindSolver {
     unionfind: UnionFind<Ty>,
+    bound_at: HashMap<Ty, Span>,
+    /// Roots given a parent, so that a failed unification can be undone.
+    trail: Vec<Ty>,
 }
 
 impl UnionFindSolver {
-    pub fn unify(&mut self, interner: TyCtxt, t1: Ty, t2: Ty) -> Result<()> {
+    /// Unifies `t1` with `t2`, which were equated by the code at `span`. On
+    /// failure the solver is left as it was before the call.
+    pub fn unify(&mut self, interner: TyCtxt, t1: Ty, t2: Ty, span: Span) -> Result<()> {
+        let mark = self.trail.len();
+        let result = self.unify_terms(interner, t1, t2, span);
+        if result.is_err() {
+            for root in self.trail.drain(mark..).rev() {
+                self.unionfind.parent[root.index()] = root;
+                self.bound_at.remove(&root);
+            }
+        }
+        result
+    }
+
+    fn unify_terms(&mut self, interner: TyCtxt, t1: Ty, t2: Ty, span: Span) -> Result<()> {
+        self.unionfind.grow(t1.index().max(t2.index()) + 1);
         if self.unionfind.equiv(t1, t2) {
-            Ok(())
-        } else {
-            let t1_kind = t1.kind(interner);
-            let t2_kind = t2.kind(interner);
-            match (t1_kind.term_kind(), t2_kind.term_kind()) {
-                (TermKind::Var, TermKind::Var) | (TermKind::Var, _) => {
-                    self.unionfind.union(t1, t2);
-                }
-                (_, TermKind::Var) => {
-                    self.unionfind.union(t2, t1);
-                }
-                (TermKind::Cons, TermKind::Cons) if t1_kind.matches(&t2_kind) => {
-                    self.unionfind.union(t1, t2);
+            return Ok(());
+        }
+        // Paths are left uncompressed so that `bound_at` can follow them back
+        // to the variable that was bound.
+        let r1 = self.unionfind.find(t1);
+        let r2 = self.unionfind.find(t2);
+        let t1_kind = r1.kind(interner);
+        let t2_kind = r2.kind(interner);
+        match (t1_kind.term_kind(), t2_kind.term_kind()) {
+            (TermKind::Var, TermKind::Var) => {
+                self.union(r1, r2);
+            }
+            (TermKind::Var, _) => {
+                self.union(r1, r2);
+                self.bound_at.insert(r1, span);
+            }
+            (_, TermKind::Var) => {
+                self.union(r2, r1);
+                self.bound_at.insert(r2, span);
+            }
+            (TermKind::Cons, TermKind::Cons) if t1_kind.matches(&t2_kind) => {
+                self.union(r1, r2);
+                for (c1, c2) in t1_kind.children().into_iter().zip(t2_kind.children()) {
+                    self.unify_terms(interner, c1, c2, span)?;
                 }
-                _ => return Err(anyhow!("cannot unify {:?} and {:?}", t1_kind, t2_kind)),
-            };
-            Ok(())
+            }
+            _ => return Err(anyhow!("cannot unify {:?} and {:?}", t1_kind, t2_kind)),
+        };
+        Ok(())
+    }
+
+    /// Makes the root `r2` the parent of the root `r1`.
+    fn union(&mut self, r1: Ty, r2: Ty) {
+        self.unionfind.union(r1, r2);
+        self.trail.push(r1);
+    }
+
+    /// Returns the representative of the equivalence class of `ty`.
+    pub fn find(&self, ty: Ty) -> Ty {
+        if ty.index() < self.unionfind.parent.len() {
+            self.unionfind.find(ty)
+        } else {
+            ty
         }
     }
+
+    /// Returns the span at which the type variable `ty`, or a variable it
+    /// was unified with, was bound to the constructor it now stands for.
+    pub fn bound_at(&self, ty: Ty) -> Option<Span> {
+        let mut ty = ty;
+        while ty.index() < self.unionfind.parent.len() {
+            if let Some(&span) = self.bound_at.get(&ty) {
+                return Some(span);
+            }
+            let parent = self.unionfind.parent[ty.index()];
+            if parent == ty {
+                break;
+            }
+            ty = parent;
+        }
+        None
+    }
 }
 
 impl Default for UnionFindSolver {
     fn default() -> UnionFindSolver {
         Self {
-            unionfind: UnionFind::new(10),
+            unionfind: UnionFind::new(0),
+            bound_at: HashMap::new(),
+            trail: Vec::new(),
         }
     }
 }
@@ -147,10 +218,4 @@ unsafe impl IndexType for Ty {
     fn index(&self) -> usize {
         IndexType::index(&self.interned)
     }
-
-    fn max() -> Ty {
-        Ty {
-            interned: IndexType::max(),
-        }
-    }
 }
@@ -1 +1,2 @@
+mod codes;
 mod infer;
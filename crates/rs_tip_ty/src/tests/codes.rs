@@ -0,0 +1,54 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use rs_tip_errors::{explain, explanations, Handler, SourceMap};
+use rs_tip_syntax::{loader::load, resolve::resolve, suppress::suppressions};
+
+use crate::{infer::infer, TyCtxt, TyCtxtInner};
+
+/// Runs every pass over `source`, loaded as `main.tip`, returning the codes
+/// of the diagnostics and checking that each of them is explained.
+fn codes(source: &str) -> Vec<&'static str> {
+    let fs = HashMap::from([(PathBuf::from("main.tip"), source.to_owned())]);
+    let mut source_map = SourceMap::new();
+    let mut handler = Handler::new();
+    if let Some(prog) = load("main.tip", &fs, &mut source_map, &mut handler).unwrap() {
+        for file in source_map.files() {
+            for suppression in suppressions(file, &prog.node) {
+                handler.suppress(suppression);
+            }
+        }
+        resolve(&prog.node, &mut handler);
+        let inner = TyCtxtInner::new();
+        infer(TyCtxt::new(&inner), &prog, &mut handler);
+        handler.report_unused_suppressions();
+    }
+    handler
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| {
+            let code = diagnostic.code.expect("diagnostic without a code");
+            assert!(explain(code).is_some(), "{} has no explanation", code);
+            code
+        })
+        .collect()
+}
+
+#[test]
+fn explanation_examples() {
+    for explanation in explanations() {
+        let failing = codes(explanation.failing);
+        assert!(
+            failing.contains(&explanation.code),
+            "the failing example of {} reports {:?}",
+            explanation.code,
+            failing
+        );
+        let fixed = codes(explanation.fixed);
+        assert!(
+            fixed.is_empty(),
+            "the fixed example of {} reports {:?}",
+            explanation.code,
+            fixed
+        );
+    }
+}
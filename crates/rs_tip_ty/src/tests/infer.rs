@@ -1,3 +1,287 @@
-fn uhh() {
-    let x = (1);
+use expect_test::{expect, Expect};
+use rs_tip_errors::{Handler, Renderer, SourceMap, Span};
+
+use crate::{infer::infer, TyCtxt, TyCtxtInner};
+
+fn check(input: &str, expect: Expect) {
+    let mut handler = Handler::new();
+    let prog = rs_tip_syntax::parse(input, &mut handler).unwrap();
+    let inner = TyCtxtInner::new();
+    let tcx = TyCtxt::new(&inner);
+    let result = infer(tcx, &prog, &mut handler);
+
+    let mut out = String::new();
+    for global in &prog.node.globals {
+        let ty = result.global_to_ty[&global.node];
+        out += &format!("{}: {}\n", global.node, result.display_ty(tcx, ty));
+    }
+    for fun in &prog.node.funs {
+        let ty = result.fun_to_ty[&fun.name.node];
+        out += &format!("{}: {}\n", fun.name.node, result.display_ty(tcx, ty));
+    }
+    let mut source_map = SourceMap::new();
+    source_map.add_file("main.tip", input);
+    for diagnostic in handler.diagnostics() {
+        out += &Renderer::new(false).render(diagnostic, &source_map);
+    }
+    expect.assert_eq(&out);
+}
+
+#[test]
+fn functions() {
+    check(
+        r#"
+id(x) { return x; }
+add(a, b) { return a + b; }
+main() { var n; n = input; output add(n, 1); return 0; }
+"#,
+        expect![[r#"
+            id: (α) -> α
+            add: (int, int) -> int
+            main: () -> int
+        "#]],
+    );
+}
+
+#[test]
+fn pointers() {
+    check(
+        r#"
+deref(p) { return *p; }
+swap(p, q) { var t; t = *p; *p = *q; *q = t; return null; }
+main() { var x; x = alloc 1; x = deref(&x); return x == swap(&x, &x); }
+"#,
+        expect![[r#"
+            deref: (↑↑int) -> ↑int
+            swap: (↑↑int, ↑↑int) -> ↑int
+            main: () -> int
+        "#]],
+    );
+}
+
+#[test]
+fn records() {
+    check(
+        r#"
+mk(v) { return {value: v, next: null}; }
+get(r) { return r.value; }
+set(p) { (*p).value = 3; return 0; }
+main() { var r; r = mk(1); r.value = 2; return get(r); }
+"#,
+        expect![[r#"
+            mk: (int) -> {next: ↑α, value: int}
+            get: ({next: ↑α, value: int}) -> int
+            set: (↑{next: α, value: int}) -> int
+            main: () -> int
+        "#]],
+    );
+}
+
+#[test]
+fn arrays() {
+    check(
+        r#"
+first(a) { return a[0]; }
+set(a, i, v) { a[i] = v; return a; }
+empty() { return []; }
+main() { var a; a = set([alloc 1, null], 1, null); output *first(a); return [empty(), [1]][0][0]; }
+"#,
+        expect![[r#"
+            first: ([]↑int) -> ↑int
+            set: ([]↑int, int, ↑int) -> []↑int
+            empty: () -> []int
+            main: () -> int
+        "#]],
+    );
+    check(
+        "main() { var a; a = [1, null]; a[null] = 2; return a; }",
+        expect![[r#"
+            main: () -> []int
+            error[T0001]: mismatched types: expected `int`, found `↑α`
+             --> main.tip:1:25
+              |
+            1 | main() { var a; a = [1, null]; a[null] = 2; return a; }
+              |                      - inferred to be `int` here
+              |                         ^^^^ this has type `↑α`
+            error[T0001]: mismatched types: expected `int`, found `↑α`
+             --> main.tip:1:34
+              |
+            1 | main() { var a; a = [1, null]; a[null] = 2; return a; }
+              |                                  ^^^^ this has type `↑α`
+        "#]],
+    );
+}
+
+#[test]
+fn for_loops() {
+    check(
+        r#"
+last(p, n) { var q, i; q = p; for (i = 0; i < n; i = i + 1) { if (*q == null) { break; } q = *q; } return q; }
+main() { var p; for (p = null; p; p = p) { continue; } return 0; }
+"#,
+        expect![[r#"
+            last: (μα.↑α, int) -> μβ.↑β
+            main: () -> int
+            error[T0001]: mismatched types: expected `int`, found `↑α`
+             --> main.tip:3:32
+              |
+            3 | main() { var p; for (p = null; p; p = p) { continue; } return 0; }
+              |                          ---- inferred to be `↑α` here
+              |                                ^ this has type `↑α`
+        "#]],
+    );
+}
+
+#[test]
+fn returns() {
+    check(
+        r#"
+deref(p) { if (p == null) { return 0; } return *p; }
+choose(n, p) { if (n) { return p; } else { return null; } }
+bad(n) { if (n) { return alloc n; } return n; }
+"#,
+        expect![[r#"
+            deref: (↑int) -> int
+            choose: (int, ↑α) -> ↑α
+            bad: (int) -> ↑int
+            error[T0001]: mismatched types: expected `↑int`, found `int`
+             --> main.tip:4:44
+              |
+            4 | bad(n) { if (n) { return alloc n; } return n; }
+              |              - inferred to be `int` here
+              |                          ------- inferred to be `↑int` here
+              |                                            ^ this has type `int`
+        "#]],
+    );
+}
+
+#[test]
+fn globals() {
+    check(
+        r#"
+var count, last;
+push(p) { count = count + 1; last = p; return 0; }
+shadow(last) { return last + 1; }
+main() { var x; x = push(&count); output *last; return count; }
+"#,
+        expect![[r#"
+            count: int
+            last: ↑int
+            push: (↑int) -> int
+            shadow: (int) -> int
+            main: () -> int
+        "#]],
+    );
+}
+
+#[test]
+fn recursive_types() {
+    check(
+        r#"
+list(n) { var l; l = null; while (n > 0) { l = alloc l; n = n - 1; } return l; }
+"#,
+        expect![[r#"
+            list: (int) -> μα.↑α
+        "#]],
+    );
+}
+
+#[test]
+fn mismatch_shows_both_locations() {
+    check(
+        r#"main() {
+    var x;
+    x = 1;
+    output x;
+    x = alloc 1;
+    return 0;
+}
+"#,
+        expect![[r#"
+            main: () -> int
+            error[T0001]: mismatched types: expected `int`, found `↑int`
+             --> main.tip:5:9
+              |
+            3 |     x = 1;
+              |         - inferred to be `int` here
+            ...
+            5 |     x = alloc 1;
+              |         ^^^^^^^ this has type `↑int`
+        "#]],
+    );
+}
+
+#[test]
+fn mismatch_through_calls() {
+    check(
+        r#"f(p) { return *p; }
+main() {
+    var y;
+    y = f(1);
+    if (y) { output y; }
+    return 0;
+}
+"#,
+        expect![[r#"
+            f: (↑α) -> α
+            main: () -> int
+            error[T0001]: mismatched types: expected `(↑α) -> α`, found `(int) -> β`
+             --> main.tip:4:9
+              |
+            1 | f(p) { return *p; }
+              | - inferred to be `(↑α) -> α` here
+            ...
+            4 |     y = f(1);
+              |         ^^^^ this has type `(int) -> β`
+        "#]],
+    );
+}
+
+#[test]
+fn generated_programs_type_check() {
+    use rs_tip_syntax::{
+        ast::IntoSpanned,
+        gen::{generate, GenConfig},
+        normalize::normalize,
+        printer::print_prog,
+    };
+
+    let config = GenConfig::default();
+    for seed in 0..200 {
+        let prog = generate(seed, &config);
+        // Normalization only introduces temporaries, which get the types of
+        // the expressions they hold.
+        let normalized = normalize(&prog).prog;
+        for prog in [prog, normalized] {
+            let prog = prog.into_spanned(Span::new(0, 0));
+            let inner = TyCtxtInner::new();
+            let mut diagnostics = Vec::new();
+            infer(TyCtxt::new(&inner), &prog, &mut diagnostics);
+            assert!(
+                diagnostics.is_empty(),
+                "{:?} in\n{}",
+                diagnostics,
+                print_prog(&prog)
+            );
+        }
+    }
+}
+
+#[test]
+fn undefined_variables() {
+    check(
+        r#"
+f() { return x + x; }
+main() { var y; y = alloc 1; return f() + *y; }
+"#,
+        expect![[r#"
+            f: () -> int
+            main: () -> int
+            error[T0002]: undefined variable: x
+             --> main.tip:2:14
+              |
+            2 | f() { return x + x; }
+              |              ^
+        "#]],
+    );
 }